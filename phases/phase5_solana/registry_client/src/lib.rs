@@ -0,0 +1,740 @@
+// Client helpers for the key registry program: PDA derivation, instruction
+// builders and typed account fetching on top of `RpcClient`.
+
+use anchor_lang::{AccountDeserialize, AnchorDeserialize, Discriminator, InstructionData, ToAccountMetas};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use key_registry::{Config, KeyRecord, KeyType, ProgramInfo};
+use solana_account_decoder::{UiAccountEncoding, UiDataSliceConfig};
+use solana_client::client_error::ClientError;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSimulateTransactionConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::ed25519_program;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::system_program;
+use solana_sdk::sysvar;
+use solana_sdk::transaction::Transaction;
+use solana_transaction_status::UiReturnDataEncoding;
+
+pub use key_registry::ID as PROGRAM_ID;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryClientError {
+    #[error("RPC request failed: {0}")]
+    Rpc(Box<ClientError>),
+    #[error("could not decode registry account: {0}")]
+    Decode(#[from] anchor_lang::error::Error),
+}
+
+impl From<ClientError> for RegistryClientError {
+    fn from(err: ClientError) -> Self {
+        RegistryClientError::Rpc(Box::new(err))
+    }
+}
+
+pub type Result<T> = std::result::Result<T, RegistryClientError>;
+
+// The default registry's id (see `key_registry::registry_seed`).
+pub const DEFAULT_REGISTRY: [u8; 8] = [0u8; 8];
+
+// PDA of the key record `owner` registered without a label in the default
+// registry.
+pub fn derive_key_record(owner: &Pubkey) -> (Pubkey, u8) {
+    derive_labeled_key_record(&DEFAULT_REGISTRY, owner, "")
+}
+
+// PDA of the key record `owner` registered under `label` in `registry_id`.
+pub fn derive_labeled_key_record(registry_id: &[u8; 8], owner: &Pubkey, label: &str) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"key_record",
+            key_registry::registry_seed(registry_id),
+            owner.as_ref(),
+            label.as_bytes(),
+        ],
+        &PROGRAM_ID,
+    )
+}
+
+// PDA of the reverse index entry for `public_key` in `registry_id`.
+pub fn derive_key_index(registry_id: &[u8; 8], public_key: &[u8]) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            b"key_index",
+            key_registry::registry_seed(registry_id),
+            key_registry::key_seed(public_key, 0),
+            key_registry::key_seed(public_key, 1),
+        ],
+        &PROGRAM_ID,
+    )
+}
+
+// PDA of `owner`'s settings (guardians, update cooldown).
+pub fn derive_settings(owner: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"settings", owner.as_ref()], &PROGRAM_ID)
+}
+
+// PDA Anchor's `emit_cpi!` signs event self-CPIs with; instructions that
+// emit CPI events (registration, revocation) take it with the program.
+pub fn derive_event_authority() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"__event_authority"], &PROGRAM_ID)
+}
+
+// PDA of the registry config.
+pub fn derive_config() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"config"], &PROGRAM_ID)
+}
+
+// An Ed25519 native program instruction checking `signature` by `public_key`
+// over `message`, with all data inline. Registry instructions that need a
+// signature check expect it immediately before them in the transaction.
+pub fn ed25519_verify_instruction(public_key: &[u8; 32], message: &[u8], signature: &[u8; 64]) -> Instruction {
+    // header (count + padding) and one offsets entry, then the data
+    let key_at: u16 = 2 + 14;
+    let sig_at = key_at + 32;
+    let msg_at = sig_at + 64;
+    let this_ix = u16::MAX;
+
+    let mut data = vec![1u8, 0];
+    for field in [sig_at, this_ix, key_at, this_ix, msg_at, message.len() as u16, this_ix] {
+        data.extend_from_slice(&field.to_le_bytes());
+    }
+    data.extend_from_slice(public_key);
+    data.extend_from_slice(signature);
+    data.extend_from_slice(message);
+
+    Instruction {
+        program_id: ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+// Instructions registering `public_key` for `owner` under `label` in
+// `registry_id` (`DEFAULT_REGISTRY` unless running several). For
+// Ed25519 keys `proof` must be the key's signature over the owner's pubkey
+// bytes, and the matching precompile instruction is prepended. `usage_flags`
+// takes `KeyRecord::USAGE_*` bits; `email_hash` is all zeros and `uri` empty
+// for none. `history_capacity` is `KeyRecord::HISTORY_LEN` for the default
+// history ring. `treasury` is the config's fee treasury (see `fetch_config`).
+#[allow(clippy::too_many_arguments)]
+pub fn register_key_instructions(
+    registry_id: &[u8; 8],
+    owner: &Pubkey,
+    treasury: &Pubkey,
+    label: &str,
+    key_type: KeyType,
+    public_key: &[u8],
+    proof: [u8; 64],
+    expires_at: i64,
+    usage_flags: u8,
+    email_hash: [u8; 32],
+    uri: &str,
+    history_capacity: u8,
+) -> Vec<Instruction> {
+    let accounts = key_registry::accounts::RegisterKey {
+        owner: *owner,
+        key_record: derive_labeled_key_record(registry_id, owner, label).0,
+        key_index: derive_key_index(registry_id, public_key).0,
+        handle_index: None,
+        config: derive_config().0,
+        treasury: *treasury,
+        instructions: sysvar::instructions::ID,
+        system_program: system_program::ID,
+        event_authority: derive_event_authority().0,
+        program: PROGRAM_ID,
+    };
+    let args = key_registry::instruction::RegisterKey {
+        registry_id: *registry_id,
+        label: label.to_string(),
+        key_type,
+        public_key: public_key.to_vec(),
+        signature: proof,
+        expires_at,
+        handle: [0u8; 32],
+        usage_flags,
+        email_hash,
+        uri: uri.to_string(),
+        history_capacity,
+    };
+
+    let mut instructions = Vec::new();
+    if key_type == KeyType::Ed25519 {
+        if let Ok(key) = <&[u8; 32]>::try_from(public_key) {
+            instructions.push(ed25519_verify_instruction(key, owner.as_ref(), &proof));
+        }
+    }
+    instructions.push(Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    });
+    instructions
+}
+
+// Instruction replacing the key of an existing record in `registry_id`.
+// `current_key` is the record's key today, whose reverse index entry moves to
+// `new_public_key`; `owner` is its current owner, whose settings are checked.
+#[allow(clippy::too_many_arguments)]
+pub fn update_key_instruction(
+    registry_id: &[u8; 8],
+    authority: &Pubkey,
+    owner: &Pubkey,
+    registrant: &Pubkey,
+    label: &str,
+    current_key: &[u8],
+    new_public_key: &[u8],
+    expires_at: i64,
+) -> Instruction {
+    let accounts = key_registry::accounts::UpdateKey {
+        authority: *authority,
+        key_record: derive_labeled_key_record(registry_id, registrant, label).0,
+        old_key_index: derive_key_index(registry_id, current_key).0,
+        new_key_index: derive_key_index(registry_id, new_public_key).0,
+        config: derive_config().0,
+        settings: derive_settings(owner).0,
+        system_program: system_program::ID,
+    };
+    let args = key_registry::instruction::UpdateKey {
+        label: label.to_string(),
+        new_public_key: new_public_key.to_vec(),
+        expires_at,
+    };
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+// Instruction failing with AlreadyRegistered if `owner` has a key record
+// under `label` in `registry_id`; add it to a transaction to make the rest
+// of it first-registration-only.
+pub fn assert_not_registered_instruction(registry_id: &[u8; 8], owner: &Pubkey, label: &str) -> Instruction {
+    let accounts = key_registry::accounts::MaybeKey {
+        key_record: derive_labeled_key_record(registry_id, owner, label).0,
+    };
+    let args = key_registry::instruction::AssertNotRegistered {
+        _registry_id: *registry_id,
+        owner: *owner,
+        label: label.to_string(),
+    };
+
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: accounts.to_account_metas(None),
+        data: args.data(),
+    }
+}
+
+// Whether `owner` has a key record under `label` in `registry_id`, by the
+// same rule as `assert_not_registered` (funded but empty addresses are not).
+pub async fn is_registered(client: &RpcClient, registry_id: &[u8; 8], owner: &Pubkey, label: &str) -> Result<bool> {
+    let address = derive_labeled_key_record(registry_id, owner, label).0;
+    let account = client.get_account_with_commitment(&address, client.commitment()).await?.value;
+    Ok(account.is_some_and(|account| !account.data.is_empty() || account.owner == PROGRAM_ID))
+}
+
+// Fetch and decode the registry config.
+pub async fn fetch_config(client: &RpcClient) -> Result<Config> {
+    let data = client.get_account_data(&derive_config().0).await?;
+    Ok(Config::try_deserialize(&mut data.as_slice())?)
+}
+
+// A key record as found on chain: either in the current layout or in an
+// older one that `migrate` has not rewritten yet.
+pub enum KeyRecordView {
+    Current(KeyRecord),
+    // decoded as `migrate` would upgrade it, with `status` left zero;
+    // `version` is the layout found. Writes fail until it is migrated.
+    Legacy { version: u8, record: KeyRecord },
+}
+
+impl KeyRecordView {
+    pub fn record(&self) -> &KeyRecord {
+        match self {
+            KeyRecordView::Current(record) | KeyRecordView::Legacy { record, .. } => record,
+        }
+    }
+
+    pub fn into_record(self) -> KeyRecord {
+        match self {
+            KeyRecordView::Current(record) | KeyRecordView::Legacy { record, .. } => record,
+        }
+    }
+}
+
+// Decode key record account data of any known layout version, reading the
+// version first (see `KeyRecord::upgrade_layout`).
+pub fn decode_key_record(data: &[u8]) -> Result<KeyRecordView> {
+    let (version, body) = KeyRecord::upgrade_layout(data)?;
+    if version == KeyRecord::CURRENT_VERSION && data.len() >= key_registry::ACCOUNT_SIZE {
+        return Ok(KeyRecordView::Current(KeyRecord::try_deserialize(&mut &data[..])?));
+    }
+    let record = KeyRecord::deserialize(&mut body.as_slice())
+        .map_err(|_| anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::AccountDidNotDeserialize))?;
+    Ok(if version == KeyRecord::CURRENT_VERSION {
+        // current layout, only short of fields appended since it was written
+        KeyRecordView::Current(record)
+    } else {
+        KeyRecordView::Legacy { version, record }
+    })
+}
+
+// Fetch and decode the key record at `address`, whatever its layout version.
+pub async fn fetch_key_record(client: &RpcClient, address: &Pubkey) -> Result<KeyRecordView> {
+    let data = client.get_account_data(address).await?;
+    decode_key_record(&data)
+}
+
+// A key record's packed `status` (see `KeyRecord::STATUS_*`), decoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecordStatus {
+    pub revoked: bool,
+    pub frozen: bool,
+    pub expired: bool, // as of the record's last write, not necessarily now
+    pub usage_flags: u8,
+}
+
+impl RecordStatus {
+    pub fn from_bits(status: u16) -> Self {
+        RecordStatus {
+            revoked: status & KeyRecord::STATUS_REVOKED != 0,
+            frozen: status & KeyRecord::STATUS_FROZEN != 0,
+            expired: status & KeyRecord::STATUS_EXPIRED != 0,
+            usage_flags: (status >> KeyRecord::STATUS_USAGE_SHIFT) as u8,
+        }
+    }
+}
+
+// Decode the status from the first `KeyRecord::STATUS_OFFSET + 2` bytes of
+// a record account, or None if they are missing or not a current-version
+// key record.
+pub fn decode_status(data: &[u8]) -> Option<RecordStatus> {
+    let at = KeyRecord::STATUS_OFFSET;
+    if data.len() < at + 2 || data[..8] != KeyRecord::DISCRIMINATOR || data[8] != KeyRecord::CURRENT_VERSION {
+        return None;
+    }
+    Some(RecordStatus::from_bits(u16::from_le_bytes([data[at], data[at + 1]])))
+}
+
+// Fetch only the leading bytes of the record at `address` and decode its
+// status, without downloading the whole account.
+pub async fn fetch_status(client: &RpcClient, address: &Pubkey) -> Result<Option<RecordStatus>> {
+    let config = RpcAccountInfoConfig {
+        encoding: Some(UiAccountEncoding::Base64),
+        data_slice: Some(UiDataSliceConfig {
+            offset: 0,
+            length: KeyRecord::STATUS_OFFSET + 2,
+        }),
+        ..RpcAccountInfoConfig::default()
+    };
+    let account = client.get_account_with_config(address, config).await?.value;
+    Ok(account.and_then(|account| decode_status(&account.data)))
+}
+
+// Where the owner field sits in account data of each layout: right after
+// the discriminator in v0 and v1, after the version byte in v2, and after
+// version and status from v3 on.
+const OWNER_OFFSETS: [usize; 3] = [8, 8 + 1, KeyRecord::OWNER_OFFSET];
+
+// Every key record `owner` currently owns, across labels and layout
+// versions, via `getProgramAccounts` filtered on the discriminator and on
+// the owner field at each layout's offset. Records are decoded with
+// `decode_key_record`, so ones not yet migrated come back as `Legacy`;
+// accounts that match the filters but don't decode are skipped, so one
+// corrupt account can't hide the rest.
+pub async fn list_records(client: &RpcClient, owner: &Pubkey) -> Result<Vec<(Pubkey, KeyRecordView)>> {
+    let mut records: Vec<(Pubkey, KeyRecordView)> = Vec::new();
+    for offset in OWNER_OFFSETS {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(0, &KeyRecord::DISCRIMINATOR)),
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(offset, owner.as_ref())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..RpcAccountInfoConfig::default()
+            },
+            ..RpcProgramAccountsConfig::default()
+        };
+        for (address, account) in client.get_program_accounts_with_config(&PROGRAM_ID, config).await? {
+            // an offset can match bytes of another layout, so keep only
+            // records whose decoded owner is `owner`, once each
+            let Ok(view) = decode_key_record(&account.data) else {
+                continue;
+            };
+            if view.record().owner == *owner && !records.iter().any(|(seen, _)| *seen == address) {
+                records.push((address, view));
+            }
+        }
+    }
+    Ok(records)
+}
+
+// Instruction whose return data is the deployment's `ProgramInfo`.
+pub fn program_info_instruction() -> Instruction {
+    Instruction {
+        program_id: PROGRAM_ID,
+        accounts: key_registry::accounts::GetProgramInfo {}.to_account_metas(None),
+        data: key_registry::instruction::ProgramInfo {}.data(),
+    }
+}
+
+// Decode `program_info` return data.
+pub fn decode_program_info(data: &[u8]) -> Result<ProgramInfo> {
+    Ok(ProgramInfo::deserialize(&mut &data[..]).map_err(anchor_lang::error::Error::from)?)
+}
+
+// Simulate `program_info` with `payer` as fee payer (no signature or funds
+// needed) and decode what the deployed program reports; None if it set no
+// return data, e.g. a deployment that predates the instruction.
+pub async fn fetch_program_info(client: &RpcClient, payer: &Pubkey) -> Result<Option<ProgramInfo>> {
+    let transaction = Transaction::new_with_payer(&[program_info_instruction()], Some(payer));
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        ..RpcSimulateTransactionConfig::default()
+    };
+    let result = client.simulate_transaction_with_config(&transaction, config).await?.value;
+    let Some(return_data) = result.return_data.filter(|data| data.program_id == PROGRAM_ID.to_string()) else {
+        return Ok(None);
+    };
+    let (encoded, UiReturnDataEncoding::Base64) = return_data.data;
+    let data = BASE64_STANDARD
+        .decode(encoded)
+        .map_err(|_| anchor_lang::error::Error::from(anchor_lang::error::ErrorCode::InstructionDidNotDeserialize))?;
+    Ok(Some(decode_program_info(&data)?))
+}
+
+// Register `public_key` for `owner` (see `register_key_instructions`).
+#[allow(clippy::too_many_arguments)]
+pub async fn register_key(
+    client: &RpcClient,
+    registry_id: &[u8; 8],
+    owner: &Keypair,
+    label: &str,
+    key_type: KeyType,
+    public_key: &[u8],
+    proof: [u8; 64],
+    expires_at: i64,
+    usage_flags: u8,
+    email_hash: [u8; 32],
+    uri: &str,
+    history_capacity: u8,
+) -> Result<Signature> {
+    let config = fetch_config(client).await?;
+    let instructions = register_key_instructions(
+        registry_id,
+        &owner.pubkey(),
+        &config.treasury,
+        label,
+        key_type,
+        public_key,
+        proof,
+        expires_at,
+        usage_flags,
+        email_hash,
+        uri,
+        history_capacity,
+    );
+    send(client, owner, &instructions).await
+}
+
+// Replace the key of the record `registrant` created under `label` in
+// `registry_id`; the signer must be its owner or delegate.
+pub async fn update_key(
+    client: &RpcClient,
+    registry_id: &[u8; 8],
+    authority: &Keypair,
+    registrant: &Pubkey,
+    label: &str,
+    new_public_key: &[u8],
+    expires_at: i64,
+) -> Result<Signature> {
+    let record = fetch_key_record(client, &derive_labeled_key_record(registry_id, registrant, label).0)
+        .await?
+        .into_record();
+    let instruction = update_key_instruction(
+        registry_id,
+        &authority.pubkey(),
+        &record.owner,
+        registrant,
+        label,
+        record.key_bytes(),
+        new_public_key,
+        expires_at,
+    );
+    send(client, authority, &[instruction]).await
+}
+
+async fn send(client: &RpcClient, payer: &Keypair, instructions: &[Instruction]) -> Result<Signature> {
+    let blockhash = client.get_latest_blockhash().await?;
+    let transaction =
+        Transaction::new_signed_with_payer(instructions, Some(&payer.pubkey()), &[payer], blockhash);
+    Ok(client.send_and_confirm_transaction(&transaction).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::{AccountSerialize, AnchorSerialize};
+    use solana_client::rpc_request::RpcRequest;
+
+    const OWNER: Pubkey = Pubkey::new_from_array([7u8; 32]);
+    const REGISTRANT: Pubkey = Pubkey::new_from_array([9u8; 32]);
+
+    fn key() -> Vec<u8> {
+        (1..=32u8).collect()
+    }
+
+    // The fields v1 and v2 share, owner through registrant, label "work".
+    fn v1_fields() -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(OWNER.as_ref());
+        body.push(KeyType::Ed25519 as u8);
+        body.push(32);
+        let mut public_key = key();
+        public_key.resize(64, 0);
+        body.extend_from_slice(&public_key);
+        body.push(254); // bump
+        body.push(0); // revoked
+        body.extend_from_slice(&[0u8; 64 * 5]); // history
+        body.push(0); // history_head
+        body.extend_from_slice(&0i64.to_le_bytes()); // expires_at
+        body.extend_from_slice(&[0u8; 32]); // delegate
+        "work".to_string().serialize(&mut body).unwrap();
+        body.extend_from_slice(REGISTRANT.as_ref());
+        body
+    }
+
+    #[test]
+    fn decodes_v0_record() {
+        let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(OWNER.as_ref());
+        data.extend_from_slice(&key());
+        data.push(253);
+
+        let KeyRecordView::Legacy { version, record } = decode_key_record(&data).unwrap() else {
+            panic!("v0 record decoded as current");
+        };
+        assert_eq!(version, 0);
+        assert_eq!(record.owner, OWNER);
+        assert_eq!(record.registrant, OWNER);
+        assert_eq!(record.key_bytes(), key().as_slice());
+        assert_eq!(record.bump, 253);
+    }
+
+    #[test]
+    fn decodes_v1_record() {
+        let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&v1_fields());
+        data.resize(8 + KeyRecord::V1_LEN, 0);
+
+        let KeyRecordView::Legacy { version, record } = decode_key_record(&data).unwrap() else {
+            panic!("v1 record decoded as current");
+        };
+        assert_eq!(version, 1);
+        assert_eq!(record.owner, OWNER);
+        assert_eq!(record.key_bytes(), key().as_slice());
+        assert_eq!(record.bump, 254);
+        assert_eq!(record.label, "work");
+        assert_eq!(record.registrant, REGISTRANT);
+    }
+
+    #[test]
+    fn decodes_v2_record() {
+        let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+        data.push(2);
+        data.extend_from_slice(&v1_fields());
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes()); // created_at
+        data.resize(8 + KeyRecord::V1_LEN + 64, 0);
+
+        let KeyRecordView::Legacy { version, record } = decode_key_record(&data).unwrap() else {
+            panic!("v2 record decoded as current");
+        };
+        assert_eq!(version, 2);
+        assert_eq!(record.owner, OWNER);
+        assert_eq!(record.label, "work");
+        assert_eq!(record.registrant, REGISTRANT);
+        assert_eq!(record.created_at, 1_700_000_000);
+        assert_eq!(record.status, 0);
+    }
+
+    #[test]
+    fn decodes_v3_record() {
+        // v3 still carried `delegate`, `guardians` and `guardian_threshold`
+        let mut fields = v1_fields();
+        let delegate_at = fields.len() - 32 - (4 + "work".len()) - 32;
+        fields[delegate_at..delegate_at + 32].copy_from_slice(&[7u8; 32]);
+        let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[3, 0, 0]);
+        data.extend_from_slice(&fields);
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes()); // created_at
+        data.extend_from_slice(&1_700_000_001i64.to_le_bytes()); // updated_at
+        data.extend_from_slice(&[9u8; 32 * 5 + 1]); // guardians + guardian_threshold
+        data.extend_from_slice(b"alice"); // handle
+        data.resize(8 + KeyRecord::LEN + 32 * 6 + 1, 0);
+
+        let KeyRecordView::Legacy { version, record } = decode_key_record(&data).unwrap() else {
+            panic!("v3 record decoded as current");
+        };
+        assert_eq!(version, 3);
+        assert_eq!(record.owner, OWNER);
+        assert_eq!(record.label, "work");
+        assert_eq!(record.registrant, REGISTRANT);
+        assert_eq!(record.updated_at, 1_700_000_001);
+        assert_eq!(&record.handle[..5], b"alice");
+        assert!(!record.frozen);
+    }
+
+    #[test]
+    fn decodes_current_record() {
+        let mut record = KeyRecord::zeroed();
+        record.version = KeyRecord::CURRENT_VERSION;
+        record.owner = OWNER;
+        record.set_key(&key());
+        let mut data = Vec::new();
+        record.try_serialize(&mut data).unwrap();
+        data.resize(key_registry::ACCOUNT_SIZE, 0);
+
+        let KeyRecordView::Current(decoded) = decode_key_record(&data).unwrap() else {
+            panic!("current record decoded as legacy");
+        };
+        assert_eq!(decoded.owner, OWNER);
+        assert_eq!(decoded.key_bytes(), key().as_slice());
+    }
+
+    #[test]
+    fn status_round_trips_each_flag() {
+        let off = RecordStatus { revoked: false, frozen: false, expired: false, usage_flags: 0 };
+        let mut record = KeyRecord::zeroed();
+        record.refresh_status(0, 0);
+        assert_eq!(RecordStatus::from_bits(record.status), off);
+
+        record.revoked = true;
+        record.refresh_status(0, 0);
+        assert_eq!(RecordStatus::from_bits(record.status), RecordStatus { revoked: true, ..off });
+        record.revoked = false;
+        record.frozen = true;
+        record.refresh_status(0, 0);
+        assert_eq!(RecordStatus::from_bits(record.status), RecordStatus { frozen: true, ..off });
+        record.frozen = false;
+        record.expires_at = 1;
+        record.refresh_status(1, 0);
+        assert_eq!(RecordStatus::from_bits(record.status), RecordStatus { expired: true, ..off });
+        record.refresh_status(0, 0);
+        assert_eq!(RecordStatus::from_bits(record.status), off);
+
+        for usage_flags in [KeyRecord::USAGE_SIGN, KeyRecord::USAGE_ENCRYPT, KeyRecord::USAGE_AUTH, u8::MAX] {
+            record.usage_flags = usage_flags;
+            record.refresh_status(0, 0);
+            assert_eq!(RecordStatus::from_bits(record.status), RecordStatus { usage_flags, ..off });
+        }
+        record.usage_flags = 0;
+        record.refresh_status(0, 0);
+        assert_eq!(RecordStatus::from_bits(record.status), off);
+    }
+
+    #[test]
+    fn owner_offsets_match_each_layout() {
+        let mut v0 = KeyRecord::DISCRIMINATOR.to_vec();
+        v0.extend_from_slice(OWNER.as_ref());
+        let mut v2 = KeyRecord::DISCRIMINATOR.to_vec();
+        v2.push(2);
+        v2.extend_from_slice(OWNER.as_ref());
+        let mut current = KeyRecord::DISCRIMINATOR.to_vec();
+        current.extend_from_slice(&[KeyRecord::CURRENT_VERSION, 0, 0]);
+        current.extend_from_slice(OWNER.as_ref());
+
+        for (data, offset) in [(v0, OWNER_OFFSETS[0]), (v2, OWNER_OFFSETS[1]), (current, OWNER_OFFSETS[2])] {
+            assert_eq!(&data[offset..offset + 32], OWNER.as_ref());
+        }
+    }
+
+    // getProgramAccounts answering with the given accounts of the program
+    fn keyed_accounts(accounts: &[(Pubkey, Vec<u8>)]) -> serde_json::Value {
+        let accounts: Vec<_> = accounts
+            .iter()
+            .map(|(address, data)| {
+                serde_json::json!({
+                    "pubkey": address.to_string(),
+                    "account": {
+                        "lamports": 1_000_000,
+                        "data": [BASE64_STANDARD.encode(data), "base64"],
+                        "owner": PROGRAM_ID.to_string(),
+                        "executable": false,
+                        "rentEpoch": 0,
+                        "space": data.len(),
+                    },
+                })
+            })
+            .collect();
+        serde_json::Value::Array(accounts)
+    }
+
+    #[tokio::test]
+    async fn lists_decodable_records_of_the_owner() {
+        let mut current = KeyRecord::zeroed();
+        current.version = KeyRecord::CURRENT_VERSION;
+        current.owner = OWNER;
+        current.set_key(&key());
+        current.label = "laptop".to_string();
+        let mut current_data = Vec::new();
+        current.try_serialize(&mut current_data).unwrap();
+        current_data.resize(key_registry::ACCOUNT_SIZE, 0);
+        let mut v1_data = KeyRecord::DISCRIMINATOR.to_vec();
+        v1_data.extend_from_slice(&v1_fields());
+        v1_data.resize(8 + KeyRecord::V1_LEN, 0);
+        let mut other = current.clone();
+        other.owner = REGISTRANT;
+        let mut other_data = Vec::new();
+        other.try_serialize(&mut other_data).unwrap();
+        // a record cut short, which doesn't decode
+        let corrupt_data = current_data[..KeyRecord::OWNER_OFFSET + 32].to_vec();
+
+        let (current_address, v1_address) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let accounts = [
+            (current_address, current_data),
+            (Pubkey::new_unique(), corrupt_data),
+            (v1_address, v1_data),
+            (Pubkey::new_unique(), other_data),
+        ];
+        // the mock answers the first owner offset's query; later ones get its
+        // default, an account without data, which is skipped as well
+        let mocks = [(RpcRequest::GetProgramAccounts, keyed_accounts(&accounts))].into();
+        let client = RpcClient::new_mock_with_mocks("succeeds".to_string(), mocks);
+
+        let records = list_records(&client, &OWNER).await.unwrap();
+        assert_eq!(records.len(), 2);
+        let (address, KeyRecordView::Current(record)) = &records[0] else {
+            panic!("current record listed as legacy");
+        };
+        assert_eq!(*address, current_address);
+        assert_eq!(record.label, "laptop");
+        assert_eq!(record.key_bytes(), key().as_slice());
+        let (address, KeyRecordView::Legacy { version: 1, record }) = &records[1] else {
+            panic!("v1 record not listed as legacy v1");
+        };
+        assert_eq!(*address, v1_address);
+        assert_eq!(record.owner, OWNER);
+        assert_eq!(record.label, "work");
+    }
+
+    #[test]
+    fn decodes_program_info() {
+        let info = ProgramInfo::current();
+        assert_eq!(decode_program_info(&info.try_to_vec().unwrap()).unwrap(), info);
+        assert!(decode_program_info(&[]).is_err());
+    }
+
+    #[test]
+    fn rejects_truncated_record() {
+        let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&v1_fields()[..100]);
+        assert!(decode_key_record(&data).is_err());
+    }
+}
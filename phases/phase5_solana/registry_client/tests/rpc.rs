@@ -0,0 +1,188 @@
+// The RPC helpers end to end: register_key, update_key and fetch_key_record
+// run against a local bank with the registry loaded, through an `RpcSender`
+// that answers the JSON-RPC methods they use from a `BanksClient`.
+
+use std::sync::Mutex;
+
+use anchor_lang::{InstructionData, ToAccountMetas};
+use async_trait::async_trait;
+use base64::prelude::{Engine, BASE64_STANDARD};
+use key_registry::{KeyRecord, KeyRegistryError, KeyType};
+use registry_client::{
+    derive_config, derive_labeled_key_record, fetch_key_record, register_key, update_key,
+    KeyRecordView, PROGRAM_ID,
+};
+use serde_json::{json, Value};
+use solana_account_decoder::{UiAccount, UiAccountEncoding};
+use solana_client::client_error::{ClientError, Result as ClientResult};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_client::RpcClientConfig;
+use solana_client::rpc_request::{RpcError, RpcRequest};
+use solana_client::rpc_response::{Response, RpcBlockhash, RpcResponseContext, RpcVersionInfo};
+use solana_client::rpc_sender::{RpcSender, RpcTransportStats};
+use solana_program_test::{processor, BanksClient, ProgramTest};
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::commitment_config::CommitmentLevel;
+use solana_sdk::entrypoint::ProgramResult;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+const REGISTRY: [u8; 8] = [0u8; 8];
+
+// A distinct, non-degenerate 32-byte key per seed.
+fn test_key(seed: u8) -> Vec<u8> {
+    (0..32u8).map(|i| i.wrapping_mul(7).wrapping_add(seed)).collect()
+}
+
+// Anchor's entry wants the accounts for 'info, which the processor! shim
+// can't name; each instruction leaks one small Vec, fine for a test run.
+fn entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    key_registry::entry(program_id, accounts, data)
+}
+
+fn rpc_error(err: impl std::fmt::Display) -> ClientError {
+    RpcError::ForUser(err.to_string()).into()
+}
+
+// Serves getVersion, getLatestBlockhash, sendTransaction,
+// getSignatureStatuses and getAccountInfo from the bank; anything else is
+// an error, so a helper that starts calling more shows up here.
+struct BanksSender {
+    banks: Mutex<BanksClient>,
+}
+
+#[async_trait]
+impl RpcSender for BanksSender {
+    async fn send(&self, request: RpcRequest, params: Value) -> ClientResult<Value> {
+        let mut banks = self.banks.lock().unwrap().clone();
+        let context = RpcResponseContext { slot: 0, api_version: None };
+        let value = match request {
+            RpcRequest::GetVersion => json!(RpcVersionInfo {
+                solana_core: "1.18.26".to_string(),
+                feature_set: None,
+            }),
+            RpcRequest::GetLatestBlockhash => {
+                let (blockhash, last_valid_block_height) = banks
+                    .get_latest_blockhash_with_commitment(CommitmentLevel::default())
+                    .await
+                    .map_err(rpc_error)?
+                    .ok_or_else(|| rpc_error("no blockhash"))?;
+                let blockhash = RpcBlockhash { blockhash: blockhash.to_string(), last_valid_block_height };
+                json!(Response { context, value: blockhash })
+            }
+            RpcRequest::SendTransaction => {
+                let encoded = params[0].as_str().ok_or_else(|| rpc_error("missing transaction"))?;
+                let bytes = BASE64_STANDARD.decode(encoded).map_err(rpc_error)?;
+                let transaction: Transaction = bincode::deserialize(&bytes).map_err(rpc_error)?;
+                let signature = transaction.signatures[0];
+                banks.process_transaction(transaction).await.map_err(rpc_error)?;
+                json!(signature.to_string())
+            }
+            RpcRequest::GetSignatureStatuses => {
+                let mut statuses = Vec::new();
+                for signature in params[0].as_array().ok_or_else(|| rpc_error("missing signatures"))? {
+                    let signature: Signature =
+                        signature.as_str().unwrap_or_default().parse().map_err(rpc_error)?;
+                    let status = banks.get_transaction_status(signature).await.map_err(rpc_error)?;
+                    statuses.push(status.map(|status| TransactionStatus {
+                        slot: status.slot,
+                        confirmations: status.confirmations,
+                        status: status.err.clone().map_or(Ok(()), Err),
+                        err: status.err,
+                        confirmation_status: Some(TransactionConfirmationStatus::Finalized),
+                    }));
+                }
+                json!(Response { context, value: statuses })
+            }
+            RpcRequest::GetAccountInfo => {
+                let address: Pubkey = params[0].as_str().unwrap_or_default().parse().map_err(rpc_error)?;
+                let account = banks.get_account(address).await.map_err(rpc_error)?;
+                let account = account
+                    .map(|account| UiAccount::encode(&address, &account, UiAccountEncoding::Base64, None, None));
+                json!(Response { context, value: account })
+            }
+            _ => return Err(rpc_error(format!("{request} is not served by BanksSender"))),
+        };
+        Ok(value)
+    }
+
+    fn get_transport_stats(&self) -> RpcTransportStats {
+        RpcTransportStats::default()
+    }
+
+    fn url(&self) -> String {
+        "banks".to_string()
+    }
+}
+
+// A bank with the registry's config initialized, an `RpcClient` in front of
+// it, and a funded owner.
+async fn start() -> (RpcClient, Keypair) {
+    let test = ProgramTest::new("key_registry", PROGRAM_ID, processor!(entry));
+    let (mut banks, payer, blockhash) = test.start().await;
+    let owner = Keypair::new();
+    let initialize = Instruction {
+        program_id: PROGRAM_ID,
+        accounts: key_registry::accounts::InitializeConfig {
+            admin: payer.pubkey(),
+            config: derive_config().0,
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::InitializeConfig {}.data(),
+    };
+    let fund = system_instruction::transfer(&payer.pubkey(), &owner.pubkey(), 1_000_000_000);
+    let transaction =
+        Transaction::new_signed_with_payer(&[initialize, fund], Some(&payer.pubkey()), &[&payer], blockhash);
+    banks.process_transaction(transaction).await.unwrap();
+
+    let sender = BanksSender { banks: Mutex::new(banks) };
+    (RpcClient::new_sender(sender, RpcClientConfig::default()), owner)
+}
+
+#[tokio::test]
+async fn register_update_and_fetch() {
+    let (client, owner) = start().await;
+    let (first, second) = (test_key(1), test_key(2));
+    let address = derive_labeled_key_record(&REGISTRY, &owner.pubkey(), "laptop").0;
+    assert!(fetch_key_record(&client, &address).await.is_err());
+
+    register_key(
+        &client,
+        &REGISTRY,
+        &owner,
+        "laptop",
+        KeyType::X25519,
+        &first,
+        [0u8; 64],
+        0,
+        KeyRecord::USAGE_ENCRYPT,
+        [0u8; 32],
+        "",
+        KeyRecord::HISTORY_LEN as u8,
+    )
+    .await
+    .unwrap();
+    let KeyRecordView::Current(record) = fetch_key_record(&client, &address).await.unwrap() else {
+        panic!("a fresh record decoded as legacy");
+    };
+    assert_eq!(record.owner, owner.pubkey());
+    assert_eq!(record.label, "laptop");
+    assert_eq!(record.key_bytes(), first.as_slice());
+    assert_eq!(record.usage_flags, KeyRecord::USAGE_ENCRYPT);
+
+    update_key(&client, &REGISTRY, &owner, &owner.pubkey(), "laptop", &second, 0).await.unwrap();
+    let record = fetch_key_record(&client, &address).await.unwrap().into_record();
+    assert_eq!(record.key_bytes(), second.as_slice());
+    assert_eq!(record.history[0][..32], first[..]);
+
+    // the program's errors come back through the client
+    let err = update_key(&client, &REGISTRY, &owner, &owner.pubkey(), "laptop", &[3u8; 31], 0).await.unwrap_err();
+    let code = u32::from(KeyRegistryError::InvalidKeyLength);
+    assert!(err.to_string().contains(&format!("custom program error: {code:#x}")), "{err}");
+}
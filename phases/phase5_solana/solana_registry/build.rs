@@ -0,0 +1,14 @@
+// Chooses the id `declare_id!` bakes into the program. Local builds use the
+// localnet id below; devnet/mainnet/CI builds set KEY_REGISTRY_PROGRAM_ID to
+// the pubkey of that cluster's program keypair (see the README).
+
+use std::{env, fs, path::Path};
+
+const LOCALNET_PROGRAM_ID: &str = "asdmk9ot5XzRsYcqezCSGft923CuGJaJS3nR5wfJcpm";
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=KEY_REGISTRY_PROGRAM_ID");
+    let id = env::var("KEY_REGISTRY_PROGRAM_ID").unwrap_or_else(|_| LOCALNET_PROGRAM_ID.to_string());
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("program_id.rs");
+    fs::write(out, format!("declare_id!(\"{id}\");\n")).unwrap();
+}
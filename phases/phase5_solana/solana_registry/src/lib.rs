@@ -1,117 +1,5789 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_lang::solana_program::ed25519_program;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::keccak;
+use anchor_lang::solana_program::program::{get_return_data, invoke, invoke_signed, set_return_data};
+use anchor_lang::solana_program::secp256k1_recover::secp256k1_recover;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
 
-declare_id!("KeyRegistry11111111111111111111111111111");
+// Set per cluster by build.rs from KEY_REGISTRY_PROGRAM_ID.
+include!(concat!(env!("OUT_DIR"), "/program_id.rs"));
+
+// Largest public key any `KeyType` may have; sizes the key buffers in `KeyRecord`.
+pub const MAX_KEY_LEN: usize = 64;
+
+// Size of a key record account: discriminator + `KeyRecord::LEN`. Clients
+// sizing `getAccountInfo` reads or rent should use this, not their own sum.
+pub const ACCOUNT_SIZE: usize = 8 + KeyRecord::LEN;
+
+// Fewest distinct byte values `check_public_key` accepts in a key.
+pub const MIN_DISTINCT_KEY_BYTES: usize = 8;
+
+// Per-entry statuses returned by `register_keys_batch` and `sweep_expired`.
+// Failed entries (with `continue_on_error`) report BATCH_FAILED plus the
+// KeyRegistryError discriminant, or u8::MAX for any other error.
+pub const BATCH_APPLIED: u8 = 0;
+pub const BATCH_SKIPPED: u8 = 1;
+pub const BATCH_FAILED: u8 = 2;
+
+// Upper bound on `verify_keys_batch` input, to bound compute.
+pub const MAX_BATCH_CANDIDATES: usize = 32;
+
+// Upper bound on the records one `sweep_expired` call closes.
+pub const MAX_SWEEP_RECORDS: usize = 8;
+
+// Upper bound on `verify_membership` proof length (tree depth).
+pub const MAX_MERKLE_DEPTH: usize = 32;
+
+// Domain prefixes of allowlist tree leaves and nodes, see merkle_leaf.
+pub const MERKLE_LEAF_PREFIX: u8 = 0;
+pub const MERKLE_NODE_PREFIX: u8 = 1;
+
+// Upper bound on the records `verify_signature_any` tries, to bound compute.
+pub const MAX_SIGNATURE_ANY_RECORDS: usize = 6;
+
+// Upper bound on the records `find_owner` scans, to bound compute.
+pub const MAX_FIND_OWNER_RECORDS: usize = 10;
+
+// Records `register_keys_batch` creates per call, to stay under the compute limit.
+pub const MAX_BATCH_REGISTRATIONS: usize = 8;
+
+// `ProgramInfo::features` bits, one per optional capability of the build.
+pub const FEATURE_EXPIRY: u32 = 1 << 0;      // `clock`: timestamps, expiry
+pub const FEATURE_MULTI_ALGO: u32 = 1 << 1;  // `p256`: on-chain P-256 checks
+pub const FEATURE_COMPRESSION: u32 = 1 << 2; // compressed registrations
+pub const FEATURE_TEST_CLOCK: u32 = 1 << 3;  // `test-clock`: admin time override
+
+// SPL programs used for compressed registrations.
+pub mod spl_account_compression {
+    use super::*;
+    declare_id!("cmtDvXumGCrqC1Age74AVPhSRVXJMd8PJS91L8KbNCK");
+}
+
+pub mod spl_noop {
+    use super::*;
+    declare_id!("noopb9bkMVfRPU8AsbpTUg8AQkHtKwMYZiFUjNRtMmV");
+}
 
 #[program]
 pub mod key_registry {
     use super::*;
 
-    // Register the caller's Ed25519 public key.
-    pub fn register_key(ctx: Context<RegisterKey>, public_key: [u8; 32]) -> Result<()> {
+    // Create the registry config; the caller becomes its admin. `init` makes
+    // a second call fail with the account-already-in-use error.
+    pub fn initialize_config(ctx: Context<InitializeConfig>) -> Result<()> {
+        // A build for another cluster's id would otherwise create the config
+        // under a program it can never be verified against.
+        require_keys_eq!(*ctx.program_id, crate::ID, KeyRegistryError::ProgramIdMismatch);
+        
+        let config = &mut ctx.accounts.config;
+        config.admin = ctx.accounts.admin.key();
+        config.paused = false;
+        config.bump = ctx.bumps.config;
+        config.fee_lamports = 0;
+        config.treasury = config.admin;
+        config.stake_lamports = 0;
+        config.pending_admin = Pubkey::default();
+        config.deployed_at = unix_now()?;
+        
+        msg!("Initialized registry config with admin: {}", config.admin);
+        
+        Ok(())
+    }
+
+    // Emergency stop: while paused, keys cannot be registered or updated.
+    // Verification stays available.
+    pub fn set_paused(ctx: Context<SetPaused>, paused: bool) -> Result<()> {
+        ctx.accounts.config.paused = paused;
+        
+        msg!("Registry paused: {}", paused);
+        
+        Ok(())
+    }
+
+    // Pin the time `config_now` reports to `unix_ts` (0 = back to the real
+    // clock), so expiry and cooldown tests are deterministic. Anchor can't
+    // compile an instruction out, so without `test-clock` it always fails
+    // with TestClockDisabled.
+    pub fn set_test_clock(ctx: Context<SetPaused>, unix_ts: i64) -> Result<()> {
+        set_clock_override(&mut ctx.accounts.config, unix_ts)?;
+        
+        msg!("Test clock set to: {}", unix_ts);
+        
+        Ok(())
+    }
+
+    // Charge `fee_lamports` per registration, paid to `treasury` (0 = free).
+    pub fn set_fee(ctx: Context<SetFee>, fee_lamports: u64, treasury: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.fee_lamports = fee_lamports;
+        config.treasury = treasury;
+        
+        msg!("Registration fee: {} lamports to {}", fee_lamports, treasury);
+        
+        Ok(())
+    }
+
+    // Restrict which programs may call `require_key_matches` via CPI to
+    // `programs` (at most `Config::MAX_ALLOWED_PROGRAMS`); an empty list lets
+    // any program call it (admin only).
+    pub fn set_allowed_programs(ctx: Context<SetAllowedPrograms>, programs: Vec<Pubkey>) -> Result<()> {
+        require!(
+            programs.len() <= Config::MAX_ALLOWED_PROGRAMS,
+            KeyRegistryError::TooManyAllowedPrograms
+        );
+        
+        let config = &mut ctx.accounts.config;
+        config.allowed_programs = [Pubkey::default(); Config::MAX_ALLOWED_PROGRAMS];
+        config.allowed_programs[..programs.len()].copy_from_slice(&programs);
+        
+        msg!("Allowed CPI callers: {:?}", programs);
+        
+        Ok(())
+    }
+
+    // First step of an admin handoff: nominate `new_admin`, who must
+    // `accept_admin` before anything changes, so the config can't be handed to
+    // an address nobody controls. Proposing again replaces the nominee.
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        ctx.accounts.config.pending_admin = new_admin;
+        
+        msg!("Proposed registry admin: {}", new_admin);
+        
+        Ok(())
+    }
+
+    // Second step of an admin handoff, signed by the nominee.
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let old_admin = config.admin;
+        config.admin = ctx.accounts.pending_admin.key();
+        config.pending_admin = Pubkey::default();
+        
+        msg!("Registry admin changed from {} to {}", old_admin, config.admin);
+        
+        Ok(())
+    }
+
+    // Withdraw a pending admin nomination (current admin only).
+    pub fn cancel_admin(ctx: Context<ProposeAdmin>) -> Result<()> {
+        ctx.accounts.config.pending_admin = Pubkey::default();
+        
+        msg!("Cancelled pending registry admin handoff");
+        
+        Ok(())
+    }
+
+    // Require `stake_lamports` to be locked in each new key record on
+    // registration, on top of its rent, to make spam registrations costly. The
+    // stake is refunded with the rent by `close_key` (admin only).
+    pub fn set_stake(ctx: Context<SetFee>, stake_lamports: u64) -> Result<()> {
+        ctx.accounts.config.stake_lamports = stake_lamports;
+        
+        msg!("Registration stake: {} lamports", stake_lamports);
+        
+        Ok(())
+    }
+
+    // Register the caller's public key of the given type.
+    // Ed25519 keys need proof of possession: `signature` must be a signature
+    // by `public_key` over the owner's pubkey bytes, checked by an Ed25519
+    // native program instruction placed just before this one. Other key types
+    // have no precompile that can check them, so `signature` is ignored.
+    // `expires_at` is a Unix timestamp after which the key stops verifying
+    // (0 = never expires). `label` names the record so an owner can keep
+    // several keys; the empty label derives the same PDA as the original
+    // owner-only seeds. A non-zero `handle` also claims that display name
+    // (pass the matching `handle_index` account); all zeros means no handle.
+    // `usage_flags` says what the key may be used for (`KeyRecord::USAGE_*`).
+    // `email_hash` optionally binds a recovery contact (see `set_email_hash`).
+    // `uri` optionally points at an off-chain metadata document (see `set_uri`).
+    // `registry_id` picks the registry the record lives in (see
+    // `registry_seed`); instructions that look a record up by owner and label
+    // take it too.
+    // `history_capacity` sizes the previous-key ring up front, as
+    // `set_history_capacity` would later (`HISTORY_LEN` for the default).
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_key(
+        ctx: Context<RegisterKey>,
+        registry_id: [u8; 8],
+        label: String,
+        key_type: KeyType,
+        public_key: Vec<u8>,
+        signature: [u8; 64],
+        expires_at: i64,
+        handle: [u8; 32],
+        usage_flags: u8,
+        email_hash: [u8; 32],
+        uri: String,
+        history_capacity: u8,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        require!(label.len() <= KeyRecord::MAX_LABEL_LEN, KeyRegistryError::LabelTooLong);
+        require!(uri.len() <= KeyRecord::MAX_URI_LEN, KeyRegistryError::UriTooLong);
+        require!(
+            history_capacity as usize <= KeyRecord::MAX_HISTORY_CAPACITY,
+            KeyRegistryError::InvalidHistoryCapacity
+        );
+        check_new_key(key_type, &public_key)?;
+        let now = config_now(&ctx.accounts.config)?;
+        check_expiry(expires_at, now)?;
+        let owner = ctx.accounts.owner.key();
+        check_possession(&ctx.accounts.instructions, key_type, &public_key, &owner, &signature)?;
+        let staked = collect_registration(
+            &ctx.accounts.owner,
+            &ctx.accounts.treasury,
+            &ctx.accounts.key_record.to_account_info(),
+            &ctx.accounts.system_program,
+            &ctx.accounts.config,
+        )?;
+        
         let key_record = &mut ctx.accounts.key_record;
-        key_record.owner = ctx.accounts.owner.key();
-        key_record.public_key = public_key;
-        key_record.bump = ctx.bumps.key_record;
+        let address = key_record.key();
+        claim_key_index(&mut ctx.accounts.key_index, owner, address, ctx.bumps.key_index)?;
+        require!(
+            (handle == [0u8; 32]) == ctx.accounts.handle_index.is_none(),
+            KeyRegistryError::InvalidHandle
+        );
+        if let Some(handle_index) = ctx.accounts.handle_index.as_mut() {
+            check_handle(&handle)?;
+            require_keys_eq!(handle_index.record, Pubkey::default(), KeyRegistryError::HandleTaken);
+            handle_index.record = address;
+            handle_index.bump = ctx.bumps.handle_index;
+        }
+        key_record.handle = handle;
+        key_record.email_hash = email_hash;
+        key_record.uri = uri;
+        if history_capacity as usize != KeyRecord::HISTORY_LEN {
+            key_record.reset_history(history_capacity);
+        }
+        init_record(
+            key_record,
+            address,
+            NewRecord {
+                registry_id,
+                owner,
+                key_type,
+                public_key: &public_key,
+                bump: ctx.bumps.key_record,
+                label,
+                expires_at,
+                usage_flags,
+                staked,
+            },
+            now,
+        )?;
         
-        msg!("Registered public key for user: {}", ctx.accounts.owner.key());
+        msg!("Registered {:?} public key for user: {}", key_type, owner);
         msg!("Public key (hex): {:02x?}", public_key);
         
+        emit_cpi!(KeyRegistered {
+            owner,
+            key_type: key_record.key_type,
+            public_key,
+            seq: key_record.seq,
+        });
+        
         Ok(())
     }
 
-    // Update the caller's registered Ed25519 public key.
-    pub fn update_key(ctx: Context<UpdateKey>, new_public_key: [u8; 32]) -> Result<()> {
+    // `register_key` for sponsored transactions: a separate `payer` (e.g. a
+    // relayer) funds the rent while `owner` signs to authorize and owns the
+    // record, which is seeded by `owner` as usual. A handle, recovery email
+    // hash and URI can be added afterwards (`set_handle`, `set_email_hash`,
+    // `set_uri`).
+    #[allow(clippy::too_many_arguments)]
+    pub fn register_key_for(
+        ctx: Context<RegisterKeyFor>,
+        registry_id: [u8; 8],
+        label: String,
+        key_type: KeyType,
+        public_key: Vec<u8>,
+        signature: [u8; 64],
+        expires_at: i64,
+        usage_flags: u8,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        require!(label.len() <= KeyRecord::MAX_LABEL_LEN, KeyRegistryError::LabelTooLong);
+        check_new_key(key_type, &public_key)?;
+        let now = config_now(&ctx.accounts.config)?;
+        check_expiry(expires_at, now)?;
+        let owner = ctx.accounts.owner.key();
+        check_possession(&ctx.accounts.instructions, key_type, &public_key, &owner, &signature)?;
+        let staked = collect_registration(
+            &ctx.accounts.payer,
+            &ctx.accounts.treasury,
+            &ctx.accounts.key_record.to_account_info(),
+            &ctx.accounts.system_program,
+            &ctx.accounts.config,
+        )?;
+        
         let key_record = &mut ctx.accounts.key_record;
+        let address = key_record.key();
+        claim_key_index(&mut ctx.accounts.key_index, owner, address, ctx.bumps.key_index)?;
+        init_record(
+            key_record,
+            address,
+            NewRecord {
+                registry_id,
+                owner,
+                key_type,
+                public_key: &public_key,
+                bump: ctx.bumps.key_record,
+                label,
+                expires_at,
+                usage_flags,
+                staked,
+            },
+            now,
+        )?;
         
-        // Only the owner can update
-        require_keys_eq!(
-            key_record.owner,
-            ctx.accounts.owner.key(),
+        msg!("Registered {:?} public key for user {} (paid by {})", key_type, owner, ctx.accounts.payer.key());
+        msg!("Public key (hex): {:02x?}", public_key);
+        
+        emit_cpi!(KeyRegistered {
+            owner,
+            key_type: key_record.key_type,
+            public_key,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Commit-reveal registration, step one: record `commitment`, the SHA-256 of
+    // `public_key || salt`, so the key stays hidden until `reveal_key` and
+    // can't be raced into the registry by someone watching the mempool.
+    pub fn commit_key(ctx: Context<CommitKey>, commitment: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        
+        let key_commitment = &mut ctx.accounts.key_commitment;
+        key_commitment.owner = ctx.accounts.owner.key();
+        key_commitment.commitment = commitment;
+        key_commitment.slot = current_slot()?;
+        key_commitment.bump = ctx.bumps.key_commitment;
+        
+        msg!("Key commitment stored for user {} at slot {}", key_commitment.owner, key_commitment.slot);
+        
+        Ok(())
+    }
+
+    // Commit-reveal registration, step two: at least
+    // `KeyCommitment::MIN_REVEAL_SLOTS` after `commit_key`, open the commitment
+    // and register the key as `register_key` would (no expiry, handle, email
+    // hash or URI). The commitment account is closed to the owner.
+    pub fn reveal_key(
+        ctx: Context<RevealKey>,
+        registry_id: [u8; 8],
+        label: String,
+        key_type: KeyType,
+        public_key: Vec<u8>,
+        salt: [u8; 32],
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        let now = config_now(&ctx.accounts.config)?;
+        let key_commitment = &ctx.accounts.key_commitment;
+        // without `clock` every slot reads 0, so the delay can't be enforced
+        require!(
+            !cfg!(feature = "clock") || current_slot()? >= key_commitment.slot + KeyCommitment::MIN_REVEAL_SLOTS,
+            KeyRegistryError::RevealTooEarly
+        );
+        let opened = anchor_lang::solana_program::hash::hashv(&[&public_key, &salt]).to_bytes();
+        require!(opened == key_commitment.commitment, KeyRegistryError::CommitmentMismatch);
+        
+        require!(label.len() <= KeyRecord::MAX_LABEL_LEN, KeyRegistryError::LabelTooLong);
+        check_new_key(key_type, &public_key)?;
+        let owner = ctx.accounts.owner.key();
+        check_possession(&ctx.accounts.instructions, key_type, &public_key, &owner, &signature)?;
+        let staked = collect_registration(
+            &ctx.accounts.owner,
+            &ctx.accounts.treasury,
+            &ctx.accounts.key_record.to_account_info(),
+            &ctx.accounts.system_program,
+            &ctx.accounts.config,
+        )?;
+        
+        let key_record = &mut ctx.accounts.key_record;
+        let address = key_record.key();
+        claim_key_index(&mut ctx.accounts.key_index, owner, address, ctx.bumps.key_index)?;
+        init_record(
+            key_record,
+            address,
+            NewRecord {
+                registry_id,
+                owner,
+                key_type,
+                public_key: &public_key,
+                bump: ctx.bumps.key_record,
+                label,
+                expires_at: 0,
+                usage_flags: 0,
+                staked,
+            },
+            now,
+        )?;
+        
+        msg!("Revealed and registered {:?} public key for user: {}", key_type, owner);
+        msg!("Public key (hex): {:02x?}", public_key);
+        
+        emit_cpi!(KeyRegistered {
+            owner,
+            key_type: key_record.key_type,
+            public_key,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Create the organization namespace `org_id`, with the caller as its admin.
+    pub fn create_org(ctx: Context<CreateOrg>, org_id: [u8; 32]) -> Result<()> {
+        let org = &mut ctx.accounts.org;
+        org.org_id = org_id;
+        org.admin = ctx.accounts.admin.key();
+        org.bump = ctx.bumps.org;
+        
+        msg!("Created org {:02x?} with admin: {}", org_id, org.admin);
+        
+        Ok(())
+    }
+
+    // Register the caller's key inside organization `org_id`, co-signed by the
+    // org admin. The record is seeded `[b"key_record", org_id, owner]` rather
+    // than by owner and label, so the owner-facing instructions don't reach
+    // it: the org admin controls it (`org_revoke`) and relying parties check
+    // it with `verify_org_key`. Ed25519 proof of possession as in `register_key`.
+    pub fn register_key_in_org(
+        ctx: Context<RegisterKeyInOrg>,
+        org_id: [u8; 32],
+        key_type: KeyType,
+        public_key: Vec<u8>,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        let now = config_now(&ctx.accounts.config)?;
+        check_new_key(key_type, &public_key)?;
+        let owner = ctx.accounts.owner.key();
+        check_possession(&ctx.accounts.instructions, key_type, &public_key, &owner, &signature)?;
+        let staked = collect_registration(
+            &ctx.accounts.owner,
+            &ctx.accounts.treasury,
+            &ctx.accounts.key_record.to_account_info(),
+            &ctx.accounts.system_program,
+            &ctx.accounts.config,
+        )?;
+        
+        // org records are seeded by org id rather than registry and label
+        let key_record = &mut ctx.accounts.key_record;
+        let address = key_record.key();
+        claim_key_index(&mut ctx.accounts.key_index, owner, address, ctx.bumps.key_index)?;
+        init_record(
+            key_record,
+            address,
+            NewRecord {
+                registry_id: [0u8; 8],
+                owner,
+                key_type,
+                public_key: &public_key,
+                bump: ctx.bumps.key_record,
+                label: String::new(),
+                expires_at: 0,
+                usage_flags: 0,
+                staked,
+            },
+            now,
+        )?;
+        
+        msg!("Registered {:?} public key for user {} in org {:02x?}", key_type, owner, org_id);
+        msg!("Public key (hex): {:02x?}", public_key);
+        
+        emit_cpi!(KeyRegistered {
+            owner,
+            key_type: key_record.key_type,
+            public_key,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Revoke `member`'s key in organization `org_id` (org admin only).
+    pub fn org_revoke(ctx: Context<OrgRevoke>, org_id: [u8; 32], member: Pubkey) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        
+        key_record.revoked = true;
+        
+        msg!("Org {:02x?} admin revoked public key for member: {}", org_id, member);
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Revoke, now)?;
+        
+        emit_cpi!(KeyRevoked {
+            owner: key_record.owner,
+            at: now,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // `verify_key` for a key registered in organization `org_id`.
+    pub fn verify_org_key(
+        ctx: Context<VerifyOrgKey>,
+        _org_id: [u8; 32],
+        _owner: Pubkey,
+        public_key_to_verify: Vec<u8>,
+    ) -> Result<bool> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        check_key_match(&ctx.accounts.key_record, "", &public_key_to_verify, now)
+    }
+
+    // Admin-sponsored onboarding: register up to `MAX_BATCH_REGISTRATIONS`
+    // keys in one call, each owned by its entry's `owner` (not the admin).
+    // Records use the empty label and never expire; the admin pays the rent.
+    // There is no proof of possession, so only the config admin may call this.
+    //
+    // `remaining_accounts` must hold two writable accounts per entry, in entry
+    // order: the owner's key record PDA, then the key's `key_index` PDA.
+    //
+    // Returns a `BATCH_*` status per entry. By default the first invalid
+    // entry fails the whole call; with `continue_on_error` it is skipped
+    // and reported, and the other entries still register.
+    pub fn register_keys_batch<'info>(
+        ctx: Context<'_, '_, '_, 'info, RegisterKeysBatch<'info>>,
+        entries: Vec<BatchKeyEntry>,
+        continue_on_error: bool,
+    ) -> Result<Vec<u8>> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        let now = config_now(&ctx.accounts.config)?;
+        require!(
+            entries.len() <= MAX_BATCH_REGISTRATIONS,
+            KeyRegistryError::BatchTooLarge
+        );
+        require!(
+            ctx.remaining_accounts.len() == entries.len() * 2,
+            KeyRegistryError::InvalidRecord
+        );
+        
+        let mut results = Vec::with_capacity(entries.len());
+        for (entry, accounts) in entries.iter().zip(ctx.remaining_accounts.chunks(2)) {
+            let (record_info, index_info) = (&accounts[0], &accounts[1]);
+            let key = &entry.public_key;
+            // Everything that can reject an entry happens before any account
+            // is created, so a skipped entry leaves nothing behind.
+            let checked = (|| {
+                check_new_key(entry.key_type, &entry.public_key)?;
+                
+                let (record_address, record_bump) =
+                    Pubkey::find_program_address(&[b"key_record", entry.owner.as_ref()], &crate::ID);
+                let (index_address, index_bump) = Pubkey::find_program_address(
+                    &[b"key_index", key_seed(key, 0), key_seed(key, 1)],
+                    &crate::ID,
+                );
+                require_keys_eq!(record_info.key(), record_address, KeyRegistryError::InvalidRecord);
+                require_keys_eq!(index_info.key(), index_address, KeyRegistryError::InvalidRecord);
+                // A funded address is an existing account (or one we can't create).
+                require!(record_info.lamports() == 0, KeyRegistryError::AlreadyRegistered);
+                require!(index_info.lamports() == 0, KeyRegistryError::KeyAlreadyIndexed);
+                Ok((record_address, record_bump, index_bump))
+            })();
+            let (record_address, record_bump, index_bump) = match checked {
+                Ok(checked) => checked,
+                Err(err) if continue_on_error => {
+                    msg!("Skipping batch entry for user {}: {}", entry.owner, err);
+                    results.push(batch_error_code(&err));
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            
+            create_pda_account(
+                &ctx.accounts.admin,
+                record_info,
+                &ctx.accounts.system_program,
+                ACCOUNT_SIZE,
+                &[b"key_record", entry.owner.as_ref(), &[record_bump]],
+            )?;
+            create_pda_account(
+                &ctx.accounts.admin,
+                index_info,
+                &ctx.accounts.system_program,
+                8 + KeyIndex::LEN,
+                &[b"key_index", key_seed(key, 0), key_seed(key, 1), &[index_bump]],
+            )?;
+            
+            let mut key_record = KeyRecord::zeroed();
+            init_record(
+                &mut key_record,
+                record_address,
+                NewRecord {
+                    registry_id: [0u8; 8],
+                    owner: entry.owner,
+                    key_type: entry.key_type,
+                    public_key: key,
+                    bump: record_bump,
+                    label: String::new(),
+                    expires_at: 0,
+                    usage_flags: entry.usage_flags,
+                    staked: 0,
+                },
+                now,
+            )?;
+            key_record.try_serialize(&mut &mut record_info.try_borrow_mut_data()?[..])?;
+            
+            let key_index = KeyIndex {
+                owner: entry.owner,
+                record: record_address,
+                bump: index_bump,
+            };
+            key_index.try_serialize(&mut &mut index_info.try_borrow_mut_data()?[..])?;
+            
+            emit_cpi!(KeyRegistered {
+                owner: entry.owner,
+                key_type: entry.key_type as u8,
+                public_key: key.clone(),
+                seq: key_record.seq,
+            });
+            results.push(BATCH_APPLIED);
+        }
+        
+        let registered = results.iter().filter(|status| **status == BATCH_APPLIED).count();
+        msg!("Batch-registered {} of {} keys", registered, entries.len());
+        
+        Ok(results)
+    }
+
+    // Compressed registrations keep `(owner, key_type, public_key)` leaves in
+    // an SPL concurrent Merkle tree instead of one `KeyRecord` PDA per user,
+    // so a registration costs a transaction fee rather than rent. The tree is
+    // append-only here: compressed keys have no expiry, revocation or updates.
+    //
+    // The admin creates a tree by allocating `merkle_tree` (owned by the
+    // compression program, sized for the parameters) and calling this, which
+    // makes the program's `tree_authority` PDA its only writer. Tradeoffs:
+    // - `max_depth` caps the tree at 2^max_depth keys, and proofs carry
+    //   max_depth nodes (depth 20 is ~1M keys and a 640-byte proof).
+    // - `max_buffer_size` is how many appends can land against the same root,
+    //   i.e. how many registrations may be in flight concurrently.
+    // - the account size, and so its one-time rent, grows with both.
+    pub fn init_key_tree(ctx: Context<InitKeyTree>, max_depth: u32, max_buffer_size: u32) -> Result<()> {
+        let mut args = max_depth.to_le_bytes().to_vec();
+        args.extend_from_slice(&max_buffer_size.to_le_bytes());
+        invoke_compression(
+            &ctx.accounts.compression_program,
+            "init_empty_merkle_tree",
+            args,
+            vec![
+                AccountMeta::new(ctx.accounts.merkle_tree.key(), false),
+                AccountMeta::new_readonly(ctx.accounts.tree_authority.key(), true),
+                AccountMeta::new_readonly(ctx.accounts.noop_program.key(), false),
+            ],
+            &[
+                ctx.accounts.merkle_tree.to_account_info(),
+                ctx.accounts.tree_authority.to_account_info(),
+                ctx.accounts.noop_program.to_account_info(),
+            ],
+            &[b"tree_authority", ctx.accounts.merkle_tree.key().as_ref(), &[ctx.bumps.tree_authority]],
+        )?;
+        
+        msg!("Initialized key tree {} (depth {})", ctx.accounts.merkle_tree.key(), max_depth);
+        
+        Ok(())
+    }
+
+    // Register the caller's key as a leaf of a key tree (see `init_key_tree`).
+    // Ed25519 keys need the same proof of possession as `register_key`.
+    pub fn register_key_compressed(
+        ctx: Context<RegisterKeyCompressed>,
+        key_type: KeyType,
+        public_key: Vec<u8>,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        check_new_key(key_type, &public_key)?;
+        let owner = ctx.accounts.owner.key();
+        check_possession(&ctx.accounts.instructions, key_type, &public_key, &owner, &signature)?;
+        
+        let leaf = compressed_leaf(&owner, key_type, &public_key);
+        invoke_compression(
+            &ctx.accounts.compression_program,
+            "append",
+            leaf.to_vec(),
+            vec![
+                AccountMeta::new(ctx.accounts.merkle_tree.key(), false),
+                AccountMeta::new_readonly(ctx.accounts.tree_authority.key(), true),
+                AccountMeta::new_readonly(ctx.accounts.noop_program.key(), false),
+            ],
+            &[
+                ctx.accounts.merkle_tree.to_account_info(),
+                ctx.accounts.tree_authority.to_account_info(),
+                ctx.accounts.noop_program.to_account_info(),
+            ],
+            &[b"tree_authority", ctx.accounts.merkle_tree.key().as_ref(), &[ctx.bumps.tree_authority]],
+        )?;
+        
+        msg!("Registered compressed {:?} public key for user: {}", key_type, owner);
+        msg!("Leaf (hex): {:02x?}", leaf);
+        
+        emit!(KeyRegistered {
+            owner,
+            key_type: key_type as u8,
+            public_key,
+            seq: 0, // compressed keys have no record
+        });
+        
+        Ok(())
+    }
+
+    // Update a registered public key (same key type) and its expiry, which
+    // must satisfy the owner's `max_lifetime`. The signer may be the record's
+    // owner or the delegate in the owner's `Settings`.
+    pub fn update_key(
+        ctx: Context<UpdateKey>,
+        label: String,
+        new_public_key: Vec<u8>,
+        expires_at: i64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        
+        let key_record = &mut ctx.accounts.key_record;
+        let settings = load_settings(&ctx.accounts.settings)?;
+        
+        // Only the owner or delegate can update
+        require!(
+            key_record.can_manage(settings.as_ref(), &ctx.accounts.authority.key()),
             KeyRegistryError::Unauthorized
         );
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        require!(!key_record.frozen, KeyRegistryError::Frozen);
+        require!(
+            new_public_key.len() == key_record.key_len as usize,
+            KeyRegistryError::InvalidKeyLength
+        );
+        if let Some(key_type) = KeyType::from_u8(key_record.key_type) {
+            check_public_key(key_type, &new_public_key)?;
+        }
+        let now = config_now(&ctx.accounts.config)?;
+        check_expiry(expires_at, now)?;
+        check_lifetime(settings.as_ref(), expires_at, now)?;
+        check_update_cooldown(settings.as_ref(), key_record, now)?;
+        
+        // Move the reverse index to the new key; the old entry is closed on exit.
+        let new_key_index = &mut ctx.accounts.new_key_index;
+        require!(
+            new_key_index.owner == Pubkey::default() && key_record.key_bytes() != new_public_key.as_slice(),
+            KeyRegistryError::KeyAlreadyIndexed
+        );
+        new_key_index.owner = key_record.owner;
+        new_key_index.record = key_record.key();
+        new_key_index.bump = ctx.bumps.new_key_index;
         
-        key_record.public_key = new_public_key;
+        let old_key = key_record.key_bytes().to_vec();
+        key_record.push_history();
+        key_record.set_key(&new_public_key);
+        key_record.expires_at = expires_at;
+        if expires_at != 0 {
+            // a timestamp expiry replaces any slot expiry (one mode at a time)
+            key_record.expires_at_slot = 0;
+        }
+        key_record.updated_at = now;
         
-        msg!("Updated public key for user: {}", ctx.accounts.owner.key());
+        msg!("Updated public key {:?} for user: {}", label, key_record.owner);
         msg!("New public key (hex): {:02x?}", new_public_key);
         
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Update, now)?;
+        
+        emit!(KeyUpdated {
+            owner: key_record.owner,
+            old_key,
+            new_key: new_public_key,
+            seq: key_record.seq,
+        });
+        
         Ok(())
     }
 
-    // Check whether `public_key_to_verify` matches the stored key.
-    pub fn verify_key(ctx: Context<VerifyKey>, public_key_to_verify: [u8; 32]) -> Result<bool> {
-        let key_record = &ctx.accounts.key_record;
-        let matches = key_record.public_key == public_key_to_verify;
+    // Register `public_key` under `label` if the caller has no such record
+    // yet, otherwise rotate the record to it, so clients needn't know which
+    // applies. Ed25519 keys need proof of possession as in `register_key` on
+    // both paths. An existing record must still be owned by the caller and
+    // hold a key of `key_type`; its other fields are left alone. Pass
+    // `old_key_index` only when rotating to a different key.
+    pub fn upsert_key(
+        ctx: Context<UpsertKey>,
+        registry_id: [u8; 8],
+        label: String,
+        key_type: KeyType,
+        public_key: Vec<u8>,
+        signature: [u8; 64],
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        let now = config_now(&ctx.accounts.config)?;
+        require!(label.len() <= KeyRecord::MAX_LABEL_LEN, KeyRegistryError::LabelTooLong);
+        check_new_key(key_type, &public_key)?;
+        let owner = ctx.accounts.owner.key();
+        check_possession(&ctx.accounts.instructions, key_type, &public_key, &owner, &signature)?;
         
-        if matches {
-            msg!("✅ Public key matches registered key for user: {}", key_record.owner);
+        // init_if_needed leaves a fresh record zeroed, so an unset owner means
+        // it was just created; an existing one is never re-initialized.
+        if ctx.accounts.key_record.owner == Pubkey::default() {
+            require!(ctx.accounts.old_key_index.is_none(), KeyRegistryError::InvalidRecord);
+            let staked = collect_registration(
+                &ctx.accounts.owner,
+                &ctx.accounts.treasury,
+                &ctx.accounts.key_record.to_account_info(),
+                &ctx.accounts.system_program,
+                &ctx.accounts.config,
+            )?;
+            
+            let key_record = &mut ctx.accounts.key_record;
+            let address = key_record.key();
+            claim_key_index(&mut ctx.accounts.new_key_index, owner, address, ctx.bumps.new_key_index)?;
+            init_record(
+                key_record,
+                address,
+                NewRecord {
+                    registry_id,
+                    owner,
+                    key_type,
+                    public_key: &public_key,
+                    bump: ctx.bumps.key_record,
+                    label,
+                    expires_at: 0,
+                    usage_flags: 0,
+                    staked,
+                },
+                now,
+            )?;
+            
+            msg!("Upsert registered {:?} public key for user: {}", key_type, owner);
+            emit_cpi!(KeyRegistered {
+                owner,
+                key_type: key_record.key_type,
+                public_key,
+                seq: key_record.seq,
+            });
+            return Ok(());
+        }
+        
+        let key_record = &mut ctx.accounts.key_record;
+        require_keys_eq!(key_record.owner, owner, KeyRegistryError::Unauthorized);
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        require!(!key_record.frozen, KeyRegistryError::Frozen);
+        require!(key_record.key_type == key_type as u8, KeyRegistryError::KeyTypeMismatch);
+        
+        let unchanged = key_record.key_bytes() == public_key.as_slice();
+        require!(
+            ctx.accounts.old_key_index.is_some() != unchanged,
+            KeyRegistryError::InvalidRecord
+        );
+        if !unchanged {
+            check_update_cooldown(load_settings(&ctx.accounts.settings)?.as_ref(), key_record, now)?;
+        }
+        let new_key_index = &mut ctx.accounts.new_key_index;
+        if unchanged {
+            require_keys_eq!(new_key_index.record, key_record.key(), KeyRegistryError::KeyAlreadyIndexed);
         } else {
-            msg!("❌ Public key does NOT match registered key for user: {}", key_record.owner);
+            claim_key_index(new_key_index, owner, key_record.key(), ctx.bumps.new_key_index)?;
         }
         
-        Ok(matches)
+        let old_key = key_record.key_bytes().to_vec();
+        if !unchanged {
+            key_record.push_history();
+            key_record.set_key(&public_key);
+        }
+        key_record.updated_at = now;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Update, now)?;
+        
+        msg!("Upsert updated {:?} public key for user: {}", key_type, owner);
+        emit!(KeyUpdated {
+            owner,
+            old_key,
+            new_key: public_key,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
     }
-}
 
-#[derive(Accounts)]
-#[instruction(public_key: [u8; 32])]
-pub struct RegisterKey<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    
-    #[account(
-        init,
-        payer = owner,
-        space = 8 + KeyRecord::LEN,
-        seeds = [b"key_record", owner.key().as_ref()],
-        bump
-    )]
-    pub key_record: Account<'info, KeyRecord>,
-    
-    pub system_program: Program<'info, System>,
-}
+    // Bind (or, with all zeros, clear) the record's recovery email hash. Only
+    // the SHA-256 of the normalized address is stored; the program never sees
+    // the email itself.
+    pub fn set_email_hash(ctx: Context<SetEmailHash>, email_hash: [u8; 32]) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.email_hash = email_hash;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Set recovery email hash for user: {}", key_record.owner);
+        
+        Ok(())
+    }
 
-#[derive(Accounts)]
-#[instruction(new_public_key: [u8; 32])]
-pub struct UpdateKey<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
-    
-    #[account(
-        mut,
-        seeds = [b"key_record", owner.key().as_ref()],
-        bump = key_record.bump,
-        has_one = owner @ KeyRegistryError::Unauthorized
-    )]
-    pub key_record: Account<'info, KeyRecord>,
-}
+    // Make the record an alias of the record passed as `target_record` (e.g.
+    // a shared team key), so `verify_key` checks against that record's key.
+    // Aliases are a single hop: the target may not itself be an alias.
+    // `None` (with no `target_record`) removes the alias.
+    pub fn set_alias(ctx: Context<SetAlias>, target: Option<Pubkey>) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        match (target, ctx.accounts.target_record.as_ref()) {
+            (Some(target), Some(target_record)) => {
+                require_keys_eq!(target_record.key(), target, KeyRegistryError::InvalidRecord);
+                require_keys_neq!(target, key_record.key(), KeyRegistryError::InvalidRecord);
+                require!(target_record.alias_of.is_none(), KeyRegistryError::AliasChainTooDeep);
+            }
+            (None, None) => {}
+            _ => return err!(KeyRegistryError::InvalidRecord),
+        }
+        key_record.alias_of = target;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Set alias for user {} to: {:?}", key_record.owner, target);
+        
+        Ok(())
+    }
 
-#[derive(Accounts)]
-#[instruction(public_key_to_verify: [u8; 32])]
-pub struct VerifyKey<'info> {
-    #[account(
-        seeds = [b"key_record", key_record.owner.as_ref()],
-        bump = key_record.bump
-    )]
-    pub key_record: Account<'info, KeyRecord>,
-}
+    // Restrict verification of the caller's record (`verify_key` and every
+    // other instruction checking or returning the key, its history or its
+    // metadata for a third party, see `check_verifier`) to the signers in
+    // `verifiers` (at most `KeyRecord::MAX_VERIFIERS`), who must pass
+    // themselves as `verifier`; an empty list makes verification public
+    // again. This only gates the instructions: the account, key included, is
+    // still readable by anyone.
+    pub fn set_verifier_allowlist(ctx: Context<SetVerifierAllowlist>, verifiers: Vec<Pubkey>) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        require!(
+            verifiers.len() <= KeyRecord::MAX_VERIFIERS,
+            KeyRegistryError::TooManyVerifiers
+        );
+        
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.verifier_allowlist = [Pubkey::default(); KeyRecord::MAX_VERIFIERS];
+        key_record.verifier_allowlist[..verifiers.len()].copy_from_slice(&verifiers);
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Allowed verifiers for user {}: {:?}", key_record.owner, verifiers);
+        
+        Ok(())
+    }
 
-#[account]
-pub struct KeyRecord {
-    pub owner: Pubkey,        // wallet address
-    pub public_key: [u8; 32], // Ed25519 public key
-    pub bump: u8,             // PDA bump
-}
+    // Name an external program relying parties can ask about the key when the
+    // built-in checks don't cover it (e.g. a newer algorithm), see
+    // `verify_delegated`. None clears it.
+    pub fn set_verifier(ctx: Context<SetVerifier>, program: Option<Pubkey>) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        require!(program != Some(crate::ID), KeyRegistryError::InvalidVerifierProgram);
+        
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.verifier_program = program;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Set verifier program for user {} to: {:?}", key_record.owner, program);
+        
+        Ok(())
+    }
 
-impl KeyRecord {
-    pub const LEN: usize = 32 + 32 + 1; // owner + public_key + bump
+    // Set a backup key of the record's key type, accepted by
+    // `verify_key_with_backup` if the primary is lost. Rotating the primary
+    // with `update_key` keeps it; an empty `key` removes it.
+    pub fn set_backup_key(ctx: Context<SetBackupKey>, key: Vec<u8>) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        if !key.is_empty() {
+            require!(
+                key.len() == key_record.key_len as usize,
+                KeyRegistryError::InvalidKeyLength
+            );
+            if let Some(key_type) = KeyType::from_u8(key_record.key_type) {
+                check_public_key(key_type, &key)?;
+            }
+        }
+        key_record.backup_key = [0u8; MAX_KEY_LEN];
+        key_record.backup_key[..key.len()].copy_from_slice(&key);
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Set backup key for user {}: {:02x?}", key_record.owner, key);
+        
+        Ok(())
+    }
+
+    // Point the record at an off-chain metadata document (e.g. JSON with
+    // algorithm details and proof links); the empty string clears it.
+    pub fn set_uri(ctx: Context<SetUri>, uri: String) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        require!(uri.len() <= KeyRecord::MAX_URI_LEN, KeyRegistryError::UriTooLong);
+        
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.uri = uri;
+        key_record.updated_at = now;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Set metadata URI for user {} to: {:?}", key_record.owner, key_record.uri);
+        
+        Ok(())
+    }
+
+    // First half of a time-locked rotation: stage `new_key` to replace the
+    // current key once `activate_after` (Unix seconds) has passed, giving the
+    // owner a window to `cancel_update` a rotation it didn't intend (e.g. by
+    // a compromised delegate). Proposing again replaces the pending key.
+    pub fn propose_update(ctx: Context<ProposeUpdate>, new_key: Vec<u8>, activate_after: i64) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        
+        let key_record = &mut ctx.accounts.key_record;
+        let settings = load_settings(&ctx.accounts.settings)?;
+        require!(
+            key_record.can_manage(settings.as_ref(), &ctx.accounts.authority.key()),
+            KeyRegistryError::Unauthorized
+        );
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        require!(!key_record.frozen, KeyRegistryError::Frozen);
+        require!(
+            new_key.len() == key_record.key_len as usize,
+            KeyRegistryError::InvalidKeyLength
+        );
+        if let Some(key_type) = KeyType::from_u8(key_record.key_type) {
+            check_public_key(key_type, &new_key)?;
+        }
+        let now = config_now(&ctx.accounts.config)?;
+        require!(activate_after > now, KeyRegistryError::InvalidExpiry);
+        
+        key_record.pending_key = [0u8; MAX_KEY_LEN];
+        key_record.pending_key[..new_key.len()].copy_from_slice(&new_key);
+        key_record.pending_activation = activate_after;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Proposed key update for user {} activating after {}", key_record.owner, activate_after);
+        msg!("Pending public key (hex): {:02x?}", new_key);
+        
+        Ok(())
+    }
+
+    // Second half of a time-locked rotation: install the pending key once its
+    // activation time has passed, moving the reverse index as `update_key` does.
+    pub fn finalize_update(ctx: Context<FinalizeUpdate>) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        
+        let key_record = &mut ctx.accounts.key_record;
+        let settings = load_settings(&ctx.accounts.settings)?;
+        require!(
+            key_record.can_manage(settings.as_ref(), &ctx.accounts.authority.key()),
+            KeyRegistryError::Unauthorized
+        );
+        require!(key_record.has_pending_update(), KeyRegistryError::NoPendingUpdate);
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        require!(!key_record.frozen, KeyRegistryError::Frozen);
+        let now = config_now(&ctx.accounts.config)?;
+        require!(now >= key_record.pending_activation, KeyRegistryError::UpdateNotReady);
+        check_update_cooldown(settings.as_ref(), key_record, now)?;
+        
+        let new_key = key_record.pending_key_bytes().to_vec();
+        let new_key_index = &mut ctx.accounts.new_key_index;
+        require!(
+            new_key_index.owner == Pubkey::default() && key_record.key_bytes() != new_key.as_slice(),
+            KeyRegistryError::KeyAlreadyIndexed
+        );
+        new_key_index.owner = key_record.owner;
+        new_key_index.record = key_record.key();
+        new_key_index.bump = ctx.bumps.new_key_index;
+        
+        let old_key = key_record.key_bytes().to_vec();
+        key_record.push_history();
+        key_record.set_key(&new_key);
+        key_record.pending_key = [0u8; MAX_KEY_LEN];
+        key_record.pending_activation = 0;
+        key_record.updated_at = now;
+        
+        msg!("Finalized key update for user: {}", key_record.owner);
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Update, now)?;
+        
+        emit!(KeyUpdated {
+            owner: key_record.owner,
+            old_key,
+            new_key,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Discard a pending rotation (owner only).
+    pub fn cancel_update(ctx: Context<CancelUpdate>) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        require!(key_record.has_pending_update(), KeyRegistryError::NoPendingUpdate);
+        
+        key_record.pending_key = [0u8; MAX_KEY_LEN];
+        key_record.pending_activation = 0;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Cancelled pending key update for user: {}", key_record.owner);
+        
+        Ok(())
+    }
+
+    // Let `delegate` manage (update) any of the caller's keys; `Pubkey::default()`
+    // removes the delegate. Stored in the caller's `Settings`, created on
+    // first use. Closing and delegate changes stay owner-only.
+    pub fn set_delegate(ctx: Context<SetDelegate>, delegate: Pubkey) -> Result<()> {
+        let settings = &mut ctx.accounts.settings;
+        settings.owner = ctx.accounts.owner.key();
+        settings.bump = ctx.bumps.settings;
+        settings.delegate = delegate;
+        
+        msg!("Set delegate for user {} to: {}", settings.owner, delegate);
+        
+        Ok(())
+    }
+
+    // Hand the record to `new_owner`. The PDA stays where it is because it is
+    // seeded by the original registrant, not the current owner. From then on
+    // the new owner's `Settings` apply, so the old owner's delegate loses access.
+    pub fn transfer_ownership(ctx: Context<TransferOwnership>, new_owner: Pubkey) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        require_keys_neq!(new_owner, Pubkey::default(), KeyRegistryError::Unauthorized);
+        
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.owner = new_owner;
+        ctx.accounts.key_index.owner = new_owner;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Transfer, now)?;
+        
+        msg!("Transferred key record from {} to {}", ctx.accounts.owner.key(), new_owner);
+        
+        Ok(())
+    }
+
+    // Change the record's display handle. The old handle (if any) is released
+    // by closing its `old_handle_index`; a non-zero `handle` is claimed through
+    // `new_handle_index`. All zeros just releases the current handle.
+    pub fn set_handle(ctx: Context<SetHandle>, handle: [u8; 32]) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        require!(handle != key_record.handle, KeyRegistryError::HandleTaken);
+        require!(
+            (key_record.handle == [0u8; 32]) == ctx.accounts.old_handle_index.is_none(),
+            KeyRegistryError::InvalidHandle
+        );
+        require!(
+            (handle == [0u8; 32]) == ctx.accounts.new_handle_index.is_none(),
+            KeyRegistryError::InvalidHandle
+        );
+        
+        if let Some(handle_index) = ctx.accounts.new_handle_index.as_mut() {
+            check_handle(&handle)?;
+            require_keys_eq!(handle_index.record, Pubkey::default(), KeyRegistryError::HandleTaken);
+            handle_index.record = key_record.key();
+            handle_index.bump = ctx.bumps.new_handle_index;
+        }
+        key_record.handle = handle;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Set handle for user {} to: {:?}", key_record.owner, handle_str(&handle));
+        
+        Ok(())
+    }
+
+    // Appoint up to `MAX_GUARDIANS` guardians, any `threshold` of whom can
+    // `recover` any of the caller's records to a new owner. Stored in the
+    // caller's `Settings`, created on first use. An empty list disables recovery.
+    pub fn set_guardians(ctx: Context<SetGuardians>, guardians: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        require!(guardians.len() <= Settings::MAX_GUARDIANS, KeyRegistryError::InvalidGuardians);
+        require!(
+            (guardians.is_empty() && threshold == 0)
+                || (threshold > 0 && threshold as usize <= guardians.len()),
+            KeyRegistryError::InvalidGuardians
+        );
+        for (i, guardian) in guardians.iter().enumerate() {
+            require!(
+                *guardian != Pubkey::default() && !guardians[..i].contains(guardian),
+                KeyRegistryError::InvalidGuardians
+            );
+        }
+        
+        let settings = &mut ctx.accounts.settings;
+        settings.owner = ctx.accounts.owner.key();
+        settings.bump = ctx.bumps.settings;
+        settings.guardians = [Pubkey::default(); Settings::MAX_GUARDIANS];
+        settings.guardians[..guardians.len()].copy_from_slice(&guardians);
+        settings.guardian_threshold = threshold;
+        
+        msg!(
+            "Set {} guardians (threshold {}) for user: {}",
+            guardians.len(),
+            threshold,
+            settings.owner
+        );
+        
+        Ok(())
+    }
+
+    // Rate-limit key changes: replacing the key of any of the caller's records
+    // (`update_key`, `finalize_update`, a rotating `upsert_key` or
+    // `reactivate_key`, and either side of `swap_keys`) fails with
+    // UpdateTooSoon until `update_cooldown` seconds have passed since the
+    // record's `updated_at`, bounding how fast a compromised delegate can
+    // churn the key. 0 disables the limit.
+    pub fn set_update_cooldown(ctx: Context<SetUpdateCooldown>, update_cooldown: i64) -> Result<()> {
+        require!(update_cooldown >= 0, KeyRegistryError::InvalidExpiry);
+        
+        let settings = &mut ctx.accounts.settings;
+        settings.owner = ctx.accounts.owner.key();
+        settings.bump = ctx.bumps.settings;
+        settings.update_cooldown = update_cooldown;
+        
+        msg!("Set update cooldown of {}s for user: {}", update_cooldown, settings.owner);
+        
+        Ok(())
+    }
+
+    // Bound the caller's keys: with `max_lifetime` > 0, `update_key` and
+    // `renew_key` only accept an expiry at most that many seconds ahead
+    // (never-expiring and slot expiries are refused), failing with
+    // LifetimeTooLong; with `allowed_usage` non-zero, `set_flags` only grants
+    // those USAGE_* bits. 0 lifts either limit. Registration is not bound,
+    // as it takes no `Settings`.
+    pub fn set_key_policy(ctx: Context<SetKeyPolicy>, max_lifetime: i64, allowed_usage: u8) -> Result<()> {
+        require!(max_lifetime >= 0, KeyRegistryError::InvalidExpiry);
+        
+        let settings = &mut ctx.accounts.settings;
+        settings.owner = ctx.accounts.owner.key();
+        settings.bump = ctx.bumps.settings;
+        settings.max_lifetime = max_lifetime;
+        settings.allowed_usage = allowed_usage;
+        
+        msg!(
+            "Set key policy for user {}: max_lifetime={}s allowed_usage={:#04b}",
+            settings.owner,
+            max_lifetime,
+            allowed_usage
+        );
+        
+        Ok(())
+    }
+
+    // Social recovery: reassign the record to `new_owner` when at least
+    // `guardian_threshold` distinct guardians from the owner's `Settings` sign.
+    // Guardians are passed as signer accounts in `remaining_accounts`, in any order.
+    pub fn recover(ctx: Context<Recover>, new_owner: Pubkey) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        require_keys_neq!(new_owner, Pubkey::default(), KeyRegistryError::Unauthorized);
+        
+        let settings = &ctx.accounts.settings;
+        let key_record = &mut ctx.accounts.key_record;
+        let mut approvals: Vec<Pubkey> = Vec::new();
+        for account in ctx.remaining_accounts.iter() {
+            if account.is_signer && settings.is_guardian(account.key) && !approvals.contains(account.key) {
+                approvals.push(*account.key);
+            }
+        }
+        require!(
+            settings.guardian_threshold > 0 && approvals.len() >= settings.guardian_threshold as usize,
+            KeyRegistryError::InsufficientGuardians
+        );
+        
+        let old_owner = key_record.owner;
+        key_record.owner = new_owner;
+        ctx.accounts.key_index.owner = new_owner;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Transfer, now)?;
+        
+        msg!(
+            "Recovered key record from {} to {} with {} guardian approvals",
+            old_owner,
+            new_owner,
+            approvals.len()
+        );
+        
+        Ok(())
+    }
+
+    // Move the caller's key expiry (0 = never expires), within the owner's
+    // `max_lifetime`. This switches the record to timestamp expiry, clearing
+    // any `expires_at_slot`.
+    pub fn renew_key(ctx: Context<RenewKey>, new_expires_at: i64) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        check_expiry(new_expires_at, now)?;
+        check_lifetime(load_settings(&ctx.accounts.settings)?.as_ref(), new_expires_at, now)?;
+        
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.expires_at = new_expires_at;
+        key_record.expires_at_slot = 0;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Renew, now)?;
+        
+        msg!("Renewed public key for user: {} (expires at {})", key_record.owner, new_expires_at);
+        
+        Ok(())
+    }
+
+    // Expire the caller's key at slot `expires_at_slot` rather than at a
+    // timestamp, which validators can skew slightly; clears `expires_at` so
+    // only one mode is active. 0 removes the expiry. Refused with
+    // LifetimeTooLong under a `max_lifetime` policy, which is in seconds.
+    pub fn set_slot_expiry(ctx: Context<RenewKey>, expires_at_slot: u64) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        if let Some(settings) = load_settings(&ctx.accounts.settings)? {
+            require!(settings.max_lifetime == 0, KeyRegistryError::LifetimeTooLong);
+        }
+        if expires_at_slot != 0 {
+            require!(expires_at_slot > current_slot()?, KeyRegistryError::InvalidExpiry);
+        }
+        
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.expires_at = 0;
+        key_record.expires_at_slot = expires_at_slot;
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Renew, now)?;
+        
+        msg!("Set slot expiry for user: {} (expires at slot {})", key_record.owner, expires_at_slot);
+        
+        Ok(())
+    }
+
+    // Heartbeat: bump `updated_at` to show the record is still maintained,
+    // changing nothing else.
+    pub fn touch(ctx: Context<Touch>) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.updated_at = now;
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Touched key record for user: {}", key_record.owner);
+        
+        Ok(())
+    }
+
+    // Bring a key record up to `KeyRecord::CURRENT_VERSION` and `KeyRecord::LEN`,
+    // growing it (and topping up its rent from `payer`) as needed. Records that
+    // are already current are left untouched. Migration only changes the layout, so anyone
+    // may pay for it.
+    pub fn migrate(ctx: Context<Migrate>) -> Result<()> {
+        let record_info = ctx.accounts.key_record.to_account_info();
+        let (from_version, body) = KeyRecord::upgrade_layout(&record_info.try_borrow_data()?)?;
+        // the length alone can't tell: a record with history overflow entries
+        // may be long enough yet still miss fields added after it was written
+        let decodes = KeyRecord::try_deserialize(&mut &record_info.try_borrow_data()?[..]).is_ok();
+        if from_version >= KeyRecord::CURRENT_VERSION && record_info.data_len() >= ACCOUNT_SIZE && decodes {
+            msg!("Key record is already at version {}", from_version);
+            return Ok(());
+        }
+        let mut key_record = KeyRecord::deserialize(&mut body.as_slice())?;
+        key_record.version = KeyRecord::CURRENT_VERSION;
+        key_record.refresh_status(optional_config_now(ctx.accounts.config.as_ref())?, current_slot()?);
+        
+        let new_len = KeyRecord::account_size(key_record.history_capacity());
+        if record_info.data_len() < new_len {
+            grow_account(&ctx.accounts.payer, &record_info, &ctx.accounts.system_program, new_len)?;
+        }
+        key_record.try_serialize(&mut &mut record_info.try_borrow_mut_data()?[..])?;
+        
+        msg!(
+            "Migrated key record for user {} from version {} to {}",
+            key_record.owner,
+            from_version,
+            KeyRecord::CURRENT_VERSION
+        );
+        
+        Ok(())
+    }
+
+    // Grow the caller's record to `new_len` bytes (discriminator included),
+    // zero-filling the new space and topping up rent from the owner, so a
+    // client can make room for a newer field without a full `migrate`. A
+    // no-op at the current size; records can't shrink. Records of an older
+    // version must be migrated first.
+    pub fn grow_record(ctx: Context<GrowRecord>, new_len: u64) -> Result<()> {
+        let record_info = ctx.accounts.key_record.to_account_info();
+        {
+            let data = record_info.try_borrow_data()?;
+            require!(
+                data.len() >= KeyRecord::OWNER_OFFSET + 32
+                    && data[..8] == KeyRecord::DISCRIMINATOR
+                    && data.len() != 8 + KeyRecord::V0_LEN
+                    && data.len() != 8 + KeyRecord::V1_LEN
+                    && data[8] == KeyRecord::CURRENT_VERSION,
+                KeyRegistryError::InvalidRecord
+            );
+            let owner_at = KeyRecord::OWNER_OFFSET;
+            require!(
+                data[owner_at..owner_at + 32] == ctx.accounts.owner.key().to_bytes(),
+                KeyRegistryError::Unauthorized
+            );
+        }
+        
+        let new_len = new_len as usize;
+        let current_len = record_info.data_len();
+        require!(new_len >= current_len, KeyRegistryError::CannotShrink);
+        if new_len == current_len {
+            msg!("Key record is already {} bytes", current_len);
+            return Ok(());
+        }
+        grow_account(&ctx.accounts.owner, &record_info, &ctx.accounts.system_program, new_len)?;
+        
+        msg!("Grew key record for user {} from {} to {} bytes", ctx.accounts.owner.key(), current_len, new_len);
+        
+        Ok(())
+    }
+
+    // When the key was first registered and last changed (Unix seconds), and
+    // its metadata URI (empty if none).
+    pub fn get_metadata(
+        ctx: Context<GetMetadata>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        _label: String,
+    ) -> Result<(i64, i64, String)> {
+        let key_record = &ctx.accounts.key_record;
+        check_verifier(key_record, ctx.accounts.verifier.as_ref())?;
+        Ok((key_record.created_at, key_record.updated_at, key_record.uri.clone()))
+    }
+
+    // The stored public key (its `key_len` bytes), as return data so that a
+    // CPI caller or a simulated transaction can read it without parsing the
+    // account.
+    pub fn get_public_key(ctx: Context<VerifyKey>, _registry_id: [u8; 8], _owner: Pubkey, _label: String) -> Result<Vec<u8>> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        Ok(ctx.accounts.key_record.key_bytes().to_vec())
+    }
+
+    // The record's stored (canonical) PDA bump, so a CPI caller can build its
+    // signer seeds without paying for `find_program_address`.
+    pub fn get_bump(ctx: Context<VerifyKey>, _registry_id: [u8; 8], _owner: Pubkey, _label: String) -> Result<u8> {
+        Ok(ctx.accounts.key_record.bump)
+    }
+
+    // Opt in to an access log: once it exists, `verify_key` calls that pass it
+    // along with a signing `verifier` record who checked the key, and when.
+    pub fn enable_access_log(ctx: Context<EnableAccessLog>) -> Result<()> {
+        let access_log = &mut ctx.accounts.access_log;
+        access_log.owner = ctx.accounts.owner.key();
+        access_log.bump = ctx.bumps.access_log;
+        
+        msg!("Enabled access log for user: {}", access_log.owner);
+        
+        Ok(())
+    }
+
+    // Recent verifiers and when they checked, most recent first (at most
+    // `AccessLog::LOG_LEN`).
+    pub fn get_access_log(ctx: Context<GetAccessLog>) -> Result<Vec<(Pubkey, i64)>> {
+        Ok(ctx.accounts.access_log.entries())
+    }
+
+    // Set how many previous keys the record keeps, 0 (none) to
+    // `MAX_HISTORY_CAPACITY`, clearing the current history. Slots beyond the
+    // built-in `HISTORY_LEN` live in `history_overflow`, so the account is
+    // resized to fit, with the owner paying or being refunded the rent
+    // difference (the registration stake stays locked in the record either
+    // way); capacities up to `HISTORY_LEN` use the fixed slots only and can't
+    // shrink the account below `ACCOUNT_SIZE`.
+    pub fn set_history_capacity(ctx: Context<SetHistoryCapacity>, capacity: u8) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        require!(
+            capacity as usize <= KeyRecord::MAX_HISTORY_CAPACITY,
+            KeyRegistryError::InvalidHistoryCapacity
+        );
+        resize_record(
+            &ctx.accounts.owner,
+            &ctx.accounts.key_record.to_account_info(),
+            &ctx.accounts.system_program,
+            KeyRecord::account_size(capacity as usize),
+            ctx.accounts.key_record.staked,
+        )?;
+        
+        let key_record = &mut ctx.accounts.key_record;
+        key_record.reset_history(capacity);
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        
+        msg!("Set history capacity for user {} to {}", key_record.owner, capacity);
+        
+        Ok(())
+    }
+
+    // Previously registered keys, most recent first (at most `history_capacity()`).
+    pub fn get_history(ctx: Context<GetHistory>, _registry_id: [u8; 8], _owner: Pubkey, _label: String) -> Result<Vec<Vec<u8>>> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        Ok(ctx.accounts.key_record.previous_keys())
+    }
+
+    // Mark the caller's key as no longer trusted (the record is kept).
+    pub fn revoke_key(ctx: Context<RevokeKey>) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        
+        key_record.revoked = true;
+        
+        msg!("Revoked public key for user: {}", ctx.accounts.owner.key());
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Revoke, now)?;
+        
+        emit_cpi!(KeyRevoked {
+            owner: key_record.owner,
+            at: now,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Undo a revocation by installing `new_public_key`, which may be the old
+    // key if its owner regained control of it. Ed25519 keys need a fresh proof
+    // of possession: `signature` by the new key over the owner's pubkey bytes
+    // followed by the record's current `nonce` (u64, little-endian), checked
+    // as in `register_key`. The nonce must be passed too, and advances on
+    // success, so a captured reactivation can't be replayed after a later
+    // revocation. When the key changes, pass `old_key_index` and
+    // `new_key_index` to move the reverse index; when it stays the same, pass
+    // neither.
+    pub fn reactivate_key(
+        ctx: Context<ReactivateKey>,
+        new_public_key: Vec<u8>,
+        signature: [u8; 64],
+        nonce: u64,
+    ) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        let now = config_now(&ctx.accounts.config)?;
+        
+        let key_record = &mut ctx.accounts.key_record;
+        require!(key_record.revoked, KeyRegistryError::NotRevoked);
+        require!(nonce == key_record.nonce, KeyRegistryError::BadNonce);
+        require!(
+            new_public_key.len() == key_record.key_len as usize,
+            KeyRegistryError::InvalidKeyLength
+        );
+        let key_type = KeyType::from_u8(key_record.key_type).ok_or(KeyRegistryError::InvalidRecord)?;
+        check_public_key(key_type, &new_public_key)?;
+        if key_type == KeyType::Ed25519 {
+            let message = [ctx.accounts.owner.key().as_ref(), &nonce.to_le_bytes()].concat();
+            let possessed = preceding_ed25519_matches(
+                &ctx.accounts.instructions,
+                &new_public_key,
+                &message,
+                &signature,
+            )?;
+            require!(possessed, KeyRegistryError::InvalidProofOfPossession);
+        }
+        
+        let rotated = key_record.key_bytes() != new_public_key.as_slice();
+        require!(
+            rotated != ctx.accounts.old_key_index.is_none() && rotated != ctx.accounts.new_key_index.is_none(),
+            KeyRegistryError::InvalidRecord
+        );
+        if rotated {
+            check_update_cooldown(load_settings(&ctx.accounts.settings)?.as_ref(), key_record, now)?;
+        }
+        if let Some(new_key_index) = ctx.accounts.new_key_index.as_mut() {
+            require_keys_eq!(new_key_index.owner, Pubkey::default(), KeyRegistryError::KeyAlreadyIndexed);
+            new_key_index.owner = key_record.owner;
+            new_key_index.record = key_record.key();
+            new_key_index.bump = ctx.bumps.new_key_index;
+        }
+        
+        let old_key = key_record.key_bytes().to_vec();
+        if rotated {
+            key_record.push_history();
+            key_record.set_key(&new_public_key);
+        }
+        key_record.revoked = false;
+        key_record.nonce += 1;
+        key_record.updated_at = now;
+        
+        msg!("Reactivated public key for user: {}", key_record.owner);
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Reactivate, now)?;
+        
+        emit!(KeyUpdated {
+            owner: key_record.owner,
+            old_key,
+            new_key: new_public_key,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Apply several status changes in one owner-signed call; `None` leaves a
+    // flag as it is. A revocation can only be lifted through `reactivate_key`,
+    // which re-checks possession, so `revoked: Some(false)` on a revoked key
+    // fails with `Revoked`.
+    pub fn set_flags(
+        ctx: Context<SetFlags>,
+        revoked: Option<bool>,
+        frozen: Option<bool>,
+        usage: Option<u8>,
+    ) -> Result<()> {
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        if let Some(revoked) = revoked {
+            require!(revoked || !key_record.revoked, KeyRegistryError::Revoked);
+            if revoked && !key_record.revoked {
+                key_record.revoked = true;
+                let address = key_record.key();
+                key_record.extend_audit(address, AuditOp::Revoke, now)?;
+                emit_cpi!(KeyRevoked {
+                    owner: key_record.owner,
+                    at: now,
+                    seq: key_record.seq,
+                });
+            }
+        }
+        if let Some(frozen) = frozen {
+            if frozen != key_record.frozen {
+                key_record.frozen = frozen;
+                let address = key_record.key();
+                key_record.extend_audit(address, if frozen { AuditOp::Freeze } else { AuditOp::Thaw }, now)?;
+                emit!(KeyFrozen {
+                    owner: key_record.owner,
+                    frozen,
+                    seq: key_record.seq,
+                });
+            }
+        }
+        if let Some(usage) = usage {
+            let settings = load_settings(&ctx.accounts.settings)?;
+            if let Some(settings) = settings.filter(|settings| settings.allowed_usage != 0) {
+                require!(usage & !settings.allowed_usage == 0, KeyRegistryError::UsageNotPermitted);
+            }
+            key_record.usage_flags = usage;
+            let address = key_record.key();
+            key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        }
+        key_record.updated_at = now;
+        key_record.refresh_status(now, current_slot()?);
+        
+        msg!(
+            "Set flags for user {}: revoked={} frozen={} usage={:#04b}",
+            key_record.owner,
+            key_record.revoked,
+            key_record.frozen,
+            key_record.usage_flags
+        );
+        
+        Ok(())
+    }
+
+    // Temporarily suspend a key: while frozen it never verifies and cannot be
+    // updated. Unlike revocation this is undone by `thaw_key`. Callable by
+    // the owner or the registry admin.
+    pub fn freeze_key(ctx: Context<FreezeKey>) -> Result<()> {
+        let now = config_now(&ctx.accounts.config)?;
+        let key_record = &mut ctx.accounts.key_record;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == key_record.owner || authority == ctx.accounts.config.admin,
+            KeyRegistryError::Unauthorized
+        );
+        require!(!key_record.frozen, KeyRegistryError::Frozen);
+        
+        key_record.frozen = true;
+        
+        msg!("Froze public key for user: {}", key_record.owner);
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Freeze, now)?;
+        
+        emit!(KeyFrozen {
+            owner: key_record.owner,
+            frozen: true,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Lift a freeze set by `freeze_key` (owner or registry admin).
+    pub fn thaw_key(ctx: Context<FreezeKey>) -> Result<()> {
+        let now = config_now(&ctx.accounts.config)?;
+        let key_record = &mut ctx.accounts.key_record;
+        let authority = ctx.accounts.authority.key();
+        require!(
+            authority == key_record.owner || authority == ctx.accounts.config.admin,
+            KeyRegistryError::Unauthorized
+        );
+        require!(key_record.frozen, KeyRegistryError::NotFrozen);
+        
+        key_record.frozen = false;
+        
+        msg!("Thawed public key for user: {}", key_record.owner);
+        
+        let address = key_record.key();
+        key_record.extend_audit(address, AuditOp::Thaw, now)?;
+        
+        emit!(KeyFrozen {
+            owner: key_record.owner,
+            frozen: false,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Publish the caller's X25519 encryption (ECDH) key. It lives in its own
+    // `EncKeyRecord`, apart from signing keys, so either can be rotated
+    // without touching the other. X25519 keys cannot sign, so there is no
+    // proof of possession.
+    pub fn register_enc_key(ctx: Context<RegisterEncKey>, public_key: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        let now = config_now(&ctx.accounts.config)?;
+        check_public_key(KeyType::X25519, &public_key)?;
+        
+        let enc_key_record = &mut ctx.accounts.enc_key_record;
+        enc_key_record.owner = ctx.accounts.owner.key();
+        enc_key_record.public_key = public_key;
+        enc_key_record.bump = ctx.bumps.enc_key_record;
+        enc_key_record.created_at = now;
+        enc_key_record.updated_at = now;
+        
+        msg!("Registered encryption key for user: {}", enc_key_record.owner);
+        msg!("Public key (hex): {:02x?}", public_key);
+        
+        Ok(())
+    }
+
+    // Rotate the caller's encryption key.
+    pub fn update_enc_key(ctx: Context<UpdateEncKey>, new_public_key: [u8; 32]) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        let now = config_now(&ctx.accounts.config)?;
+        check_public_key(KeyType::X25519, &new_public_key)?;
+        
+        let enc_key_record = &mut ctx.accounts.enc_key_record;
+        enc_key_record.public_key = new_public_key;
+        enc_key_record.updated_at = now;
+        
+        msg!("Updated encryption key for user: {}", enc_key_record.owner);
+        msg!("New public key (hex): {:02x?}", new_public_key);
+        
+        Ok(())
+    }
+
+    // Check whether `public_key_to_verify` is `owner`'s encryption key.
+    pub fn verify_enc_key(ctx: Context<VerifyEncKey>, owner: Pubkey, public_key_to_verify: [u8; 32]) -> Result<bool> {
+        let matches = keys_equal(&ctx.accounts.enc_key_record.public_key, &public_key_to_verify, 32);
+        
+        if matches {
+            msg!("✅ Encryption key matches for user: {}", owner);
+        } else {
+            msg!("❌ Encryption key does NOT match for user: {}", owner);
+        }
+        
+        Ok(matches)
+    }
+
+    // Add a 32-byte key to the caller's key bundle, creating the bundle on
+    // first use. A bundle holds up to `KeyBundle::MAX_KEYS` keys under one
+    // rent deposit, for owners with several keys of one type; bundled keys
+    // are not in the reverse index.
+    pub fn add_key(ctx: Context<AddKey>, key: [u8; 32]) -> Result<()> {
+        require!(key != [0u8; 32], KeyRegistryError::InvalidPublicKey);
+        
+        let key_bundle = &mut ctx.accounts.key_bundle;
+        require!(!key_bundle.keys.contains(&key), KeyRegistryError::KeyAlreadyIndexed);
+        require!(key_bundle.keys.len() < KeyBundle::MAX_KEYS, KeyRegistryError::BundleFull);
+        key_bundle.owner = ctx.accounts.owner.key();
+        key_bundle.bump = ctx.bumps.key_bundle;
+        key_bundle.keys.push(key);
+        
+        msg!("Added key to bundle of user {} ({} keys)", key_bundle.owner, key_bundle.keys.len());
+        
+        Ok(())
+    }
+
+    // Drop `key` from the caller's bundle; the last key takes its slot.
+    pub fn remove_key(ctx: Context<RemoveKey>, key: [u8; 32]) -> Result<()> {
+        let key_bundle = &mut ctx.accounts.key_bundle;
+        let position = key_bundle
+            .keys
+            .iter()
+            .position(|bundled| *bundled == key)
+            .ok_or(KeyRegistryError::KeyNotInBundle)?;
+        key_bundle.keys.swap_remove(position);
+        
+        msg!("Removed key from bundle of user: {}", key_bundle.owner);
+        
+        Ok(())
+    }
+
+    // Whether `candidate` is one of `owner`'s bundled keys.
+    pub fn verify_in_bundle(ctx: Context<VerifyInBundle>, owner: Pubkey, candidate: [u8; 32]) -> Result<bool> {
+        let matches = ctx
+            .accounts
+            .key_bundle
+            .keys
+            .iter()
+            .fold(false, |found, bundled| found | keys_equal(bundled, &candidate, 32));
+        
+        if matches {
+            msg!("✅ Key is in the bundle of user: {}", owner);
+        } else {
+            msg!("❌ Key is NOT in the bundle of user: {}", owner);
+        }
+        
+        Ok(matches)
+    }
+
+    // Store (or replace) the Merkle root of an allowlist of keys for the
+    // caller, so one small account can stand for many keys. See
+    // `verify_membership` for the tree layout.
+    pub fn register_root(ctx: Context<RegisterRoot>, root: [u8; 32]) -> Result<()> {
+        let key_root = &mut ctx.accounts.key_root;
+        key_root.owner = ctx.accounts.owner.key();
+        key_root.root = root;
+        key_root.bump = ctx.bumps.key_root;
+        
+        msg!("Set key allowlist root for user {}: {:02x?}", key_root.owner, root);
+        
+        Ok(())
+    }
+
+    // Whether `public_key` is in `owner`'s allowlist tree, given its leaf's
+    // sibling hashes from the leaf up. Leaves are `merkle_leaf(public_key)`
+    // and parents `merkle_node` of their two children, each hashed under its
+    // own prefix so an interior node can't be passed off as a leaf. Children
+    // are ordered by value, so proofs need no left/right flags.
+    pub fn verify_membership(
+        ctx: Context<VerifyMembership>,
+        owner: Pubkey,
+        public_key: Vec<u8>,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<bool> {
+        require!(proof.len() <= MAX_MERKLE_DEPTH, KeyRegistryError::BatchTooLarge);
+        require!(public_key.len() <= MAX_KEY_LEN, KeyRegistryError::InvalidKeyLength);
+        
+        let computed = proof.iter().fold(merkle_leaf(&public_key), |node, sibling| merkle_node(&node, sibling));
+        let matches = computed == ctx.accounts.key_root.root;
+        
+        if matches {
+            msg!("✅ Key is in the allowlist of user: {}", owner);
+        } else {
+            msg!("❌ Key is NOT in the allowlist of user: {}", owner);
+        }
+        
+        Ok(matches)
+    }
+
+    // CPI `inner_ix_data` into `target_program` with the caller's key record
+    // as a PDA signer, so the target can gate an action on "this record's key
+    // is valid right now" by checking the record account signed (and that it
+    // is owned by this program). The inner instruction's accounts are the
+    // record (first, read-only, signing) followed by `remaining_accounts` with
+    // their own flags.
+    //
+    // The record's signature is a confused-deputy hazard, so it is scoped:
+    // the record only signs while revoked, frozen and expiry checks pass and
+    // only at the owner's request; it is never writable; it can't sign into
+    // this program (where it would authorize registry instructions on
+    // itself); and it may not also appear in `remaining_accounts`. Targets
+    // must treat the signature as an attestation about the key, not as
+    // authority over the owner's other accounts.
+    //
+    // Only records seeded by registry, owner and label can sign: org records
+    // (see `register_key_in_org`) are seeded by an org id the record doesn't
+    // keep, so they fail with OrgRecordCannotAttest.
+    pub fn invoke_with_attestation<'info>(
+        ctx: Context<'_, '_, 'info, 'info, InvokeWithAttestation<'info>>,
+        inner_ix_data: Vec<u8>,
+    ) -> Result<()> {
+        let target = &ctx.accounts.target_program;
+        require_keys_neq!(target.key(), crate::ID, KeyRegistryError::InvalidAttestationTarget);
+        
+        let key_record = &ctx.accounts.key_record;
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        require!(!key_record.frozen, KeyRegistryError::Frozen);
+        require!(
+            !key_record.is_expired(optional_config_now(ctx.accounts.config.as_ref())?, current_slot()?),
+            KeyRegistryError::KeyExpired
+        );
+        
+        let record_address = key_record.key();
+        let bump = [key_record.bump];
+        let seeds: [&[u8]; 5] = [
+            b"key_record",
+            registry_seed(&key_record.registry_id),
+            key_record.registrant.as_ref(),
+            key_record.label.as_bytes(),
+            &bump,
+        ];
+        // anything else the program owns with a KeyRecord layout is an org record
+        let derived = Pubkey::create_program_address(&seeds, &crate::ID)
+            .map_err(|_| error!(KeyRegistryError::OrgRecordCannotAttest))?;
+        require_keys_eq!(derived, record_address, KeyRegistryError::OrgRecordCannotAttest);
+        
+        let mut accounts = vec![AccountMeta::new_readonly(record_address, true)];
+        let mut infos = vec![key_record.to_account_info()];
+        for account in ctx.remaining_accounts {
+            require_keys_neq!(account.key(), record_address, KeyRegistryError::InvalidAttestationTarget);
+            accounts.push(if account.is_writable {
+                AccountMeta::new(account.key(), account.is_signer)
+            } else {
+                AccountMeta::new_readonly(account.key(), account.is_signer)
+            });
+            infos.push(account.clone());
+        }
+        infos.push(target.to_account_info());
+        
+        let ix = Instruction {
+            program_id: target.key(),
+            accounts,
+            data: inner_ix_data,
+        };
+        invoke_signed(&ix, &infos, &[&seeds])?;
+        
+        msg!("Key record of user {} attested to program {}", key_record.owner, target.key());
+        
+        Ok(())
+    }
+
+    // Exchange the keys of the caller's record and `other_owner`'s in one
+    // instruction, which fails unless both owners sign. Both records must be
+    // usable, in the same registry and of the same key type; each old key
+    // goes to its record's history and the reverse index entries are
+    // repointed at their key's new record.
+    pub fn swap_keys(ctx: Context<SwapKeys>, other_owner: Pubkey) -> Result<()> {
+        require!(!ctx.accounts.config.paused, KeyRegistryError::Paused);
+        require_keys_neq!(other_owner, ctx.accounts.owner.key(), KeyRegistryError::InvalidRecord);
+        
+        let key_record = &mut ctx.accounts.key_record;
+        let other_record = &mut ctx.accounts.other_record;
+        require!(!key_record.revoked && !other_record.revoked, KeyRegistryError::Revoked);
+        require!(!key_record.frozen && !other_record.frozen, KeyRegistryError::Frozen);
+        let (now, slot) = (config_now(&ctx.accounts.config)?, current_slot()?);
+        require!(
+            !key_record.is_expired(now, slot) && !other_record.is_expired(now, slot),
+            KeyRegistryError::KeyExpired
+        );
+        require!(key_record.registry_id == other_record.registry_id, KeyRegistryError::InvalidRecord);
+        require!(key_record.key_type == other_record.key_type, KeyRegistryError::KeyTypeMismatch);
+        check_update_cooldown(load_settings(&ctx.accounts.settings)?.as_ref(), key_record, now)?;
+        check_update_cooldown(load_settings(&ctx.accounts.other_settings)?.as_ref(), other_record, now)?;
+        
+        let key = key_record.key_bytes().to_vec();
+        let other_key = other_record.key_bytes().to_vec();
+        key_record.push_history();
+        key_record.set_key(&other_key);
+        key_record.updated_at = now;
+        other_record.push_history();
+        other_record.set_key(&key);
+        other_record.updated_at = now;
+        
+        // `key_index` (for `key`) now belongs to the other record, and vice versa
+        let (record_address, other_address) = (key_record.key(), other_record.key());
+        let key_index = &mut ctx.accounts.key_index;
+        key_index.owner = other_owner;
+        key_index.record = other_address;
+        let other_key_index = &mut ctx.accounts.other_key_index;
+        other_key_index.owner = key_record.owner;
+        other_key_index.record = record_address;
+        
+        key_record.extend_audit(record_address, AuditOp::Update, now)?;
+        other_record.extend_audit(other_address, AuditOp::Update, now)?;
+        
+        msg!("Swapped public keys of users {} and {}", key_record.owner, other_owner);
+        
+        emit!(KeyUpdated {
+            owner: key_record.owner,
+            old_key: key.clone(),
+            new_key: other_key.clone(),
+            seq: key_record.seq,
+        });
+        emit!(KeyUpdated {
+            owner: other_owner,
+            old_key: other_key,
+            new_key: key,
+            seq: other_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Close the caller's key record and its reverse index entry (plus its
+    // handle, if any), returning the rent and any registration stake
+    // (`staked`) to the owner. The PDA is freed, so the owner can
+    // `register_key` again afterwards.
+    pub fn close_key(ctx: Context<CloseKey>) -> Result<()> {
+        require!(
+            (ctx.accounts.key_record.handle == [0u8; 32]) == ctx.accounts.handle_index.is_none(),
+            KeyRegistryError::InvalidHandle
+        );
+        
+        msg!("Closed key record for user: {}", ctx.accounts.owner.key());
+        
+        Ok(())
+    }
+
+    // Admin cleanup: close up to `MAX_SWEEP_RECORDS` expired key records and
+    // their reverse index entries, refunding rent and stake to each owner.
+    // `remaining_accounts` holds (record, key index, owner) triples; records
+    // that haven't expired, or that hold a handle (whose index would be left
+    // dangling), are skipped rather than failing the sweep. Returns a
+    // `BATCH_*` status per triple; a malformed triple fails the call unless
+    // `continue_on_error` is set.
+    pub fn sweep_expired<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SweepExpired<'info>>,
+        continue_on_error: bool,
+    ) -> Result<Vec<u8>> {
+        let triples = ctx.remaining_accounts.chunks_exact(3);
+        require!(triples.remainder().is_empty(), KeyRegistryError::InvalidRecord);
+        require!(triples.len() <= MAX_SWEEP_RECORDS, KeyRegistryError::BatchTooLarge);
+        
+        let now = config_now(&ctx.accounts.config)?;
+        let slot = current_slot()?;
+        let mut results = Vec::with_capacity(triples.len());
+        for accounts in triples {
+            let checked = (|| {
+                let key_record = Account::<KeyRecord>::try_from(&accounts[0])?;
+                let key_index = Account::<KeyIndex>::try_from(&accounts[1])?;
+                require_keys_eq!(key_index.record, key_record.key(), KeyRegistryError::InvalidRecord);
+                require_keys_eq!(accounts[2].key(), key_record.owner, KeyRegistryError::InvalidRecord);
+                Ok((key_record, key_index))
+            })();
+            let (key_record, key_index) = match checked {
+                Ok(checked) => checked,
+                Err(err) if continue_on_error => {
+                    msg!("Skipping sweep entry {}: {}", accounts[0].key(), err);
+                    results.push(batch_error_code(&err));
+                    continue;
+                }
+                Err(err) => return Err(err),
+            };
+            let owner = &accounts[2];
+            
+            if !key_record.is_expired(now, slot) || key_record.handle != [0u8; 32] {
+                results.push(BATCH_SKIPPED);
+                continue;
+            }
+            key_index.close(owner.clone())?;
+            key_record.close(owner.clone())?;
+            results.push(BATCH_APPLIED);
+        }
+        
+        let closed = results.iter().filter(|status| **status == BATCH_APPLIED).count();
+        msg!("Swept {} expired key records of {}", closed, ctx.remaining_accounts.len() / 3);
+        
+        Ok(results)
+    }
+
+    // Fail with AlreadyRegistered if `owner` has a key record under `label`,
+    // so another program can CPI this to gate first-time-only flows. Absent
+    // means the PDA holds no data and isn't owned by this program; lamports
+    // alone don't count, since anyone can send some to the address and
+    // registration still succeeds over them.
+    pub fn assert_not_registered(ctx: Context<MaybeKey>, _registry_id: [u8; 8], owner: Pubkey, label: String) -> Result<()> {
+        let info = ctx.accounts.key_record.to_account_info();
+        require!(
+            info.data_is_empty() && *info.owner != crate::ID,
+            KeyRegistryError::AlreadyRegistered
+        );
+        
+        msg!("No key record {:?} for user {}", label, owner);
+        
+        Ok(())
+    }
+
+    // Whether a key record exists for the wallet that registered it and its
+    // label. Unlike `verify_key` this doesn't fail for first-time users: a
+    // missing (zero-lamport) or foreign account just reads as `false`.
+    pub fn account_exists(ctx: Context<MaybeKey>, _registry_id: [u8; 8], owner: Pubkey, label: String) -> Result<bool> {
+        let info = ctx.accounts.key_record.to_account_info();
+        let exists = info.lamports() > 0
+            && *info.owner == crate::ID
+            && info
+                .try_borrow_data()?
+                .get(..8)
+                .is_some_and(|disc| disc == KeyRecord::DISCRIMINATOR);
+        
+        msg!("Key record {:?} for user {} exists: {}", label, owner, exists);
+        
+        Ok(exists)
+    }
+
+    // Check whether `public_key_to_verify` matches the stored key.
+    // Only the first `key_len` stored bytes are compared; a revoked, frozen or
+    // expired key never matches. For an alias (see `set_alias`), pass the
+    // aliased record as `alias_target`; its key and status are used instead.
+    pub fn verify_key(
+        ctx: Context<VerifyKey>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        label: String,
+        public_key_to_verify: Vec<u8>,
+    ) -> Result<bool> {
+        let key_record = resolve_verified(&ctx.accounts.key_record, ctx.accounts.alias_target.as_ref(), ctx.accounts.verifier.as_ref())?;
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let matches = check_key_match(key_record, &label, &public_key_to_verify, now)?;
+        if let (Some(access_log), Some(verifier)) = (ctx.accounts.access_log.as_mut(), &ctx.accounts.verifier) {
+            access_log.record(verifier.key(), now);
+        }
+        // One byte, 1 = match, for CPI callers reading `get_return_data()`;
+        // the same bytes Anchor's Borsh encoding of the bool produces.
+        set_return_data(&[matches as u8]);
+        Ok(matches)
+    }
+
+    // `verify_key` taking the candidate as a base58 string, the way wallets
+    // and explorers display keys. Fails with InvalidEncoding rather than
+    // returning false when the string isn't base58.
+    pub fn verify_key_b58(
+        ctx: Context<VerifyKey>,
+        registry_id: [u8; 8],
+        owner: Pubkey,
+        label: String,
+        key_b58: String,
+    ) -> Result<bool> {
+        // base58 needs at most ~1.37 characters per byte; longer input can't
+        // decode to a key that fits and would only burn compute.
+        require!(key_b58.len() <= 2 * MAX_KEY_LEN, KeyRegistryError::InvalidEncoding);
+        let candidate = bs58::decode(&key_b58)
+            .into_vec()
+            .map_err(|_| error!(KeyRegistryError::InvalidEncoding))?;
+        verify_key(ctx, registry_id, owner, label, candidate)
+    }
+
+    // `verify_key` against the key-length window of `blob` starting at
+    // `offset`, for clients holding keys inside a larger buffer. Fails with
+    // OffsetOutOfBounds if the window runs past the end of `blob`.
+    pub fn verify_key_slice(
+        ctx: Context<VerifyKey>,
+        registry_id: [u8; 8],
+        owner: Pubkey,
+        label: String,
+        blob: Vec<u8>,
+        offset: u32,
+    ) -> Result<bool> {
+        let key_len = resolve_verified(&ctx.accounts.key_record, ctx.accounts.alias_target.as_ref(), ctx.accounts.verifier.as_ref())?.key_len as usize;
+        let candidate = (offset as usize)
+            .checked_add(key_len)
+            .and_then(|end| blob.get(offset as usize..end))
+            .ok_or(KeyRegistryError::OffsetOutOfBounds)?
+            .to_vec();
+        verify_key(ctx, registry_id, owner, label, candidate)
+    }
+
+    // Ask the record's `verifier_program` (see `set_verifier`) about `data`.
+    // The CPI's instruction data is key_type || key_len || key bytes || data,
+    // with the record as its only account (read-only); the verifier answers
+    // with return data whose first byte is 1 for success. Anything else, or
+    // no answer, is false. Unusable keys are false without asking.
+    pub fn verify_delegated(
+        ctx: Context<VerifyDelegated>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        _label: String,
+        data: Vec<u8>,
+    ) -> Result<bool> {
+        let key_record = &ctx.accounts.key_record;
+        check_verifier(key_record, ctx.accounts.verifier.as_ref())?;
+        let verifier = &ctx.accounts.verifier_program;
+        if !key_record.is_usable(optional_config_now(ctx.accounts.config.as_ref())?, current_slot()?) {
+            msg!("❌ Public key for user {} is revoked, frozen or expired", key_record.owner);
+            return Ok(false);
+        }
+        
+        let mut ix_data = vec![key_record.key_type, key_record.key_len];
+        ix_data.extend_from_slice(key_record.key_bytes());
+        ix_data.extend_from_slice(&data);
+        let ix = Instruction {
+            program_id: verifier.key(),
+            accounts: vec![AccountMeta::new_readonly(key_record.key(), false)],
+            data: ix_data,
+        };
+        invoke(&ix, &[key_record.to_account_info(), verifier.to_account_info()])?;
+        
+        let matches = matches!(
+            get_return_data(),
+            Some((program, answer)) if program == verifier.key() && answer.first() == Some(&1)
+        );
+        if matches {
+            msg!("✅ Verifier program {} accepted the key of user: {}", verifier.key(), key_record.owner);
+        } else {
+            msg!("❌ Verifier program {} rejected the key of user: {}", verifier.key(), key_record.owner);
+        }
+        
+        Ok(matches)
+    }
+
+    // Emit a `KeyAttested` event stating the record's key and status as of
+    // the current slot. Programs can't sign arbitrary data, so the attestation
+    // is the confirmed transaction itself: given its signature, anyone can
+    // fetch it (`getTransaction`), check it succeeded and invoked this program,
+    // and decode the event from its "Program data:" log line; the event's
+    // slot must equal the transaction's. Aliases are followed as in `verify_key`.
+    pub fn attest(ctx: Context<VerifyKey>, _registry_id: [u8; 8], _owner: Pubkey, _label: String) -> Result<()> {
+        let key_record = resolve_verified(&ctx.accounts.key_record, ctx.accounts.alias_target.as_ref(), ctx.accounts.verifier.as_ref())?;
+        let slot = current_slot()?;
+        let status = key_record.status(optional_config_now(ctx.accounts.config.as_ref())?, slot);
+        
+        msg!("Attested {:?} key for user {} at slot {}", status, key_record.owner, slot);
+        
+        emit!(KeyAttested {
+            owner: key_record.owner,
+            key_type: key_record.key_type,
+            public_key: key_record.key_bytes().to_vec(),
+            slot,
+            status: status as u8,
+            seq: key_record.seq,
+        });
+        
+        Ok(())
+    }
+
+    // Stricter `verify_key`: also false for a key without an expiry (time or
+    // slot), so it only accepts bounded keys that match, are in date and
+    // are neither revoked nor frozen. Aliases are followed as in `verify_key`.
+    pub fn verify_key_strict(
+        ctx: Context<VerifyKey>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        label: String,
+        candidate: Vec<u8>,
+    ) -> Result<bool> {
+        let key_record = resolve_verified(&ctx.accounts.key_record, ctx.accounts.alias_target.as_ref(), ctx.accounts.verifier.as_ref())?;
+        if !key_record.has_expiry() {
+            msg!("❌ Public key for user {} has no expiry", key_record.owner);
+            emit!(KeyVerified {
+                owner: key_record.owner,
+                matched: false,
+                seq: key_record.seq,
+            });
+            return Ok(false);
+        }
+        check_key_match(key_record, &label, &candidate, optional_config_now(ctx.accounts.config.as_ref())?)
+    }
+
+    // `verify_key` for relying programs that need "this key belongs to this
+    // wallet": `expected_owner` picks the record (PDA seeds) and must also be
+    // its current owner, so a record transferred away no longer vouches for it.
+    pub fn verify_key_and_owner(
+        ctx: Context<VerifyKey>,
+        _registry_id: [u8; 8],
+        expected_owner: Pubkey,
+        label: String,
+        public_key_to_verify: Vec<u8>,
+    ) -> Result<bool> {
+        let key_record = resolve_verified(&ctx.accounts.key_record, ctx.accounts.alias_target.as_ref(), ctx.accounts.verifier.as_ref())?;
+        if key_record.owner != expected_owner {
+            msg!("❌ Record {:?} is owned by {}, not {}", label, key_record.owner, expected_owner);
+            emit!(KeyVerified {
+                owner: key_record.owner,
+                matched: false,
+                seq: key_record.seq,
+            });
+            return Ok(false);
+        }
+        check_key_match(key_record, &label, &public_key_to_verify, optional_config_now(ctx.accounts.config.as_ref())?)
+    }
+
+    // `verify_key` that also accepts the record's backup key (see
+    // `set_backup_key`). A backup match emits `BackupKeyUsed` so monitoring
+    // can flag that the primary may be lost.
+    pub fn verify_key_with_backup(
+        ctx: Context<VerifyKey>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        label: String,
+        public_key_to_verify: Vec<u8>,
+    ) -> Result<bool> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        let key_record = &ctx.accounts.key_record;
+        if !key_record.is_usable(optional_config_now(ctx.accounts.config.as_ref())?, current_slot()?) {
+            msg!("❌ Public key for user {} is revoked, frozen or expired", key_record.owner);
+            emit!(KeyVerified {
+                owner: key_record.owner,
+                matched: false,
+                seq: key_record.seq,
+            });
+            return Ok(false);
+        }
+        
+        let len = key_record.key_len as usize;
+        let primary = keys_equal(key_record.key_bytes(), &public_key_to_verify, len);
+        let backup = !primary
+            && key_record.has_backup_key()
+            && keys_equal(&key_record.backup_key[..len], &public_key_to_verify, len);
+        
+        if primary {
+            msg!("✅ Public key matches registered key {:?} for user: {}", label, key_record.owner);
+        } else if backup {
+            msg!("🛟 Public key matches the backup of {:?} for user: {}", label, key_record.owner);
+            emit!(BackupKeyUsed {
+                owner: key_record.owner,
+                seq: key_record.seq,
+            });
+        } else {
+            msg!("❌ Public key matches neither key of {:?} for user: {}", label, key_record.owner);
+        }
+        
+        emit!(KeyVerified {
+            owner: key_record.owner,
+            matched: primary || backup,
+            seq: key_record.seq,
+        });
+        
+        Ok(primary || backup)
+    }
+
+    // `verify_key` that also bumps the record's `verify_count` on a match,
+    // which like any other mutation advances `seq` and the audit chain.
+    // It writes to the record, so callers pay for a writable account; use
+    // `verify_key` when the count isn't needed.
+    pub fn verify_key_counted(
+        ctx: Context<VerifyKeyCounted>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        label: String,
+        public_key_to_verify: Vec<u8>,
+    ) -> Result<bool> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let key_record = &mut ctx.accounts.key_record;
+        let matches = check_key_match(key_record, &label, &public_key_to_verify, now)?;
+        if matches {
+            key_record.verify_count = key_record.verify_count.saturating_add(1);
+            let address = key_record.key();
+            key_record.extend_audit(address, AuditOp::Metadata, now)?;
+        }
+        
+        Ok(matches)
+    }
+
+    // Error-returning variant of `verify_key` for cross-program use, since a
+    // CPI caller cannot read `verify_key`'s bool: the instruction (and so the
+    // caller's transaction) fails unless the key matches and is usable.
+    // When the config lists allowed programs, the transaction's top-level
+    // instruction must belong to one of them (or to this program), else
+    // CallerNotAllowed. Only the outermost caller is visible this way.
+    //
+    // Build with the `cpi` feature and call
+    //   key_registry::cpi::require_key_matches(
+    //       CpiContext::new(
+    //           registry_program,
+    //           key_registry::cpi::accounts::RequireKeyMatches { key_record, config, instructions, verifier },
+    //       ),
+    //       owner,
+    //       label,
+    //       public_key,
+    //   )
+    // where `key_record` is the (read-only) record PDA for the owner/label,
+    // `config` the registry config, `instructions` the instructions sysvar
+    // and `verifier` a signer on the record's allowlist, if it has one.
+    pub fn require_key_matches(
+        ctx: Context<RequireKeyMatches>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        label: String,
+        public_key_to_verify: Vec<u8>,
+    ) -> Result<()> {
+        let allowed = &ctx.accounts.config.allowed_programs;
+        if allowed.iter().any(|program| *program != Pubkey::default()) {
+            let current = load_current_index_checked(&ctx.accounts.instructions)?;
+            let caller = load_instruction_at_checked(current as usize, &ctx.accounts.instructions)?.program_id;
+            require!(
+                caller == crate::ID || allowed.contains(&caller),
+                KeyRegistryError::CallerNotAllowed
+            );
+        }
+        
+        let key_record = &ctx.accounts.key_record;
+        check_verifier(key_record, ctx.accounts.verifier.as_ref())?;
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        require!(!key_record.frozen, KeyRegistryError::Frozen);
+        require!(
+            !key_record.is_expired(config_now(&ctx.accounts.config)?, current_slot()?),
+            KeyRegistryError::KeyExpired
+        );
+        require!(
+            keys_equal(key_record.key_bytes(), &public_key_to_verify, key_record.key_len as usize),
+            KeyRegistryError::KeyMismatch
+        );
+        
+        msg!("✅ Public key matches registered key {:?} for user: {}", label, key_record.owner);
+        
+        Ok(())
+    }
+
+    // Diagnostic only: how many bytes `candidate` differs from the stored key
+    // by, to help spot encoding mix-ups (base58 vs base64, stray prefixes).
+    // 0 means identical; anything else is a mismatch. Bytes beyond the shorter
+    // of the two count as differing, and the result saturates at 255. Ignores
+    // revocation, freezing and expiry.
+    pub fn diff_key(ctx: Context<VerifyKey>, _registry_id: [u8; 8], _owner: Pubkey, label: String, candidate: Vec<u8>) -> Result<u8> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        let stored = ctx.accounts.key_record.key_bytes();
+        let differing = stored.iter().zip(&candidate).filter(|(a, b)| a != b).count()
+            + stored.len().abs_diff(candidate.len());
+        let distance = u8::try_from(differing).unwrap_or(u8::MAX);
+        
+        msg!("Candidate differs from key {:?} in {} bytes", label, distance);
+        
+        Ok(distance)
+    }
+
+    // Whether the key can currently verify at all: not revoked, not frozen and
+    // not past `expires_at`.
+    pub fn is_key_usable(ctx: Context<VerifyKey>, _registry_id: [u8; 8], _owner: Pubkey, label: String) -> Result<bool> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        let key_record = &ctx.accounts.key_record;
+        let usable = key_record.is_usable(optional_config_now(ctx.accounts.config.as_ref())?, current_slot()?);
+        
+        msg!("Key {:?} for user {} usable: {}", label, key_record.owner, usable);
+        
+        Ok(usable)
+    }
+
+    // Whether `candidate_hash` is the recovery email hash bound to the record,
+    // for "prove you know the recovery email" flows. No hash never matches.
+    pub fn verify_email_hash(
+        ctx: Context<VerifyKey>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        label: String,
+        candidate_hash: [u8; 32],
+    ) -> Result<bool> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        let key_record = &ctx.accounts.key_record;
+        let matches = key_record.email_hash != [0u8; 32] && keys_equal(&key_record.email_hash, &candidate_hash, 32);
+        
+        msg!("Recovery email hash for {:?} of user {} matches: {}", label, key_record.owner, matches);
+        
+        Ok(matches)
+    }
+
+    // Fail with `UsageNotPermitted` unless the key allows every `required`
+    // usage bit, so e.g. a program can refuse to encrypt to a signing key.
+    pub fn require_usage(ctx: Context<VerifyKey>, _registry_id: [u8; 8], _owner: Pubkey, label: String, required: u8) -> Result<()> {
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        let key_record = &ctx.accounts.key_record;
+        require!(
+            key_record.usage_flags & required == required,
+            KeyRegistryError::UsageNotPermitted
+        );
+        
+        msg!("Key {:?} for user {} permits usage {:#04b}", label, key_record.owner, required);
+        
+        Ok(())
+    }
+
+    // Compare several candidate keys against the stored key in one call, e.g.
+    // when a client is unsure which of its cached keys is current. Results
+    // line up with `candidates`; a revoked, frozen or expired key matches none.
+    pub fn verify_keys_batch(
+        ctx: Context<VerifyKey>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        label: String,
+        candidates: Vec<Vec<u8>>,
+    ) -> Result<Vec<bool>> {
+        require!(
+            candidates.len() <= MAX_BATCH_CANDIDATES,
+            KeyRegistryError::BatchTooLarge
+        );
+        
+        check_verifier(&ctx.accounts.key_record, ctx.accounts.verifier.as_ref())?;
+        let key_record = &ctx.accounts.key_record;
+        let usable = key_record.is_usable(optional_config_now(ctx.accounts.config.as_ref())?, current_slot()?);
+        let results: Vec<bool> = candidates
+            .iter()
+            .map(|candidate| usable && keys_equal(key_record.key_bytes(), candidate, key_record.key_len as usize))
+            .collect();
+        
+        msg!(
+            "Checked {} candidate keys against {:?} for user {}: {} matched",
+            results.len(),
+            label,
+            key_record.owner,
+            results.iter().filter(|m| **m).count()
+        );
+        
+        Ok(results)
+    }
+
+    // Which of up to `MAX_FIND_OWNER_RECORDS` key records, passed as
+    // `remaining_accounts`, holds the 32-byte key `candidate`: the owner of the
+    // first usable one that does, or None.
+    pub fn find_owner<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FindOwner<'info>>,
+        candidate: [u8; 32],
+    ) -> Result<Option<Pubkey>> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_FIND_OWNER_RECORDS,
+            KeyRegistryError::BatchTooLarge
+        );
+        
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let slot = current_slot()?;
+        for account in ctx.remaining_accounts {
+            let key_record = Account::<KeyRecord>::try_from(account)?;
+            if key_record.is_usable(now, slot)
+                && check_verifier(&key_record, ctx.accounts.verifier.as_ref()).is_ok()
+                && keys_equal(key_record.key_bytes(), &candidate, candidate.len()) {
+                msg!("✅ Candidate key belongs to user: {}", key_record.owner);
+                return Ok(Some(key_record.owner));
+            }
+        }
+        
+        msg!("❌ Candidate key matched none of {} records", ctx.remaining_accounts.len());
+        
+        Ok(None)
+    }
+
+    // Check that the registered key signed `message`, by key type:
+    // - Ed25519: checked by the Ed25519 native program, so the transaction
+    //   must carry that instruction just before this one.
+    // - secp256k1: `signature` (r || s) with `recovery_id` over
+    //   keccak256(message). The secp256k1 precompile checks signatures against
+    //   an Ethereum address, which can't be derived on-chain from the stored
+    //   compressed key, so the signer is recovered with the secp256k1_recover
+    //   syscall and compared to the stored key instead.
+    // - P-256: `signature` (r || s) over `message` with SHA-256, checked in
+    //   the program since there is no P-256 precompile. For a WebAuthn
+    //   assertion `message` is authenticatorData || SHA-256(clientDataJSON).
+    //   This is costly in compute, so it needs the `p256` feature; builds
+    //   without it fail with `UnsupportedKeyType`.
+    // Other key types can't sign and fail with `UnsupportedKeyType`.
+    // `recovery_id` is only used for secp256k1.
+    pub fn verify_signature(
+        ctx: Context<VerifySignature>,
+        _registry_id: [u8; 8],
+        _owner: Pubkey,
+        _label: String,
+        message: Vec<u8>,
+        signature: [u8; 64],
+        recovery_id: u8,
+    ) -> Result<bool> {
+        let key_record = &ctx.accounts.key_record;
+        check_verifier(key_record, ctx.accounts.verifier.as_ref())?;
+        let key_type = KeyType::from_u8(key_record.key_type).ok_or(KeyRegistryError::InvalidRecord)?;
+        require!(
+            matches!(key_type, KeyType::Ed25519 | KeyType::Secp256k1)
+                || (key_type == KeyType::Secp256r1 && cfg!(feature = "p256")),
+            KeyRegistryError::UnsupportedKeyType
+        );
+        if key_record.revoked {
+            msg!("❌ Public key for user {} has been revoked", key_record.owner);
+            return Ok(false);
+        }
+        if key_record.frozen {
+            msg!("🧊 Public key for user {} is frozen", key_record.owner);
+            return Ok(false);
+        }
+        if key_record.is_expired(optional_config_now(ctx.accounts.config.as_ref())?, current_slot()?) {
+            msg!("⌛ Public key for user {} expired at {}", key_record.owner, key_record.expires_at);
+            return Ok(false);
+        }
+        
+        let verified = match key_type {
+            KeyType::Ed25519 => preceding_ed25519_matches(
+                &ctx.accounts.instructions,
+                key_record.key_bytes(),
+                &message,
+                &signature,
+            )?,
+            KeyType::Secp256k1 => secp256k1_signer_matches(key_record.key_bytes(), &message, &signature, recovery_id),
+            #[cfg(feature = "p256")]
+            KeyType::Secp256r1 => p256_signature_matches(key_record.key_bytes(), &message, &signature),
+            _ => false,
+        };
+        require!(verified, KeyRegistryError::SignatureVerificationFailed);
+        
+        msg!("✅ {:?} signature verified for user: {}", key_type, key_record.owner);
+        
+        Ok(true)
+    }
+
+    // Whether `signature` over `message` is by any of the Ed25519 keys in the
+    // key records passed as `remaining_accounts` (at most
+    // `MAX_SIGNATURE_ANY_RECORDS`): the owner of the first usable one whose
+    // key the preceding Ed25519 precompile instruction checked, or None.
+    // Records of other key types are skipped.
+    pub fn verify_signature_any<'info>(
+        ctx: Context<'_, '_, 'info, 'info, VerifySignatureAny<'info>>,
+        message: Vec<u8>,
+        signature: [u8; 64],
+    ) -> Result<Option<Pubkey>> {
+        require!(
+            ctx.remaining_accounts.len() <= MAX_SIGNATURE_ANY_RECORDS,
+            KeyRegistryError::BatchTooLarge
+        );
+        
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        let slot = current_slot()?;
+        for account in ctx.remaining_accounts {
+            let key_record = Account::<KeyRecord>::try_from(account)?;
+            if key_record.key_type == KeyType::Ed25519 as u8
+                && key_record.is_usable(now, slot)
+                && check_verifier(&key_record, ctx.accounts.verifier.as_ref()).is_ok()
+                && preceding_ed25519_matches(&ctx.accounts.instructions, key_record.key_bytes(), &message, &signature)?
+            {
+                msg!("✅ Ed25519 signature verified for user: {}", key_record.owner);
+                return Ok(Some(key_record.owner));
+            }
+        }
+        
+        msg!("❌ Signature matches none of the {} key records", ctx.remaining_accounts.len());
+        
+        Ok(None)
+    }
+
+    // Start a sign-in: store `nonce` for the caller to sign off-chain with its
+    // registered Ed25519 key. The challenge lapses after `Challenge::TTL`.
+    pub fn create_challenge(ctx: Context<CreateChallenge>, nonce: [u8; 32]) -> Result<()> {
+        let challenge = &mut ctx.accounts.challenge;
+        challenge.owner = ctx.accounts.owner.key();
+        challenge.nonce = nonce;
+        challenge.expires_at = optional_config_now(ctx.accounts.config.as_ref())? + Challenge::TTL;
+        challenge.bump = ctx.bumps.challenge;
+        
+        msg!("Created challenge for user {} expiring at {}", challenge.owner, challenge.expires_at);
+        
+        Ok(())
+    }
+
+    // Complete a sign-in: `signature` must be the owner's registered Ed25519
+    // key signing the challenge nonce, checked by an Ed25519 native program
+    // instruction just before this one. The challenge is closed on success so
+    // it can't be replayed.
+    pub fn answer_challenge(ctx: Context<AnswerChallenge>, nonce: [u8; 32], signature: [u8; 64]) -> Result<()> {
+        let challenge = &ctx.accounts.challenge;
+        let now = optional_config_now(ctx.accounts.config.as_ref())?;
+        require!(
+            now < challenge.expires_at,
+            KeyRegistryError::ChallengeExpired
+        );
+        
+        let key_record = &ctx.accounts.key_record;
+        require!(
+            key_record.key_type == KeyType::Ed25519 as u8,
+            KeyRegistryError::SignatureVerificationFailed
+        );
+        require!(!key_record.revoked, KeyRegistryError::Revoked);
+        require!(!key_record.frozen, KeyRegistryError::Frozen);
+        require!(
+            !key_record.is_expired(now, current_slot()?),
+            KeyRegistryError::KeyExpired
+        );
+        let verified = preceding_ed25519_matches(
+            &ctx.accounts.instructions,
+            key_record.key_bytes(),
+            &nonce,
+            &signature,
+        )?;
+        require!(verified, KeyRegistryError::SignatureVerificationFailed);
+        
+        msg!("✅ Challenge answered by user: {}", challenge.owner);
+        
+        Ok(())
+    }
+
+    // Check that `owner` registered `public_key` in a key tree: fails unless
+    // the leaf at `leaf_index` proves into `root`, which must be one of the
+    // tree's recent roots. The compression program takes the proof as
+    // accounts, so `remaining_accounts` holds the sibling nodes, leaf first
+    // (minus any levels the tree's canopy stores).
+    pub fn verify_key_compressed<'info>(
+        ctx: Context<'_, '_, '_, 'info, VerifyKeyCompressed<'info>>,
+        owner: Pubkey,
+        key_type: KeyType,
+        public_key: Vec<u8>,
+        root: [u8; 32],
+        leaf_index: u32,
+    ) -> Result<()> {
+        let leaf = compressed_leaf(&owner, key_type, &public_key);
+        let mut args = root.to_vec();
+        args.extend_from_slice(&leaf);
+        args.extend_from_slice(&leaf_index.to_le_bytes());
+        
+        let mut accounts = vec![AccountMeta::new_readonly(ctx.accounts.merkle_tree.key(), false)];
+        let mut infos = vec![ctx.accounts.merkle_tree.to_account_info()];
+        for node in ctx.remaining_accounts {
+            accounts.push(AccountMeta::new_readonly(node.key(), false));
+            infos.push(node.clone());
+        }
+        invoke_compression(&ctx.accounts.compression_program, "verify_leaf", args, accounts, &infos, &[])?;
+        
+        msg!("✅ Compressed public key verified for user: {}", owner);
+        
+        Ok(())
+    }
+
+    // Describe this deployment: sets return data to the Borsh-encoded
+    // `ProgramInfo`, so clients can simulate it and skip instructions the
+    // build doesn't support. Takes no accounts.
+    pub fn program_info(_ctx: Context<GetProgramInfo>) -> Result<()> {
+        let info = ProgramInfo::current();
+        set_return_data(&info.try_to_vec()?);
+        Ok(())
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Config::LEN,
+        seeds = [b"config"],
+        bump
+    )]
+    pub config: Account<'info, Config>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPaused<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct SetFee<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = pending_admin @ KeyRegistryError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct SetAllowedPrograms<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(
+    registry_id: [u8; 8],
+    label: String,
+    key_type: KeyType,
+    public_key: Vec<u8>,
+    signature: [u8; 64],
+    expires_at: i64,
+    handle: [u8; 32],
+    usage_flags: u8,
+    email_hash: [u8; 32],
+    uri: String,
+    history_capacity: u8
+)]
+pub struct RegisterKey<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    // Anchor creates `init` accounts before checking other constraints, so the
+    // rent check rides on `space` to fail with InsufficientRent up front.
+    #[account(
+        init,
+        payer = owner,
+        space = rent_checked_space(&owner, KeyRecord::account_size(history_capacity as usize), &[8 + KeyIndex::LEN])?,
+        seeds = [b"key_record", registry_seed(&registry_id), owner.key().as_ref(), label.as_bytes()],
+        bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // init_if_needed so an existing entry fails with KeyAlreadyIndexed
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + KeyIndex::LEN,
+        seeds = [b"key_index", registry_seed(&registry_id), key_seed(&public_key, 0), key_seed(&public_key, 1)],
+        bump
+    )]
+    pub key_index: Account<'info, KeyIndex>,
+    
+    // only passed when registering with a handle
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + HandleIndex::LEN,
+        seeds = [b"handle", registry_seed(&registry_id), handle.as_ref()],
+        bump
+    )]
+    pub handle_index: Option<Account<'info, HandleIndex>>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: receives the registration fee; address is constrained to the config's treasury
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], label: String, key_type: KeyType, public_key: Vec<u8>)]
+pub struct RegisterKeyFor<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>, // funds rent, fee and stake, gains no rights over the record
+    
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = payer,
+        space = rent_checked_space(&payer, ACCOUNT_SIZE, &[8 + KeyIndex::LEN])?,
+        seeds = [b"key_record", registry_seed(&registry_id), owner.key().as_ref(), label.as_bytes()],
+        bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + KeyIndex::LEN,
+        seeds = [b"key_index", registry_seed(&registry_id), key_seed(&public_key, 0), key_seed(&public_key, 1)],
+        bump
+    )]
+    pub key_index: Account<'info, KeyIndex>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: receives the registration fee; address is constrained to the config's treasury
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CommitKey<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + KeyCommitment::LEN,
+        seeds = [b"commitment", owner.key().as_ref()],
+        bump
+    )]
+    pub key_commitment: Account<'info, KeyCommitment>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], label: String, key_type: KeyType, public_key: Vec<u8>)]
+pub struct RevealKey<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"commitment", owner.key().as_ref()],
+        bump = key_commitment.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_commitment: Account<'info, KeyCommitment>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = rent_checked_space(&owner, ACCOUNT_SIZE, &[8 + KeyIndex::LEN])?,
+        seeds = [b"key_record", registry_seed(&registry_id), owner.key().as_ref(), label.as_bytes()],
+        bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + KeyIndex::LEN,
+        seeds = [b"key_index", registry_seed(&registry_id), key_seed(&public_key, 0), key_seed(&public_key, 1)],
+        bump
+    )]
+    pub key_index: Account<'info, KeyIndex>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: receives the registration fee; address is constrained to the config's treasury
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(org_id: [u8; 32])]
+pub struct CreateOrg<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + Org::LEN,
+        seeds = [b"org", org_id.as_ref()],
+        bump
+    )]
+    pub org: Account<'info, Org>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(org_id: [u8; 32], key_type: KeyType, public_key: Vec<u8>)]
+pub struct RegisterKeyInOrg<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    pub admin: Signer<'info>, // org admin, approves the membership
+    
+    #[account(
+        seeds = [b"org", org_id.as_ref()],
+        bump = org.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub org: Account<'info, Org>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = rent_checked_space(&owner, ACCOUNT_SIZE, &[8 + KeyIndex::LEN])?,
+        seeds = [b"key_record", org_id.as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + KeyIndex::LEN,
+        seeds = [b"key_index", key_seed(&public_key, 0), key_seed(&public_key, 1)],
+        bump
+    )]
+    pub key_index: Account<'info, KeyIndex>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: receives the registration fee; address is constrained to the config's treasury
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(org_id: [u8; 32], member: Pubkey)]
+pub struct OrgRevoke<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"org", org_id.as_ref()],
+        bump = org.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub org: Account<'info, Org>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", org_id.as_ref(), member.as_ref()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(org_id: [u8; 32], owner: Pubkey)]
+pub struct VerifyOrgKey<'info> {
+    #[account(
+        seeds = [b"key_record", org_id.as_ref(), owner.as_ref()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RegisterKeysBatch<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitKeyTree<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: allocated by the caller; the compression program initializes it
+    #[account(mut, owner = spl_account_compression::ID)]
+    pub merkle_tree: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer only, holds no data
+    #[account(seeds = [b"tree_authority", merkle_tree.key().as_ref()], bump)]
+    pub tree_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the SPL account-compression program
+    #[account(address = spl_account_compression::ID)]
+    pub compression_program: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the SPL noop program
+    #[account(address = spl_noop::ID)]
+    pub noop_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterKeyCompressed<'info> {
+    pub owner: Signer<'info>,
+    
+    /// CHECK: a key tree; the compression program checks it against `tree_authority`
+    #[account(mut, owner = spl_account_compression::ID)]
+    pub merkle_tree: UncheckedAccount<'info>,
+    
+    /// CHECK: PDA signer only, holds no data
+    #[account(seeds = [b"tree_authority", merkle_tree.key().as_ref()], bump)]
+    pub tree_authority: UncheckedAccount<'info>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: address is constrained to the SPL account-compression program
+    #[account(address = spl_account_compression::ID)]
+    pub compression_program: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the SPL noop program
+    #[account(address = spl_noop::ID)]
+    pub noop_program: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(label: String, new_public_key: Vec<u8>)]
+pub struct UpdateKey<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>, // owner or delegate, checked in the handler
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(key_record.key_bytes(), 0), key_seed(key_record.key_bytes(), 1)],
+        bump = old_key_index.bump,
+        constraint = old_key_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub old_key_index: Account<'info, KeyIndex>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + KeyIndex::LEN,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(&new_public_key, 0), key_seed(&new_public_key, 1)],
+        bump
+    )]
+    pub new_key_index: Account<'info, KeyIndex>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: the owner's `Settings` PDA, which may not exist; read for `delegate`, `max_lifetime` and `update_cooldown`
+    #[account(seeds = [b"settings", key_record.owner.as_ref()], bump)]
+    pub settings: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeUpdate<'info> {
+    pub authority: Signer<'info>, // owner or delegate, checked in the handler
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: the owner's `Settings` PDA, which may not exist; read for `delegate`
+    #[account(seeds = [b"settings", key_record.owner.as_ref()], bump)]
+    pub settings: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FinalizeUpdate<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>, // owner or delegate, checked in the handler
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        mut,
+        close = authority,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(key_record.key_bytes(), 0), key_seed(key_record.key_bytes(), 1)],
+        bump = old_key_index.bump,
+        constraint = old_key_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub old_key_index: Account<'info, KeyIndex>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + KeyIndex::LEN,
+        seeds = [
+            b"key_index",
+            registry_seed(&key_record.registry_id),
+            key_seed(key_record.pending_key_bytes(), 0),
+            key_seed(key_record.pending_key_bytes(), 1)
+        ],
+        bump
+    )]
+    pub new_key_index: Account<'info, KeyIndex>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: the owner's `Settings` PDA, which may not exist; read for `delegate` and `update_cooldown`
+    #[account(seeds = [b"settings", key_record.owner.as_ref()], bump)]
+    pub settings: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelUpdate<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Settings::LEN,
+        seeds = [b"settings", owner.key().as_ref()],
+        bump
+    )]
+    pub settings: Account<'info, Settings>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetVerifierAllowlist<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct SetVerifier<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct SetAlias<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // only passed when setting an alias
+    pub target_record: Option<Account<'info, KeyRecord>>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct SetBackupKey<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct SetHistoryCapacity<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    // resized by the handler (see resize_record) rather than with `realloc`,
+    // which would refund the stake along with the rent on a shrink
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    pub system_program: Program<'info, System>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct SetUri<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct SetEmailHash<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct TransferOwnership<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(key_record.key_bytes(), 0), key_seed(key_record.key_bytes(), 1)],
+        bump = key_index.bump,
+        constraint = key_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub key_index: Account<'info, KeyIndex>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], label: String, key_type: KeyType, public_key: Vec<u8>)]
+pub struct UpsertKey<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = rent_checked_space(&owner, ACCOUNT_SIZE, &[8 + KeyIndex::LEN])?,
+        seeds = [b"key_record", registry_seed(&registry_id), owner.key().as_ref(), label.as_bytes()],
+        bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // only passed when an existing record rotates to a different key
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"key_index", registry_seed(&registry_id), key_seed(key_record.key_bytes(), 0), key_seed(key_record.key_bytes(), 1)],
+        bump = old_key_index.bump,
+        constraint = old_key_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub old_key_index: Option<Account<'info, KeyIndex>>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + KeyIndex::LEN,
+        seeds = [b"key_index", registry_seed(&registry_id), key_seed(&public_key, 0), key_seed(&public_key, 1)],
+        bump
+    )]
+    pub new_key_index: Account<'info, KeyIndex>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: the owner's `Settings` PDA, which may not exist; read for `update_cooldown`
+    #[account(seeds = [b"settings", owner.key().as_ref()], bump)]
+    pub settings: UncheckedAccount<'info>,
+    
+    /// CHECK: receives the registration fee; address is constrained to the config's treasury
+    #[account(mut, address = config.treasury)]
+    pub treasury: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(handle: [u8; 32])]
+pub struct SetHandle<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // index of the current handle, released (closed) by this instruction
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"handle", registry_seed(&key_record.registry_id), key_record.handle.as_ref()],
+        bump = old_handle_index.bump,
+        constraint = old_handle_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub old_handle_index: Option<Account<'info, HandleIndex>>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + HandleIndex::LEN,
+        seeds = [b"handle", registry_seed(&key_record.registry_id), handle.as_ref()],
+        bump
+    )]
+    pub new_handle_index: Option<Account<'info, HandleIndex>>,
+    
+    pub system_program: Program<'info, System>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardians<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Settings::LEN,
+        seeds = [b"settings", owner.key().as_ref()],
+        bump
+    )]
+    pub settings: Account<'info, Settings>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetUpdateCooldown<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Settings::LEN,
+        seeds = [b"settings", owner.key().as_ref()],
+        bump
+    )]
+    pub settings: Account<'info, Settings>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetKeyPolicy<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + Settings::LEN,
+        seeds = [b"settings", owner.key().as_ref()],
+        bump
+    )]
+    pub settings: Account<'info, Settings>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Recover<'info> {
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(key_record.key_bytes(), 0), key_seed(key_record.key_bytes(), 1)],
+        bump = key_index.bump,
+        constraint = key_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub key_index: Account<'info, KeyIndex>,
+    
+    #[account(seeds = [b"settings", key_record.owner.as_ref()], bump = settings.bump)]
+    pub settings: Account<'info, Settings>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct RenewKey<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+    
+    /// CHECK: the owner's `Settings` PDA, which may not exist; read for `max_lifetime`
+    #[account(seeds = [b"settings", key_record.owner.as_ref()], bump)]
+    pub settings: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct Touch<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct Migrate<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    /// CHECK: older layouts don't deserialize as `KeyRecord`; the handler checks
+    /// the discriminator, and the account must belong to this program
+    #[account(mut, owner = crate::ID)]
+    pub key_record: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct GrowRecord<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    /// CHECK: short records don't deserialize as `KeyRecord`; the handler
+    /// checks the discriminator and stored owner, and the account must belong
+    /// to this program
+    #[account(mut, owner = crate::ID)]
+    pub key_record: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], owner: Pubkey, label: String)]
+pub struct GetMetadata<'info> {
+    #[account(
+        seeds = [b"key_record", registry_seed(&registry_id), owner.as_ref(), label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct SweepExpired<'info> {
+    pub admin: Signer<'info>,
+    
+    #[account(
+        seeds = [b"config"],
+        bump = config.bump,
+        has_one = admin @ KeyRegistryError::Unauthorized
+    )]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct FindOwner<'info> {
+    // signs for records with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct EnableAccessLog<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + AccessLog::LEN,
+        seeds = [b"access_log", owner.key().as_ref()],
+        bump
+    )]
+    pub access_log: Account<'info, AccessLog>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetAccessLog<'info> {
+    #[account(
+        seeds = [b"access_log", access_log.owner.as_ref()],
+        bump = access_log.bump
+    )]
+    pub access_log: Account<'info, AccessLog>,
+}
+
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], owner: Pubkey, label: String)]
+pub struct GetHistory<'info> {
+    #[account(
+        seeds = [b"key_record", registry_seed(&registry_id), owner.as_ref(), label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct RevokeKey<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(new_public_key: Vec<u8>)]
+pub struct ReactivateKey<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // only passed when the key changes; both move the reverse index
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(key_record.key_bytes(), 0), key_seed(key_record.key_bytes(), 1)],
+        bump = old_key_index.bump,
+        constraint = old_key_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub old_key_index: Option<Account<'info, KeyIndex>>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + KeyIndex::LEN,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(&new_public_key, 0), key_seed(&new_public_key, 1)],
+        bump
+    )]
+    pub new_key_index: Option<Account<'info, KeyIndex>>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: the owner's `Settings` PDA, which may not exist; read for `update_cooldown`
+    #[account(seeds = [b"settings", key_record.owner.as_ref()], bump)]
+    pub settings: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct SetFlags<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+    
+    /// CHECK: the owner's `Settings` PDA, which may not exist; read for `allowed_usage`
+    #[account(seeds = [b"settings", key_record.owner.as_ref()], bump)]
+    pub settings: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeKey<'info> {
+    pub authority: Signer<'info>, // owner or config admin, checked in the handler
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterEncKey<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + EncKeyRecord::LEN,
+        seeds = [b"enc_key", owner.key().as_ref()],
+        bump
+    )]
+    pub enc_key_record: Account<'info, EncKeyRecord>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateEncKey<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"enc_key", owner.key().as_ref()],
+        bump = enc_key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub enc_key_record: Account<'info, EncKeyRecord>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct VerifyEncKey<'info> {
+    #[account(
+        seeds = [b"enc_key", owner.as_ref()],
+        bump = enc_key_record.bump
+    )]
+    pub enc_key_record: Account<'info, EncKeyRecord>,
+}
+
+#[derive(Accounts)]
+pub struct AddKey<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + KeyBundle::LEN,
+        seeds = [b"key_bundle", owner.key().as_ref()],
+        bump
+    )]
+    pub key_bundle: Account<'info, KeyBundle>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveKey<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_bundle", owner.key().as_ref()],
+        bump = key_bundle.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_bundle: Account<'info, KeyBundle>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct VerifyInBundle<'info> {
+    #[account(
+        seeds = [b"key_bundle", owner.as_ref()],
+        bump = key_bundle.bump
+    )]
+    pub key_bundle: Account<'info, KeyBundle>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterRoot<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + KeyRoot::LEN,
+        seeds = [b"key_root", owner.key().as_ref()],
+        bump
+    )]
+    pub key_root: Account<'info, KeyRoot>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(owner: Pubkey)]
+pub struct VerifyMembership<'info> {
+    #[account(
+        seeds = [b"key_root", owner.as_ref()],
+        bump = key_root.bump
+    )]
+    pub key_root: Account<'info, KeyRoot>,
+}
+
+#[derive(Accounts)]
+pub struct InvokeWithAttestation<'info> {
+    pub owner: Signer<'info>,
+    
+    // the address is checked against the signer seeds in the handler, which
+    // also turns org records away
+    #[account(has_one = owner @ KeyRegistryError::Unauthorized)]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    /// CHECK: any executable program other than this one; see invoke_with_attestation
+    #[account(executable)]
+    pub target_program: UncheckedAccount<'info>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(other_owner: Pubkey)]
+pub struct SwapKeys<'info> {
+    pub owner: Signer<'info>,
+    
+    #[account(address = other_owner @ KeyRegistryError::Unauthorized)]
+    pub other: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&other_record.registry_id), other_record.registrant.as_ref(), other_record.label.as_bytes()],
+        bump = other_record.bump,
+        constraint = other_record.owner == other_owner @ KeyRegistryError::Unauthorized
+    )]
+    pub other_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(key_record.key_bytes(), 0), key_seed(key_record.key_bytes(), 1)],
+        bump = key_index.bump,
+        constraint = key_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub key_index: Account<'info, KeyIndex>,
+    
+    #[account(
+        mut,
+        seeds = [b"key_index", registry_seed(&other_record.registry_id), key_seed(other_record.key_bytes(), 0), key_seed(other_record.key_bytes(), 1)],
+        bump = other_key_index.bump,
+        constraint = other_key_index.record == other_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub other_key_index: Account<'info, KeyIndex>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: the owner's `Settings` PDA, which may not exist; read for `update_cooldown`
+    #[account(seeds = [b"settings", key_record.owner.as_ref()], bump)]
+    pub settings: UncheckedAccount<'info>,
+    
+    /// CHECK: the other owner's `Settings` PDA, which may not exist; read for `update_cooldown`
+    #[account(seeds = [b"settings", other_owner.as_ref()], bump)]
+    pub other_settings: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseKey<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"key_index", registry_seed(&key_record.registry_id), key_seed(key_record.key_bytes(), 0), key_seed(key_record.key_bytes(), 1)],
+        bump = key_index.bump,
+        constraint = key_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub key_index: Account<'info, KeyIndex>,
+    
+    // only passed when the record has a handle
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"handle", registry_seed(&key_record.registry_id), key_record.handle.as_ref()],
+        bump = handle_index.bump,
+        constraint = handle_index.record == key_record.key() @ KeyRegistryError::InvalidRecord
+    )]
+    pub handle_index: Option<Account<'info, HandleIndex>>,
+}
+
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], owner: Pubkey, label: String)]
+pub struct MaybeKey<'info> {
+    /// CHECK: may not exist yet; only its address is constrained, and the
+    /// handler checks owner and discriminator by hand
+    #[account(seeds = [b"key_record", registry_seed(&registry_id), owner.as_ref(), label.as_bytes()], bump)]
+    pub key_record: UncheckedAccount<'info>,
+}
+
+// `owner` is the wallet that registered the record (its `registrant`). The
+// address is derived from the caller's arguments rather than from data in the
+// account being checked.
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], owner: Pubkey, label: String)]
+pub struct VerifyKey<'info> {
+    #[account(
+        seeds = [b"key_record", registry_seed(&registry_id), owner.as_ref(), label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // the aliased record, only read by `verify_key` when `key_record` is an alias
+    pub alias_target: Option<Account<'info, KeyRecord>>,
+    
+    // the record owner's access log, only written by `verify_key`, and only
+    // together with `verifier`
+    #[account(
+        mut,
+        seeds = [b"access_log", key_record.owner.as_ref()],
+        bump = access_log.bump
+    )]
+    pub access_log: Option<Account<'info, AccessLog>>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], owner: Pubkey, label: String)]
+pub struct VerifyDelegated<'info> {
+    #[account(
+        seeds = [b"key_record", registry_seed(&registry_id), owner.as_ref(), label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    /// CHECK: must be the program the record names in `verifier_program`
+    #[account(
+        executable,
+        constraint = key_record.verifier_program == Some(verifier_program.key()) @ KeyRegistryError::InvalidVerifierProgram
+    )]
+    pub verifier_program: UncheckedAccount<'info>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], owner: Pubkey, label: String)]
+pub struct RequireKeyMatches<'info> {
+    #[account(
+        seeds = [b"key_record", registry_seed(&registry_id), owner.as_ref(), label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Account<'info, Config>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+}
+
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], owner: Pubkey, label: String)]
+pub struct VerifyKeyCounted<'info> {
+    #[account(
+        mut,
+        seeds = [b"key_record", registry_seed(&registry_id), owner.as_ref(), label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: [u8; 32])]
+pub struct CreateChallenge<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + Challenge::LEN,
+        seeds = [b"challenge", owner.key().as_ref(), nonce.as_ref()],
+        bump
+    )]
+    pub challenge: Account<'info, Challenge>,
+    
+    pub system_program: Program<'info, System>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+#[instruction(nonce: [u8; 32])]
+pub struct AnswerChallenge<'info> {
+    /// CHECK: only receives the challenge's rent; `has_one` ties it to the challenge
+    #[account(mut)]
+    pub owner: UncheckedAccount<'info>,
+    
+    #[account(
+        mut,
+        close = owner,
+        seeds = [b"challenge", owner.key().as_ref(), nonce.as_ref()],
+        bump = challenge.bump,
+        has_one = owner @ KeyRegistryError::Unauthorized
+    )]
+    pub challenge: Account<'info, Challenge>,
+    
+    #[account(
+        seeds = [b"key_record", registry_seed(&key_record.registry_id), key_record.registrant.as_ref(), key_record.label.as_bytes()],
+        bump = key_record.bump,
+        constraint = key_record.owner == owner.key() @ KeyRegistryError::Unauthorized
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyKeyCompressed<'info> {
+    /// CHECK: a key tree; the compression program checks the proof against it
+    #[account(owner = spl_account_compression::ID)]
+    pub merkle_tree: UncheckedAccount<'info>,
+    
+    /// CHECK: address is constrained to the SPL account-compression program
+    #[account(address = spl_account_compression::ID)]
+    pub compression_program: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(registry_id: [u8; 8], owner: Pubkey, label: String)]
+pub struct VerifySignature<'info> {
+    #[account(
+        seeds = [b"key_record", registry_seed(&registry_id), owner.as_ref(), label.as_bytes()],
+        bump = key_record.bump
+    )]
+    pub key_record: Account<'info, KeyRecord>,
+    
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[derive(Accounts)]
+pub struct GetProgramInfo {}
+
+#[derive(Accounts)]
+pub struct VerifySignatureAny<'info> {
+    /// CHECK: address is constrained to the instructions sysvar
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    // signs for a record with a verifier allowlist, see check_verifier
+    pub verifier: Option<Signer<'info>>,
+    
+    // the registry config; only its `clock_override` is read, in `test-clock`
+    // builds (see `config_now`)
+    #[account(seeds = [b"config"], bump = config.bump)]
+    pub config: Option<Account<'info, Config>>,
+}
+
+#[account]
+pub struct Config {
+    pub admin: Pubkey,                 // may pause the registry
+    pub paused: bool,                  // emergency stop for register/update
+    pub bump: u8,                      // PDA bump
+    pub fee_lamports: u64,             // charged per registration, 0 = free
+    pub treasury: Pubkey,              // receives registration fees
+    pub allowed_programs: [Pubkey; 8], // CPI callers of require_key_matches, all default = any
+    pub stake_lamports: u64,           // locked in each new record, refunded on close, 0 = none
+    pub pending_admin: Pubkey,         // nominated by propose_admin, default = none
+    pub deployed_at: i64,              // Unix seconds, set by initialize_config
+    pub clock_override: i64,           // test-clock builds only, see set_test_clock; 0 = real clock
+}
+
+impl Config {
+    pub const MAX_ALLOWED_PROGRAMS: usize = 8;
+    // admin + paused + bump + fee_lamports + treasury + allowed_programs + stake_lamports
+    // + pending_admin + deployed_at + clock_override
+    pub const LEN: usize = 32 + 1 + 1 + 8 + 32 + 32 * Self::MAX_ALLOWED_PROGRAMS + 8 + 32 + 8 + 8;
+}
+
+// Reverse lookup from a public key to the record that registered it.
+#[account]
+pub struct KeyIndex {
+    pub owner: Pubkey,  // current owner of `record`
+    pub record: Pubkey, // KeyRecord address
+    pub bump: u8,       // PDA bump
+}
+
+impl KeyIndex {
+    pub const LEN: usize = 32 + 32 + 1; // owner + record + bump
+}
+
+// Uniqueness marker for a display handle. The owner is resolved through the
+// record, so transfers and recovery never leave it stale.
+#[account]
+pub struct HandleIndex {
+    pub record: Pubkey, // KeyRecord holding the handle
+    pub bump: u8,       // PDA bump
+}
+
+impl HandleIndex {
+    pub const LEN: usize = 32 + 1; // record + bump
+}
+
+// A pending sign-in nonce for `owner`, see create_challenge.
+#[account]
+pub struct Challenge {
+    pub owner: Pubkey,   // who must answer
+    pub nonce: [u8; 32], // message to sign
+    pub expires_at: i64, // Unix seconds
+    pub bump: u8,        // PDA bump
+}
+
+impl Challenge {
+    pub const LEN: usize = 32 + 32 + 8 + 1; // owner + nonce + expires_at + bump
+    pub const TTL: i64 = 5 * 60; // seconds a challenge stays answerable
+}
+
+// Per-owner management policy, seeded `[b"settings", owner]`, kept out of
+// `KeyRecord` so verification never has to load it.
+#[account]
+pub struct Settings {
+    pub owner: Pubkey,          // wallet address
+    pub bump: u8,               // PDA bump
+    pub guardians: [Pubkey; 5], // social-recovery guardians, default = unused slot
+    pub guardian_threshold: u8, // guardian signatures `recover` needs, 0 = disabled
+    pub update_cooldown: i64,   // seconds update_key waits after updated_at, 0 = no limit
+    pub delegate: Pubkey,       // may update any of the owner's keys, default = none
+    pub max_lifetime: i64,      // longest expiry, in seconds ahead, keys may get; 0 = no limit
+    pub allowed_usage: u8,      // USAGE_* bits set_flags may grant, 0 = any
+}
+
+impl Settings {
+    pub const MAX_GUARDIANS: usize = 5;
+    // owner + bump + guardians + guardian_threshold + update_cooldown
+    // + delegate + max_lifetime + allowed_usage
+    pub const LEN: usize = 32 + 1 + 32 * Self::MAX_GUARDIANS + 1 + 8 + 32 + 8 + 1;
+
+    pub fn is_guardian(&self, key: &Pubkey) -> bool {
+        *key != Pubkey::default() && self.guardians.contains(key)
+    }
+}
+
+// An organization namespace grouping its members' key records, which are
+// seeded `[b"key_record", org_id, member]`.
+#[account]
+pub struct Org {
+    pub org_id: [u8; 32], // PDA seed
+    pub admin: Pubkey,    // approves members and may revoke their keys
+    pub bump: u8,         // PDA bump
+}
+
+impl Org {
+    pub const LEN: usize = 32 + 32 + 1; // org_id + admin + bump
+}
+
+// Opt-in ring buffer of who recently verified `owner`'s keys (see
+// `enable_access_log`).
+#[account]
+pub struct AccessLog {
+    pub owner: Pubkey,          // wallet address
+    pub bump: u8,               // PDA bump
+    pub head: u8,               // next slot to overwrite
+    pub verifiers: [Pubkey; 8], // verifier of each entry, default = empty
+    pub verified_at: [i64; 8],  // Unix seconds of each entry
+}
+
+impl AccessLog {
+    pub const LOG_LEN: usize = 8;
+    pub const LEN: usize = 32 + 1 + 1 + (32 + 8) * Self::LOG_LEN; // owner + bump + head + verifiers + verified_at
+
+    pub fn record(&mut self, verifier: Pubkey, at: i64) {
+        let head = self.head as usize;
+        self.verifiers[head] = verifier;
+        self.verified_at[head] = at;
+        self.head = ((head + 1) % Self::LOG_LEN) as u8;
+    }
+
+    // Entries, most recent first.
+    pub fn entries(&self) -> Vec<(Pubkey, i64)> {
+        let head = self.head as usize;
+        (1..=Self::LOG_LEN)
+            .map(|i| (head + Self::LOG_LEN - i) % Self::LOG_LEN)
+            .filter(|slot| self.verifiers[*slot] != Pubkey::default())
+            .map(|slot| (self.verifiers[slot], self.verified_at[slot]))
+            .collect()
+    }
+}
+
+// A pending commit-reveal registration (see `commit_key`), one per owner.
+#[account]
+pub struct KeyCommitment {
+    pub owner: Pubkey,        // who may reveal
+    pub commitment: [u8; 32], // SHA-256 of public_key || salt
+    pub slot: u64,            // slot of commit_key
+    pub bump: u8,             // PDA bump
+}
+
+impl KeyCommitment {
+    pub const LEN: usize = 32 + 32 + 8 + 1; // owner + commitment + slot + bump
+    pub const MIN_REVEAL_SLOTS: u64 = 2; // slots between commit and reveal
+}
+
+// Up to `MAX_KEYS` 32-byte keys of one owner under a single rent deposit
+// (see `add_key`), seeded `[b"key_bundle", owner]`.
+#[account]
+pub struct KeyBundle {
+    pub owner: Pubkey,       // wallet address
+    pub bump: u8,            // PDA bump
+    pub keys: Vec<[u8; 32]>, // bundled keys, unordered
+}
+
+impl KeyBundle {
+    pub const MAX_KEYS: usize = 8;
+    pub const LEN: usize = 32 + 1 + 4 + 32 * Self::MAX_KEYS; // owner + bump + keys
+}
+
+// Merkle root of an owner's key allowlist (see `verify_membership`).
+#[account]
+pub struct KeyRoot {
+    pub owner: Pubkey,  // wallet address
+    pub root: [u8; 32], // keccak256 Merkle root
+    pub bump: u8,       // PDA bump
+}
+
+impl KeyRoot {
+    pub const LEN: usize = 32 + 32 + 1; // owner + root + bump
+}
+
+// An X25519 encryption key others use to encrypt to `owner`. Kept apart from
+// `KeyRecord` (signing keys) so the two purposes never mix.
+#[account]
+pub struct EncKeyRecord {
+    pub owner: Pubkey,        // wallet address
+    pub public_key: [u8; 32], // X25519 public key
+    pub bump: u8,             // PDA bump
+    pub created_at: i64,      // Unix seconds, set by register_enc_key
+    pub updated_at: i64,      // Unix seconds, refreshed by update_enc_key
+}
+
+impl EncKeyRecord {
+    pub const LEN: usize = 32 + 32 + 1 + 8 + 8; // owner + public_key + bump + created_at + updated_at
+}
+
+// Return data of `program_info`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub struct ProgramInfo {
+    pub version: String,    // crate semver
+    pub schema_version: u8, // KeyRecord::CURRENT_VERSION
+    pub features: u32,      // FEATURE_* bits
+}
+
+impl ProgramInfo {
+    pub fn current() -> Self {
+        let mut features = FEATURE_COMPRESSION;
+        if cfg!(feature = "clock") {
+            features |= FEATURE_EXPIRY;
+        }
+        if cfg!(feature = "p256") {
+            features |= FEATURE_MULTI_ALGO;
+        }
+        if cfg!(feature = "test-clock") {
+            features |= FEATURE_TEST_CLOCK;
+        }
+        ProgramInfo {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            schema_version: KeyRecord::CURRENT_VERSION,
+            features,
+        }
+    }
+
+    pub fn supports(&self, feature: u32) -> bool {
+        self.features & feature == feature
+    }
+}
+
+// One `register_keys_batch` entry.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct BatchKeyEntry {
+    pub owner: Pubkey,
+    pub key_type: KeyType,
+    pub public_key: Vec<u8>,
+    pub usage_flags: u8,
+}
+
+// A record's standing as reported by `attest`, first failing check wins.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyStatus {
+    Valid,
+    Revoked,
+    Frozen,
+    Expired,
+}
+
+// Mutations chained into `KeyRecord::audit_head`; the discriminant is the
+// `operation` byte hashed and emitted in `AuditExtended`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditOp {
+    Register,
+    Update,
+    Revoke,
+    Reactivate,
+    Freeze,
+    Thaw,
+    Transfer,
+    Renew,
+    Metadata, // any other field, e.g. uri, handle or a pending update
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyType {
+    Ed25519,   // 32-byte signing key
+    Secp256k1, // 33-byte compressed (EVM-style) key
+    X25519,    // 32-byte Diffie-Hellman key
+    Secp256r1, // 33-byte compressed P-256 (WebAuthn passkey) key
+}
+
+impl KeyType {
+    pub fn from_u8(value: u8) -> Option<Self> {
+        match value {
+            0 => Some(KeyType::Ed25519),
+            1 => Some(KeyType::Secp256k1),
+            2 => Some(KeyType::X25519),
+            3 => Some(KeyType::Secp256r1),
+            _ => None,
+        }
+    }
+
+    // Number of bytes a public key of this type occupies.
+    pub const fn key_len(&self) -> usize {
+        match self {
+            KeyType::Ed25519 => 32,
+            KeyType::Secp256k1 => 33,
+            KeyType::X25519 => 32,
+            KeyType::Secp256r1 => 33,
+        }
+    }
+}
+
+#[account]
+pub struct KeyRecord {
+    pub version: u8,                     // layout version, see CURRENT_VERSION
+    pub status: u16,                     // STATUS_* bits plus usage_flags, see refresh_status
+    pub owner: Pubkey,                   // wallet address
+    pub key_type: u8,                    // KeyType discriminant
+    pub key_len: u8,                     // bytes of `public_key` in use
+    pub public_key: [u8; MAX_KEY_LEN],   // public key, zero-padded
+    pub bump: u8,                        // PDA bump
+    pub revoked: bool,                   // set by revoke_key
+    pub history: [[u8; MAX_KEY_LEN]; 5], // previous keys (ring buffer, zero = empty)
+    pub history_head: u8,                // next history slot to overwrite
+    pub expires_at: i64,                 // Unix seconds, 0 = never
+    pub label: String,                   // PDA seed, at most MAX_LABEL_LEN bytes
+    pub registrant: Pubkey,              // PDA seed; original owner, kept across transfers
+    pub created_at: i64,                 // Unix seconds, set once by register_key
+    pub updated_at: i64,                 // Unix seconds, refreshed by update_key
+    pub handle: [u8; 32],                // UTF-8 display name, zero-padded, zero = none
+    pub frozen: bool,                    // temporary suspension, see freeze_key/thaw_key
+    pub verify_count: u64,               // successful verify_key_counted calls
+    pub usage_flags: u8,                 // USAGE_* bits the key may be used for
+    pub email_hash: [u8; 32],            // SHA-256 of the normalized recovery email, zero = none
+    pub alias_of: Option<Pubkey>,        // record whose key this one verifies against, see set_alias
+    pub uri: String,                     // off-chain metadata document, at most MAX_URI_LEN bytes
+    pub backup_key: [u8; MAX_KEY_LEN],   // fallback key, zero-padded, zero = none
+    pub nonce: u64,                      // signed into authorizations, advanced on use to stop replays
+    pub pending_key: [u8; MAX_KEY_LEN],  // staged by propose_update, zero-padded, zero = none
+    pub pending_activation: i64,         // Unix seconds after which finalize_update may apply it
+    pub staked: u64,                     // lamports locked above rent at registration, see set_stake
+    pub registry_id: [u8; 8],            // PDA seed namespace, see registry_seed; zero = default registry
+    pub audit_head: [u8; 32],            // hash chain over every mutation, see extend_audit
+    pub expires_at_slot: u64,            // slot expiry instead of `expires_at`, 0 = none; see set_slot_expiry
+    pub verifier_program: Option<Pubkey>, // external verifier for verify_delegated, see set_verifier
+    pub seq: u64,                        // mutations so far, advanced by extend_audit; 0 = none
+    pub verifier_allowlist: [Pubkey; 4], // who may verify the key, all default = anyone; see check_verifier
+    pub history_limit: Option<u8>,       // history ring size, None = HISTORY_LEN; see set_history_capacity
+    pub history_overflow: Vec<[u8; MAX_KEY_LEN]>, // history slots past HISTORY_LEN; keep last, it grows
+}
+
+impl KeyRecord {
+    // v0: the original layout, owner + 32-byte Ed25519 key + bump. Later fields
+    //     were appended without a version, so these records don't deserialize
+    //     as `KeyRecord` at all until `migrate` rewrites them.
+    // v1: records written before `version` existed (same fields, no version byte).
+    // v2: `version` leads the record.
+    // v3: `status` follows `version`, at a fixed offset for ranged reads.
+    // v4: `delegate` moved to `Settings` and the unused `guardians` and
+    //     `guardian_threshold` were dropped, so the record shrank mid-layout.
+    // Fields added since are appended with a zero default, so older records of
+    // the current version only need to be grown (zero-extended) by `migrate`.
+    // The exception is `history_overflow`, whose length follows the history
+    // capacity: it stays last, and new fields go in front of it.
+    pub const CURRENT_VERSION: u8 = 4;
+    pub const HISTORY_LEN: usize = 5;
+    pub const MAX_HISTORY_CAPACITY: usize = 16;
+    pub const MAX_LABEL_LEN: usize = 16;
+    pub const MAX_URI_LEN: usize = 128;
+
+    // Byte offsets in the account data (after the 8-byte discriminator and
+    // the version byte): `status` (u16, little-endian), e.g. for a ranged
+    // `getAccountInfo`, and `owner`, e.g. for `memcmp` filters.
+    pub const STATUS_OFFSET: usize = 8 + 1;
+    pub const OWNER_OFFSET: usize = 8 + 1 + 2;
+    pub const MAX_VERIFIERS: usize = 4;
+
+    // `usage_flags` bits, in the spirit of X.509 key usage.
+    pub const USAGE_SIGN: u8 = 1;
+    pub const USAGE_ENCRYPT: u8 = 2;
+    pub const USAGE_AUTH: u8 = 4;
+
+    // `status` bits. The low byte holds flags, the high byte `usage_flags`.
+    // STATUS_EXPIRED is a snapshot taken on the last write, so a record can
+    // expire without it being set; check `expires_at`/`expires_at_slot` too.
+    pub const STATUS_REVOKED: u16 = 1;
+    pub const STATUS_FROZEN: u16 = 2;
+    pub const STATUS_EXPIRED: u16 = 4;
+    pub const STATUS_USAGE_SHIFT: u32 = 8;
+
+    // version + status + owner + key_type + key_len + public_key + bump + revoked + history
+    // + history_head + expires_at + label + registrant + created_at + updated_at
+    // + handle + frozen + verify_count
+    // + usage_flags + email_hash + alias_of + uri + backup_key + nonce + pending_key
+    // + pending_activation + staked + registry_id + audit_head + expires_at_slot
+    // + verifier_program + seq + verifier_allowlist + history_limit
+    // + history_overflow (empty)
+    pub const LEN: usize = 1 + 2 + 32 + 1 + 1 + MAX_KEY_LEN + 1 + 1 + MAX_KEY_LEN * Self::HISTORY_LEN
+        + 1 + 8 + 4 + Self::MAX_LABEL_LEN + 32 + 8 + 8 + 32 + 1
+        + 8 + 1 + 32 + 33 + 4 + Self::MAX_URI_LEN + MAX_KEY_LEN + 8 + MAX_KEY_LEN + 8 + 8 + 8 + 32 + 8 + 33 + 8
+        + 32 * Self::MAX_VERIFIERS + 2 + 4;
+
+    // Data lengths (after the discriminator) of v0 and v1 records; frozen
+    // layouts, so they keep their literal sizes.
+    pub const V0_LEN: usize = 32 + 32 + 1;
+    pub const V1_LEN: usize = 32 + 1 + 1 + 64 + 1 + 1 + 64 * 5 + 1 + 8 + 32 + 4 + 16 + 32;
+    // Body offset of the v3 `delegate` (after version, status, owner,
+    // key_type, key_len, public_key, bump, revoked, history, history_head
+    // and expires_at), and the bytes v4 dropped: delegate + guardians
+    // + guardian_threshold.
+    const V3_DELEGATE_AT: usize = 1 + 2 + 32 + 1 + 1 + 64 + 1 + 1 + 64 * 5 + 1 + 8;
+    const V4_DROPPED: usize = 32 + 32 * 5 + 1;
+
+    // The version of the record in `data` (account data, discriminator
+    // included) and its body rewritten in the current layout, as `migrate`
+    // stores it: v0 is rebuilt field by field, v1 gains its version byte,
+    // v1/v2 a zero `status`, v1-v3 lose the fields v4 dropped, and fields
+    // appended since are zero-filled.
+    // InvalidRecord if `data` is not a key record of a known version.
+    pub fn upgrade_layout(data: &[u8]) -> Result<(u8, Vec<u8>)> {
+        require!(data.len() >= 8 && data[..8] == Self::DISCRIMINATOR, KeyRegistryError::InvalidRecord);
+        if data.len() == 8 + Self::V0_LEN {
+            let mut record = Self::zeroed();
+            record.owner = Pubkey::new_from_array(data[8..40].try_into().unwrap());
+            record.key_type = KeyType::Ed25519 as u8;
+            record.set_key(&data[40..72]);
+            record.bump = data[72];
+            // v0 records were seeded by owner alone, i.e. the default
+            // registry with an empty label
+            record.registrant = record.owner;
+            let mut body = record.try_to_vec()?;
+            body.resize(Self::LEN, 0);
+            return Ok((0, body));
+        }
+        // No later layout was ever shorter than v1, so anything smaller is
+        // truncated and would only be zero-filled into a bogus record.
+        require!(data.len() >= 8 + Self::V1_LEN, KeyRegistryError::InvalidRecord);
+        let mut body = if data.len() == 8 + Self::V1_LEN {
+            // v1 records lack the leading version byte
+            [&[1u8][..], &data[8..]].concat()
+        } else {
+            data[8..].to_vec()
+        };
+        let version = body[0];
+        require!(
+            (1..=Self::CURRENT_VERSION).contains(&version),
+            KeyRegistryError::InvalidRecord
+        );
+        if version < 3 {
+            // make room for `status` after the version byte
+            body.splice(1..1, [0u8; 2]);
+        }
+        if version < 4 {
+            // zero-fill fields the record predates first, so the dropped
+            // ones are there to remove
+            if body.len() < Self::LEN + Self::V4_DROPPED {
+                body.resize(Self::LEN + Self::V4_DROPPED, 0);
+            }
+            let delegate_at = Self::V3_DELEGATE_AT;
+            body.drain(delegate_at..delegate_at + 32);
+            // guardians follow label, registrant, created_at and updated_at
+            let label_len = u32::from_le_bytes(body[delegate_at..delegate_at + 4].try_into().unwrap()) as usize;
+            require!(label_len <= Self::MAX_LABEL_LEN, KeyRegistryError::InvalidRecord);
+            let guardians_at = delegate_at + 4 + label_len + 32 + 8 + 8;
+            body.drain(guardians_at..guardians_at + 32 * 5 + 1);
+        }
+        if body.len() < Self::LEN {
+            body.resize(Self::LEN, 0);
+        }
+        Ok((version, body))
+    }
+
+    // A record with every field at its zero default.
+    pub fn zeroed() -> Self {
+        let zeros = vec![0u8; Self::LEN];
+        Self::deserialize(&mut zeros.as_slice()).expect("all-zero bytes decode as defaults")
+    }
+
+    // The stored key without its zero padding.
+    pub fn key_bytes(&self) -> &[u8] {
+        &self.public_key[..self.key_len as usize]
+    }
+
+    // Store `key` zero-padded into the fixed buffer.
+    pub fn set_key(&mut self, key: &[u8]) {
+        self.public_key = [0u8; MAX_KEY_LEN];
+        self.public_key[..key.len()].copy_from_slice(key);
+        self.key_len = key.len() as u8;
+    }
+
+    // Whether `signer` is the owner or the delegate in the owner's `settings`.
+    pub fn can_manage(&self, settings: Option<&Settings>, signer: &Pubkey) -> bool {
+        *signer == self.owner
+            || settings.is_some_and(|settings| settings.delegate != Pubkey::default() && *signer == settings.delegate)
+    }
+
+    // Whether either a time or a slot expiry is set.
+    pub fn has_expiry(&self) -> bool {
+        self.expires_at != 0 || self.expires_at_slot != 0
+    }
+
+    // Whether the key has passed its expiry at time `now`.
+    // Expired by whichever mode is set: `expires_at` against `now` or
+    // `expires_at_slot` against `slot` (at most one is non-zero).
+    pub fn is_expired(&self, now: i64, slot: u64) -> bool {
+        (self.expires_at != 0 && now >= self.expires_at)
+            || (self.expires_at_slot != 0 && slot >= self.expires_at_slot)
+    }
+
+    // The staged key of a pending rotation (see propose_update).
+    pub fn pending_key_bytes(&self) -> &[u8] {
+        &self.pending_key[..self.key_len as usize]
+    }
+
+    pub fn has_pending_update(&self) -> bool {
+        self.pending_activation != 0
+    }
+
+    // Whether a backup key is set.
+    pub fn has_backup_key(&self) -> bool {
+        self.backup_key.iter().any(|b| *b != 0)
+    }
+
+    // Not revoked, frozen or expired: the key may verify.
+    pub fn is_usable(&self, now: i64, slot: u64) -> bool {
+        !self.revoked && !self.frozen && !self.is_expired(now, slot)
+    }
+
+    pub fn status(&self, now: i64, slot: u64) -> KeyStatus {
+        if self.revoked {
+            KeyStatus::Revoked
+        } else if self.frozen {
+            KeyStatus::Frozen
+        } else if self.is_expired(now, slot) {
+            KeyStatus::Expired
+        } else {
+            KeyStatus::Valid
+        }
+    }
+
+    // Record a mutation at `now` (from `config_now`): bump `seq`, refresh
+    // `status`, and chain `op` and the resulting state into `audit_head`:
+    // head = keccak256(prev_head || op || audit_state()), starting from all
+    // zeros. The emitted `AuditExtended` carries each step so clients can
+    // replay the chain and compare it to the stored head.
+    pub fn extend_audit(&mut self, record: Pubkey, op: AuditOp, now: i64) -> Result<()> {
+        self.seq += 1;
+        self.refresh_status(now, current_slot()?);
+        let state = self.audit_state();
+        self.audit_head = keccak::hashv(&[&self.audit_head, &[op as u8], &state]).to_bytes();
+        emit!(AuditExtended {
+            record,
+            operation: op as u8,
+            seq: self.seq,
+            state,
+            head: self.audit_head,
+        });
+        Ok(())
+    }
+
+    // Recompute `status` from the flags, expiry and usage bits.
+    pub fn refresh_status(&mut self, now: i64, slot: u64) {
+        let mut status = (self.usage_flags as u16) << Self::STATUS_USAGE_SHIFT;
+        if self.revoked {
+            status |= Self::STATUS_REVOKED;
+        }
+        if self.frozen {
+            status |= Self::STATUS_FROZEN;
+        }
+        if self.is_expired(now, slot) {
+            status |= Self::STATUS_EXPIRED;
+        }
+        self.status = status;
+    }
+
+    // owner || key_type || key bytes || revoked || frozen || expires_at (LE)
+    // || expires_at_slot (LE).
+    pub fn audit_state(&self) -> Vec<u8> {
+        let mut state = Vec::with_capacity(32 + 1 + self.key_len as usize + 1 + 1 + 8 + 8);
+        state.extend_from_slice(self.owner.as_ref());
+        state.push(self.key_type);
+        state.extend_from_slice(self.key_bytes());
+        state.push(self.revoked as u8);
+        state.push(self.frozen as u8);
+        state.extend_from_slice(&self.expires_at.to_le_bytes());
+        state.extend_from_slice(&self.expires_at_slot.to_le_bytes());
+        state
+    }
+
+    // Account size of a record keeping `history_capacity` previous keys.
+    pub fn account_size(history_capacity: usize) -> usize {
+        let capacity = history_capacity.min(Self::MAX_HISTORY_CAPACITY);
+        ACCOUNT_SIZE + MAX_KEY_LEN * capacity.saturating_sub(Self::HISTORY_LEN)
+    }
+
+    // Slots in the history ring (see set_history_capacity).
+    pub fn history_capacity(&self) -> usize {
+        self.history_limit.map_or(Self::HISTORY_LEN, |limit| limit as usize)
+    }
+
+    // Empty the history ring and size it for `capacity` keys; the account
+    // must already be `account_size(capacity)` bytes.
+    pub fn reset_history(&mut self, capacity: u8) {
+        self.history = [[0u8; MAX_KEY_LEN]; Self::HISTORY_LEN];
+        self.history_head = 0;
+        self.history_overflow = vec![[0u8; MAX_KEY_LEN]; (capacity as usize).saturating_sub(Self::HISTORY_LEN)];
+        self.history_limit = Some(capacity);
+    }
+
+    // The `slot`-th history entry, in `history` then `history_overflow`.
+    fn history_slot(&self, slot: usize) -> Option<&[u8; MAX_KEY_LEN]> {
+        match slot.checked_sub(Self::HISTORY_LEN) {
+            None => self.history.get(slot),
+            Some(overflow) => self.history_overflow.get(overflow),
+        }
+    }
+
+    // Remember the current key, overwriting the oldest entry once full.
+    pub fn push_history(&mut self) {
+        let capacity = self.history_capacity();
+        if capacity == 0 {
+            return;
+        }
+        let head = self.history_head as usize % capacity;
+        let key = self.public_key;
+        let slot = match head.checked_sub(Self::HISTORY_LEN) {
+            None => self.history.get_mut(head),
+            Some(overflow) => self.history_overflow.get_mut(overflow),
+        };
+        if let Some(slot) = slot {
+            *slot = key;
+        }
+        self.history_head = ((head + 1) % capacity) as u8;
+    }
+
+    // Keys from the history buffer, most recent first.
+    pub fn previous_keys(&self) -> Vec<Vec<u8>> {
+        let capacity = self.history_capacity();
+        let head = self.history_head as usize;
+        (1..=capacity)
+            .filter_map(|i| self.history_slot((head + capacity - i) % capacity))
+            .filter(|key| key.iter().any(|b| *b != 0))
+            .map(|key| key[..self.key_len as usize].to_vec())
+            .collect()
+    }
+}
+
+// Every key type fits the key buffers, and `key_len` (a u8) can describe them.
+const _: () = assert!(MAX_KEY_LEN <= u8::MAX as usize);
+const _: () = assert!(KeyType::Ed25519.key_len() <= MAX_KEY_LEN);
+const _: () = assert!(KeyType::Secp256k1.key_len() <= MAX_KEY_LEN);
+const _: () = assert!(KeyType::X25519.key_len() <= MAX_KEY_LEN);
+const _: () = assert!(KeyType::Secp256r1.key_len() <= MAX_KEY_LEN);
+
+// Seed component separating independent registries run by one deployment:
+// key records, key index entries and handles are seeded with it after their
+// prefix, so distinct `registry_id`s never collide. The all-zero id is the
+// default registry and contributes no seed, keeping its addresses the same as
+// before registries existed. The config and other per-owner accounts are
+// shared between registries.
+pub fn registry_seed(registry_id: &[u8; 8]) -> &[u8] {
+    if *registry_id == [0u8; 8] {
+        &[]
+    } else {
+        registry_id
+    }
+}
+
+// The `BATCH_*` status reporting `err` for a failed batch entry.
+fn batch_error_code(err: &Error) -> u8 {
+    match err {
+        Error::AnchorError(err) => err
+            .error_code_number
+            .checked_sub(anchor_lang::error::ERROR_CODE_OFFSET)
+            .and_then(|code| u8::try_from(code).ok())
+            .and_then(|code| BATCH_FAILED.checked_add(code))
+            .unwrap_or(u8::MAX),
+        _ => u8::MAX,
+    }
+}
+
+// Parse key record account data (discriminator included) without Anchor's
+// account machinery, for off-chain tooling: the discriminator must be
+// KeyRecord's, and trailing padding after the record is ignored. Only the
+// current layout: `KeyRecord::upgrade_layout` first for older accounts.
+pub fn parse_key_record(data: &[u8]) -> Result<KeyRecord> {
+    require!(
+        data.len() >= 8 && data[..8] == KeyRecord::DISCRIMINATOR,
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+    require!(data.len() > 8 && data[8] == KeyRecord::CURRENT_VERSION, KeyRegistryError::InvalidRecord);
+    KeyRecord::deserialize(&mut &data[8..]).map_err(|_| error!(ErrorCode::AccountDidNotDeserialize))
+}
+
+// A leaf of a `register_root` allowlist tree: keccak256(0x00 || public_key).
+pub fn merkle_leaf(public_key: &[u8]) -> [u8; 32] {
+    keccak::hashv(&[&[MERKLE_LEAF_PREFIX], public_key]).to_bytes()
+}
+
+// The parent of two allowlist tree nodes: keccak256(0x01 || lower || higher),
+// comparing the children as bytes.
+pub fn merkle_node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (lower, higher) = if a <= b { (a, b) } else { (b, a) };
+    keccak::hashv(&[&[MERKLE_NODE_PREFIX], lower, higher]).to_bytes()
+}
+
+// The `part`-th 32-byte chunk of `key`, possibly empty. PDA seeds are capped at
+// 32 bytes, so `key_index` addresses are seeded by the key in two chunks; for
+// 32-byte keys the second chunk is empty and the seeds reduce to
+// [b"key_index", key].
+pub fn key_seed(key: &[u8], part: usize) -> &[u8] {
+    let start = (part * 32).min(key.len());
+    let end = (start + 32).min(key.len());
+    &key[start..end]
+}
+
+// Cheap guards against obviously unusable keys: the all-zero key (which also
+// looks like an uninitialized record) and the Ed25519 identity point.
+fn check_public_key(key_type: KeyType, key: &[u8]) -> Result<()> {
+    require!(key.iter().any(|b| *b != 0), KeyRegistryError::InvalidPublicKey);
+    if key_type == KeyType::Ed25519 {
+        let is_identity = key[0] == 1 && key[1..].iter().all(|b| *b == 0);
+        require!(!is_identity, KeyRegistryError::InvalidPublicKey);
+    }
+    // Not a cryptographic check: real keys of 32+ bytes use ~30 distinct byte
+    // values, so very few means a placeholder like [0xab; 32] or a bad paste.
+    let mut seen = [0u64; 4];
+    let mut distinct = 0;
+    for b in key {
+        let (word, bit) = ((*b >> 6) as usize, 1u64 << (*b & 63));
+        if seen[word] & bit == 0 {
+            seen[word] |= bit;
+            distinct += 1;
+        }
+    }
+    require!(distinct >= MIN_DISTINCT_KEY_BYTES, KeyRegistryError::WeakKey);
+    Ok(())
+}
+
+// A handle without its zero padding, if it is valid UTF-8.
+fn handle_str(handle: &[u8; 32]) -> Option<&str> {
+    let len = handle.iter().rposition(|b| *b != 0).map_or(0, |i| i + 1);
+    std::str::from_utf8(&handle[..len]).ok()
+}
+
+// Handles are UTF-8 with zero padding only at the end.
+fn check_handle(handle: &[u8; 32]) -> Result<()> {
+    let text = handle_str(handle).ok_or(KeyRegistryError::InvalidHandle)?;
+    require!(!text.is_empty() && !text.contains('\0'), KeyRegistryError::InvalidHandle);
+    Ok(())
+}
+
+// Create a program-owned account at the PDA signed for by `seeds`, funded
+// for rent by `payer`.
+fn create_pda_account<'info>(
+    payer: &Signer<'info>,
+    target: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    space: usize,
+    seeds: &[&[u8]],
+) -> Result<()> {
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            system_program.to_account_info(),
+            system_program::CreateAccount {
+                from: payer.to_account_info(),
+                to: target.clone(),
+            },
+            &[seeds],
+        ),
+        Rent::get()?.minimum_balance(space),
+        space as u64,
+        &crate::ID,
+    )
+}
+
+// The record whose key `key_record` verifies against: itself, or if it is an
+// alias, `alias_target`, which must be the aliased record and not an alias.
+fn resolve_alias<'a, 'info>(
+    key_record: &'a Account<'info, KeyRecord>,
+    alias_target: Option<&'a Account<'info, KeyRecord>>,
+) -> Result<&'a KeyRecord> {
+    let Some(target) = key_record.alias_of else {
+        return Ok(key_record);
+    };
+    let alias_target = alias_target.ok_or(KeyRegistryError::InvalidRecord)?;
+    require_keys_eq!(alias_target.key(), target, KeyRegistryError::InvalidRecord);
+    require!(alias_target.alias_of.is_none(), KeyRegistryError::AliasChainTooDeep);
+    Ok(alias_target)
+}
+
+// The owner's Settings behind `settings`, a PDA passed unchecked because
+// most owners never create one; None while it doesn't exist.
+fn load_settings(settings: &AccountInfo) -> Result<Option<Settings>> {
+    if *settings.owner != crate::ID || settings.data_is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Settings::try_deserialize(&mut &settings.try_borrow_data()?[..])?))
+}
+
+// Fail with UpdateTooSoon while `key_record` was changed less than the
+// owner's `update_cooldown` before `now`; without Settings there is no
+// limit. Every path that replaces a record's key checks this.
+fn check_update_cooldown(settings: Option<&Settings>, key_record: &KeyRecord, now: i64) -> Result<()> {
+    if let Some(settings) = settings {
+        require!(
+            now.saturating_sub(key_record.updated_at) >= settings.update_cooldown,
+            KeyRegistryError::UpdateTooSoon
+        );
+    }
+    Ok(())
+}
+
+// Fail with LifetimeTooLong unless `expires_at` (0 = never) lies within the
+// owner's `max_lifetime` of `now`; without that policy any expiry goes.
+fn check_lifetime(settings: Option<&Settings>, expires_at: i64, now: i64) -> Result<()> {
+    if let Some(settings) = settings.filter(|settings| settings.max_lifetime > 0) {
+        require!(
+            expires_at != 0 && expires_at.saturating_sub(now) <= settings.max_lifetime,
+            KeyRegistryError::LifetimeTooLong
+        );
+    }
+    Ok(())
+}
+
+// Fail with VerifierNotAllowed unless `verifier` signed and is on the
+// record's allowlist (see set_verifier_allowlist); a record without one lets
+// anyone verify. Every instruction that checks or reveals the key (or its
+// history and metadata) for a third party calls this first.
+fn check_verifier(key_record: &KeyRecord, verifier: Option<&Signer>) -> Result<()> {
+    let allowlist = &key_record.verifier_allowlist;
+    if allowlist.iter().any(|allowed| *allowed != Pubkey::default()) {
+        let verifier = verifier.map(|verifier| verifier.key());
+        require!(
+            verifier.is_some_and(|verifier| allowlist.contains(&verifier)),
+            KeyRegistryError::VerifierNotAllowed
+        );
+    }
+    Ok(())
+}
+
+// `resolve_alias` once `verifier` passes `check_verifier` for the record and,
+// if it is an alias, for the aliased record too.
+fn resolve_verified<'a, 'info>(
+    key_record: &'a Account<'info, KeyRecord>,
+    alias_target: Option<&'a Account<'info, KeyRecord>>,
+    verifier: Option<&Signer>,
+) -> Result<&'a KeyRecord> {
+    check_verifier(key_record, verifier)?;
+    let resolved = resolve_alias(key_record, alias_target)?;
+    check_verifier(resolved, verifier)?;
+    Ok(resolved)
+}
+
+// Shared by `verify_key` and `verify_key_counted`: whether `candidate` is the
+// stored key at time `now`, logging and emitting `KeyVerified` either way.
+fn check_key_match(key_record: &KeyRecord, label: &str, candidate: &[u8], now: i64) -> Result<bool> {
+    if key_record.revoked {
+        msg!("❌ Public key for user {} has been revoked", key_record.owner);
+        emit!(KeyVerified {
+            owner: key_record.owner,
+            matched: false,
+            seq: key_record.seq,
+        });
+        return Ok(false);
+    }
+    if key_record.frozen {
+        msg!("🧊 Public key for user {} is frozen", key_record.owner);
+        emit!(KeyVerified {
+            owner: key_record.owner,
+            matched: false,
+            seq: key_record.seq,
+        });
+        return Ok(false);
+    }
+    if key_record.is_expired(now, current_slot()?) {
+        msg!("⌛ Public key for user {} expired at {}", key_record.owner, key_record.expires_at);
+        emit!(KeyVerified {
+            owner: key_record.owner,
+            matched: false,
+            seq: key_record.seq,
+        });
+        return Ok(false);
+    }
+    
+    let matches = keys_equal(key_record.key_bytes(), candidate, key_record.key_len as usize);
+    
+    if matches {
+        msg!("✅ Public key matches registered key {:?} for user: {}", label, key_record.owner);
+    } else {
+        msg!("❌ Public key does NOT match registered key {:?} for user: {}", label, key_record.owner);
+    }
+    
+    emit!(KeyVerified {
+        owner: key_record.owner,
+        matched: matches,
+        seq: key_record.seq,
+    });
+    
+    Ok(matches)
+}
+
+// Current Unix time from the `Clock` sysvar. Without the `clock` feature it is
+// always 0, so timestamps stay unset and expiry checks pass, which lets
+// instruction logic be unit-tested without a validator. Production builds must
+// keep `clock` (on by default) enabled.
+#[cfg(feature = "clock")]
+pub fn unix_now() -> Result<i64> {
+    Ok(Clock::get()?.unix_timestamp)
+}
+
+#[cfg(not(feature = "clock"))]
+pub fn unix_now() -> Result<i64> {
+    Ok(0)
+}
+
+// `unix_now`, or the config's `clock_override` when set in `test-clock`
+// builds. Every expiry, cooldown and activation check, and every time a
+// record stores (`created_at`, `updated_at`, the `STATUS_EXPIRED` bit), reads
+// the clock through this (or `optional_config_now`), so tests can move it
+// past a deadline; only the config's own `deployed_at` reads `unix_now`.
+#[cfg(feature = "test-clock")]
+pub fn config_now(config: &Config) -> Result<i64> {
+    if config.clock_override != 0 {
+        return Ok(config.clock_override);
+    }
+    unix_now()
+}
+
+#[cfg(not(feature = "test-clock"))]
+pub fn config_now(_config: &Config) -> Result<i64> {
+    unix_now()
+}
+
+// `config_now` for instructions taking the config as an optional account;
+// the real clock when it isn't passed.
+pub fn optional_config_now(config: Option<&Account<Config>>) -> Result<i64> {
+    match config {
+        Some(config) => config_now(config),
+        None => unix_now(),
+    }
+}
+
+#[cfg(feature = "test-clock")]
+fn set_clock_override(config: &mut Config, unix_ts: i64) -> Result<()> {
+    config.clock_override = unix_ts;
+    Ok(())
+}
+
+#[cfg(not(feature = "test-clock"))]
+fn set_clock_override(_config: &mut Config, _unix_ts: i64) -> Result<()> {
+    err!(KeyRegistryError::TestClockDisabled)
+}
+
+// The current slot, for slot-based expiry; 0 without `clock` like `unix_now`.
+#[cfg(feature = "clock")]
+pub fn current_slot() -> Result<u64> {
+    Ok(Clock::get()?.slot)
+}
+
+#[cfg(not(feature = "clock"))]
+pub fn current_slot() -> Result<u64> {
+    Ok(0)
+}
+
+// `space`, once `payer` is known to cover rent for it plus the `other` accounts
+// the instruction creates; otherwise InsufficientRent, logging the shortfall.
+fn rent_checked_space(payer: &AccountInfo, space: usize, other: &[usize]) -> Result<usize> {
+    let rent = Rent::get()?;
+    let required: u64 = rent.minimum_balance(space)
+        + other.iter().map(|other_space| rent.minimum_balance(*other_space)).sum::<u64>();
+    if payer.lamports() < required {
+        msg!("Payer {} has {} lamports, rent needs {}", payer.key(), payer.lamports(), required);
+        return err!(KeyRegistryError::InsufficientRent);
+    }
+    Ok(space)
+}
+
+// Reference `(owner, key_record PDA, bump)` triples for client implementations
+// to check their derivation against: fixed owners `[1; 32]`..`[4; 32]`, empty
+// label. Derived against this crate's `ID`, so they follow a program id change.
+#[cfg(not(target_os = "solana"))]
+pub fn test_vectors() -> Vec<(Pubkey, Pubkey, u8)> {
+    (1..=4u8)
+        .map(|fill| {
+            let owner = Pubkey::new_from_array([fill; 32]);
+            let (pda, bump) =
+                Pubkey::find_program_address(&[b"key_record", owner.as_ref(), b""], &crate::ID);
+            (owner, pda, bump)
+        })
+        .collect()
+}
+
+// Whether `a` and `b` are both `len` bytes long and equal. The contents are
+// compared in constant time (no early exit), so a mismatch position doesn't
+// show in compute usage; lengths are public and checked first.
+fn keys_equal(a: &[u8], b: &[u8], len: usize) -> bool {
+    if a.len() != len || b.len() != len {
+        return false;
+    }
+    let diff = a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y));
+    std::hint::black_box(diff) == 0
+}
+
+// Leaf hash of a compressed registration.
+fn compressed_leaf(owner: &Pubkey, key_type: KeyType, public_key: &[u8]) -> [u8; 32] {
+    keccak::hashv(&[owner.as_ref(), &[key_type as u8], public_key]).to_bytes()
+}
+
+// Call the Anchor instruction `name` of the SPL account-compression program.
+// Its crate isn't a dependency, so the instruction is built by hand.
+fn invoke_compression<'info>(
+    compression_program: &AccountInfo<'info>,
+    name: &str,
+    args: Vec<u8>,
+    accounts: Vec<AccountMeta>,
+    infos: &[AccountInfo<'info>],
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let preimage = format!("global:{}", name);
+    let mut data = anchor_lang::solana_program::hash::hash(preimage.as_bytes()).to_bytes()[..8].to_vec();
+    data.extend_from_slice(&args);
+    let ix = Instruction {
+        program_id: compression_program.key(),
+        accounts,
+        data,
+    };
+    let signers: &[&[&[u8]]] = if signer_seeds.is_empty() { &[] } else { &[signer_seeds] };
+    invoke_signed(&ix, infos, signers)?;
+    Ok(())
+}
+
+// What a registration sets on a new record, see `init_record`.
+struct NewRecord<'a> {
+    registry_id: [u8; 8],
+    owner: Pubkey,
+    key_type: KeyType,
+    public_key: &'a [u8],
+    bump: u8,
+    label: String,
+    expires_at: i64,
+    usage_flags: u8,
+    staked: u64,
+}
+
+// Fill in a freshly created (all-zero) record at `address`, registered at
+// `now`, and start its audit chain. Every registration path goes through here, so a new field
+// only needs its initial value set once.
+fn init_record(key_record: &mut KeyRecord, address: Pubkey, new: NewRecord, now: i64) -> Result<()> {
+    key_record.version = KeyRecord::CURRENT_VERSION;
+    key_record.registry_id = new.registry_id;
+    key_record.staked = new.staked;
+    key_record.owner = new.owner;
+    key_record.registrant = new.owner;
+    key_record.key_type = new.key_type as u8;
+    key_record.set_key(new.public_key);
+    key_record.bump = new.bump;
+    key_record.expires_at = new.expires_at;
+    key_record.label = new.label;
+    key_record.usage_flags = new.usage_flags;
+    key_record.created_at = now;
+    key_record.updated_at = now;
+    key_record.extend_audit(address, AuditOp::Register, now)
+}
+
+// Point a fresh reverse index entry (public key -> owner) at `record`. Each
+// key may be indexed once.
+fn claim_key_index(key_index: &mut KeyIndex, owner: Pubkey, record: Pubkey, bump: u8) -> Result<()> {
+    require_keys_eq!(key_index.owner, Pubkey::default(), KeyRegistryError::KeyAlreadyIndexed);
+    key_index.owner = owner;
+    key_index.record = record;
+    key_index.bump = bump;
+    Ok(())
+}
+
+// A key about to be registered has its type's length and passes
+// `check_public_key`.
+fn check_new_key(key_type: KeyType, public_key: &[u8]) -> Result<()> {
+    require!(
+        public_key.len() == key_type.key_len(),
+        KeyRegistryError::InvalidKeyLength
+    );
+    check_public_key(key_type, public_key)
+}
+
+// Registrations of Ed25519 keys must prove possession: the preceding Ed25519
+// instruction checks `signature` by the key over the owner's pubkey bytes.
+// Other key types have no precompile to check them with.
+fn check_possession(
+    instructions: &AccountInfo,
+    key_type: KeyType,
+    public_key: &[u8],
+    owner: &Pubkey,
+    signature: &[u8; 64],
+) -> Result<()> {
+    if key_type == KeyType::Ed25519 {
+        let possessed = preceding_ed25519_matches(instructions, public_key, owner.as_ref(), signature)?;
+        require!(possessed, KeyRegistryError::InvalidProofOfPossession);
+    }
+    Ok(())
+}
+
+// Charge a registration: the config's fee goes to `treasury` and its stake is
+// locked in the new `record`. Returns the stake, for `KeyRecord::staked`.
+fn collect_registration<'info>(
+    payer: &Signer<'info>,
+    treasury: &AccountInfo<'info>,
+    record: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    config: &Config,
+) -> Result<u64> {
+    collect_fee(payer, treasury, system_program, config.fee_lamports)?;
+    collect_fee(payer, record, system_program, config.stake_lamports)?;
+    Ok(config.stake_lamports)
+}
+
+// Transfer the registration fee from `payer` to `treasury`, if there is one.
+// Also locks the registration stake, with the new record as `treasury`.
+fn collect_fee<'info>(
+    payer: &Signer<'info>,
+    treasury: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    fee_lamports: u64,
+) -> Result<()> {
+    if fee_lamports == 0 {
+        return Ok(());
+    }
+    require!(payer.lamports() >= fee_lamports, KeyRegistryError::FeeTransferFailed);
+    system_program::transfer(
+        CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: treasury.clone(),
+            },
+        ),
+        fee_lamports,
+    )
+    .map_err(|_| error!(KeyRegistryError::FeeTransferFailed))
+}
+
+// Realloc `account` to `new_len` bytes (zero-filled), with `payer` topping up
+// its rent exemption.
+fn grow_account<'info>(
+    payer: &Signer<'info>,
+    account: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    new_len: usize,
+) -> Result<()> {
+    let shortfall = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+// Resize the program-owned `record` to `new_len` bytes, keeping it rent
+// exempt plus its locked `staked` lamports: `payer` covers a shortfall and is
+// refunded anything above that, so a shrink only gives back the rent.
+fn resize_record<'info>(
+    payer: &Signer<'info>,
+    record: &AccountInfo<'info>,
+    system_program: &Program<'info, System>,
+    new_len: usize,
+    staked: u64,
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(new_len).saturating_add(staked);
+    let balance = record.lamports();
+    if balance < required {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: record.clone(),
+                },
+            ),
+            required - balance,
+        )?;
+    } else if balance > required {
+        let refund = balance - required;
+        **record.try_borrow_mut_lamports()? -= refund;
+        **payer.to_account_info().try_borrow_mut_lamports()? += refund;
+    }
+    record.realloc(new_len, true)?;
+    Ok(())
+}
+
+// Reject expiry timestamps that are already in the past at `now` (0 = never
+// expires).
+fn check_expiry(expires_at: i64, now: i64) -> Result<()> {
+    if expires_at != 0 {
+        require!(
+            expires_at > now,
+            KeyRegistryError::InvalidExpiry
+        );
+    }
+    Ok(())
+}
+
+// Whether the instruction just before the current one is an Ed25519 native
+// program call that checked `signature` by `public_key` over `message`.
+fn preceding_ed25519_matches(
+    instructions: &AccountInfo,
+    public_key: &[u8],
+    message: &[u8],
+    signature: &[u8; 64],
+) -> Result<bool> {
+    let current = load_current_index_checked(instructions)?;
+    if current == 0 {
+        return Ok(false);
+    }
+    let ix = load_instruction_at_checked(current as usize - 1, instructions)?;
+    
+    Ok(ed25519_ix_matches(&ix, public_key, message, signature))
+}
+
+// Whether the secp256k1 key recovered from `signature` over keccak256(message)
+// is `public_key` (33-byte compressed SEC1).
+fn secp256k1_signer_matches(public_key: &[u8], message: &[u8], signature: &[u8; 64], recovery_id: u8) -> bool {
+    let hash = keccak::hash(message).to_bytes();
+    let Ok(recovered) = secp256k1_recover(&hash, recovery_id, signature) else {
+        return false;
+    };
+    // uncompressed x || y; compressed is a y-parity prefix and x
+    let point = recovered.to_bytes();
+    let prefix = 0x02 | (point[63] & 1);
+    public_key.len() == 33 && public_key[0] == prefix && public_key[1..] == point[..32]
+}
+
+// Whether `signature` (r || s) is a valid P-256 ECDSA signature by
+// `public_key` (33-byte compressed SEC1) over SHA-256(message).
+#[cfg(feature = "p256")]
+fn p256_signature_matches(public_key: &[u8], message: &[u8], signature: &[u8; 64]) -> bool {
+    use p256::ecdsa::signature::Verifier;
+    use p256::ecdsa::{Signature, VerifyingKey};
+    
+    let Ok(key) = VerifyingKey::from_sec1_bytes(public_key) else {
+        return false;
+    };
+    let Ok(signature) = Signature::try_from(signature.as_slice()) else {
+        return false;
+    };
+    key.verify(message, &signature).is_ok()
+}
+
+// Parse an Ed25519 native program instruction and look for an entry that
+// covers exactly this (public_key, message, signature). Only entries whose
+// data lives inside the instruction itself are accepted.
+fn ed25519_ix_matches(ix: &Instruction, public_key: &[u8], message: &[u8], signature: &[u8; 64]) -> bool {
+    const HEADER_LEN: usize = 2;
+    const OFFSETS_LEN: usize = 14;
+    const THIS_IX: usize = u16::MAX as usize;
+    
+    if ix.program_id != ed25519_program::ID || !ix.accounts.is_empty() {
+        return false;
+    }
+    let data = &ix.data;
+    if data.len() < HEADER_LEN {
+        return false;
+    }
+    let slice = |at: usize, len: usize| data.get(at..at + len);
+    
+    (0..data[0] as usize).any(|i| {
+        let Some(offsets) = slice(HEADER_LEN + i * OFFSETS_LEN, OFFSETS_LEN) else {
+            return false;
+        };
+        let field = |n: usize| u16::from_le_bytes([offsets[n * 2], offsets[n * 2 + 1]]) as usize;
+        // signature, public key and message offsets, each followed by an
+        // instruction index (the message also carries its length)
+        let (sig_at, sig_ix) = (field(0), field(1));
+        let (key_at, key_ix) = (field(2), field(3));
+        let (msg_at, msg_len, msg_ix) = (field(4), field(5), field(6));
+        
+        sig_ix == THIS_IX
+            && key_ix == THIS_IX
+            && msg_ix == THIS_IX
+            && slice(sig_at, 64) == Some(&signature[..])
+            && slice(key_at, 32) == Some(public_key)
+            && slice(msg_at, msg_len) == Some(message)
+    })
+}
+
+#[event]
+pub struct KeyRegistered {
+    pub owner: Pubkey,
+    pub key_type: u8,
+    pub public_key: Vec<u8>,
+    pub seq: u64,
+}
+
+#[event]
+pub struct KeyUpdated {
+    pub owner: Pubkey,
+    pub old_key: Vec<u8>,
+    pub new_key: Vec<u8>,
+    pub seq: u64,
+}
+
+#[event]
+pub struct KeyVerified {
+    pub owner: Pubkey,
+    pub matched: bool,
+    pub seq: u64,
+}
+
+#[event]
+pub struct BackupKeyUsed {
+    pub owner: Pubkey,
+    pub seq: u64,
+}
+
+#[event]
+pub struct KeyRevoked {
+    pub owner: Pubkey,
+    pub at: i64,
+    pub seq: u64,
+}
+
+#[event]
+pub struct KeyFrozen {
+    pub owner: Pubkey,
+    pub frozen: bool,
+    pub seq: u64,
+}
+
+#[event]
+pub struct AuditExtended {
+    pub record: Pubkey,
+    pub operation: u8,
+    pub seq: u64,
+    pub state: Vec<u8>,
+    pub head: [u8; 32],
+}
+
+#[event]
+pub struct KeyAttested {
+    pub owner: Pubkey,
+    pub key_type: u8,
+    pub public_key: Vec<u8>,
+    pub slot: u64,
+    pub status: u8,
+    pub seq: u64,
 }
 
+// Anchor reports these as 6000 + the discriminant. The discriminants are
+// pinned so clients can rely on the codes: never renumber or reuse one, and
+// give new variants the next free number.
 #[error_code]
 pub enum KeyRegistryError {
     #[msg("Unauthorized: You are not the owner of this key record")]
-    Unauthorized,
+    Unauthorized = 0,
+    #[msg("Revoked: This key has been revoked")]
+    Revoked = 1,
+    #[msg("InvalidKeyLength: Public key length does not match its key type")]
+    InvalidKeyLength = 2,
+    #[msg("SignatureVerificationFailed: No valid signature by the registered key was found")]
+    SignatureVerificationFailed = 3,
+    #[msg("InvalidProofOfPossession: Registration must be signed by the key being registered")]
+    InvalidProofOfPossession = 4,
+    #[msg("InvalidExpiry: Expiry timestamp is already in the past")]
+    InvalidExpiry = 5,
+    #[msg("Paused: The registry is paused by its admin")]
+    Paused = 6,
+    #[msg("LabelTooLong: Key labels are at most 16 bytes")]
+    LabelTooLong = 7,
+    #[msg("BatchTooLarge: Too many entries in a single batch")]
+    BatchTooLarge = 8,
+    #[msg("KeyMismatch: Public key does not match the registered key")]
+    KeyMismatch = 9,
+    #[msg("KeyExpired: This key has expired")]
+    KeyExpired = 10,
+    #[msg("InvalidRecord: Account is not a key record")]
+    InvalidRecord = 11,
+    #[msg("KeyAlreadyIndexed: This public key is already registered")]
+    KeyAlreadyIndexed = 12,
+    #[msg("InvalidPublicKey: Public key is all zeros or a small-order point")]
+    InvalidPublicKey = 13,
+    #[msg("InvalidGuardians: Up to 5 distinct guardians with 1 <= threshold <= count")]
+    InvalidGuardians = 14,
+    #[msg("InsufficientGuardians: Not enough guardian signatures to recover this record")]
+    InsufficientGuardians = 15,
+    #[msg("InvalidHandle: Handle must be zero-padded UTF-8 with its index account passed")]
+    InvalidHandle = 16,
+    #[msg("HandleTaken: This handle is already in use")]
+    HandleTaken = 17,
+    #[msg("AlreadyRegistered: A key record already exists at this address")]
+    AlreadyRegistered = 18,
+    #[msg("Frozen: This key is frozen")]
+    Frozen = 19,
+    #[msg("NotFrozen: This key is not frozen")]
+    NotFrozen = 20,
+    #[msg("NotRevoked: This key has not been revoked")]
+    NotRevoked = 21,
+    #[msg("UsageNotPermitted: This key is not permitted for the requested usage")]
+    UsageNotPermitted = 22,
+    #[msg("InsufficientRent: Payer cannot cover rent for the new accounts")]
+    InsufficientRent = 23,
+    #[msg("AliasChainTooDeep: An alias cannot point to another alias")]
+    AliasChainTooDeep = 24,
+    #[msg("ChallengeExpired: This challenge has expired")]
+    ChallengeExpired = 25,
+    #[msg("UriTooLong: Metadata URIs are at most 128 bytes")]
+    UriTooLong = 26,
+    #[msg("FeeTransferFailed: Could not pay the registration fee")]
+    FeeTransferFailed = 27,
+    #[msg("CannotShrink: Key records can only grow")]
+    CannotShrink = 28,
+    #[msg("BadNonce: Nonce does not match the record's current nonce")]
+    BadNonce = 29,
+    #[msg("UnsupportedKeyType: Signatures cannot be verified for this key type")]
+    UnsupportedKeyType = 30,
+    #[msg("NoPendingUpdate: No key update has been proposed")]
+    NoPendingUpdate = 31,
+    #[msg("UpdateNotReady: The pending key update is still time-locked")]
+    UpdateNotReady = 32,
+    #[msg("CommitmentMismatch: Public key and salt do not open the commitment")]
+    CommitmentMismatch = 33,
+    #[msg("RevealTooEarly: Too few slots have passed since the commitment")]
+    RevealTooEarly = 34,
+    #[msg("CallerNotAllowed: The calling program is not on the config's allow list")]
+    CallerNotAllowed = 35,
+    #[msg("TooManyAllowedPrograms: At most 8 programs can be allowed")]
+    TooManyAllowedPrograms = 36,
+    #[msg("KeyTypeMismatch: The record holds a key of a different type")]
+    KeyTypeMismatch = 37,
+    #[msg("BundleFull: A key bundle holds at most 8 keys")]
+    BundleFull = 38,
+    #[msg("KeyNotInBundle: This key is not in the bundle")]
+    KeyNotInBundle = 39,
+    #[msg("UpdateTooSoon: The key was updated too recently, see update_cooldown")]
+    UpdateTooSoon = 40,
+    #[msg("ProgramIdMismatch: This build's program id is not the deployed program's")]
+    ProgramIdMismatch = 41,
+    #[msg("InvalidEncoding: The key is not valid base58")]
+    InvalidEncoding = 42,
+    #[msg("InvalidAttestationTarget: A key record can't sign into this program or be passed twice")]
+    InvalidAttestationTarget = 43,
+    #[msg("InvalidVerifierProgram: Not the record's verifier program, or this program itself")]
+    InvalidVerifierProgram = 44,
+    #[msg("TestClockDisabled: set_test_clock needs a build with the test-clock feature")]
+    TestClockDisabled = 45,
+    #[msg("WeakKey: The key uses too few distinct bytes to be a real key")]
+    WeakKey = 46,
+    #[msg("InvalidHistoryCapacity: A record keeps at most 16 previous keys")]
+    InvalidHistoryCapacity = 47,
+    #[msg("OffsetOutOfBounds: The key would extend past the end of the blob")]
+    OffsetOutOfBounds = 48,
+    #[msg("VerifierNotAllowed: The record owner only allows listed verifiers")]
+    VerifierNotAllowed = 49,
+    #[msg("TooManyVerifiers: At most 4 verifiers can be allowed")]
+    TooManyVerifiers = 50,
+    #[msg("OrgRecordCannotAttest: Org key records can't sign attestations")]
+    OrgRecordCannotAttest = 51,
+    #[msg("LifetimeTooLong: The expiry is past the owner's max_lifetime policy")]
+    LifetimeTooLong = 52,
 }
 
+#[cfg(test)]
+mod tests;
 
 
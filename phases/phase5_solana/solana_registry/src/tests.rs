@@ -0,0 +1,418 @@
+// Unit tests of the pure helpers and layout constants.
+
+use super::*;
+
+// `test_vectors` are the key_record PDAs the program itself derives for an
+// owner's unlabeled record in the default registry.
+#[test]
+fn test_vectors_match_record_seeds() {
+    let vectors = test_vectors();
+    assert_eq!(vectors.len(), 4);
+    for (fill, (owner, pda, bump)) in (1..=4u8).zip(vectors) {
+        assert_eq!(owner, Pubkey::new_from_array([fill; 32]));
+        let seeds: [&[u8]; 4] = [b"key_record", registry_seed(&[0u8; 8]), owner.as_ref(), b""];
+        assert_eq!(Pubkey::find_program_address(&seeds, &crate::ID), (pda, bump));
+        let with_bump = [b"key_record".as_ref(), owner.as_ref(), b"", &[bump]];
+        assert_eq!(Pubkey::create_program_address(&with_bump, &crate::ID).unwrap(), pda);
+    }
+}
+
+#[test]
+fn keys_equal_compares_length_and_contents() {
+    let key = [7u8; 32];
+    assert!(keys_equal(&key, &key, 32));
+    assert!(keys_equal(&[], &[], 0));
+    
+    let mut flipped = key;
+    flipped[31] ^= 1;
+    assert!(!keys_equal(&key, &flipped, 32));
+    flipped = key;
+    flipped[0] ^= 0x80;
+    assert!(!keys_equal(&key, &flipped, 32));
+    
+    // both sides must be exactly `len` bytes, not just share a prefix
+    assert!(!keys_equal(&key[..31], &key[..31], 32));
+    assert!(!keys_equal(&key, &key[..31], 31));
+    assert!(!keys_equal(&key, &key, 31));
+    // a short key doesn't match its zero-padded form
+    let mut padded = [0u8; MAX_KEY_LEN];
+    padded[..32].copy_from_slice(&key);
+    assert!(!keys_equal(&key, &padded, 32));
+}
+
+// Every field at its largest, with no history overflow: Borsh writes each
+// field back to back, so this is the sum of their sizes.
+fn largest_record() -> KeyRecord {
+    let mut record = KeyRecord::zeroed();
+    record.label = "l".repeat(KeyRecord::MAX_LABEL_LEN);
+    record.uri = "u".repeat(KeyRecord::MAX_URI_LEN);
+    record.alias_of = Some(Pubkey::new_unique());
+    record.verifier_program = Some(Pubkey::new_unique());
+    record.history_limit = Some(KeyRecord::HISTORY_LEN as u8);
+    record
+}
+
+#[test]
+fn len_is_the_sum_of_field_sizes() {
+    assert_eq!(largest_record().try_to_vec().unwrap().len(), KeyRecord::LEN);
+    // and never less than the smallest record
+    assert!(KeyRecord::zeroed().try_to_vec().unwrap().len() <= KeyRecord::LEN);
+}
+
+// Clients match on these numbers, so they must never change with the enum
+// order: new variants only ever get new codes.
+#[test]
+fn error_codes_are_stable() {
+    use KeyRegistryError::*;
+    let codes = [
+        (Unauthorized, 6000),
+        (Revoked, 6001),
+        (InvalidKeyLength, 6002),
+        (SignatureVerificationFailed, 6003),
+        (InvalidProofOfPossession, 6004),
+        (InvalidExpiry, 6005),
+        (Paused, 6006),
+        (LabelTooLong, 6007),
+        (BatchTooLarge, 6008),
+        (KeyMismatch, 6009),
+        (KeyExpired, 6010),
+        (InvalidRecord, 6011),
+        (KeyAlreadyIndexed, 6012),
+        (InvalidPublicKey, 6013),
+        (InvalidGuardians, 6014),
+        (InsufficientGuardians, 6015),
+        (InvalidHandle, 6016),
+        (HandleTaken, 6017),
+        (AlreadyRegistered, 6018),
+        (Frozen, 6019),
+        (NotFrozen, 6020),
+        (NotRevoked, 6021),
+        (UsageNotPermitted, 6022),
+        (InsufficientRent, 6023),
+        (AliasChainTooDeep, 6024),
+        (ChallengeExpired, 6025),
+        (UriTooLong, 6026),
+        (FeeTransferFailed, 6027),
+        (CannotShrink, 6028),
+        (BadNonce, 6029),
+        (UnsupportedKeyType, 6030),
+        (NoPendingUpdate, 6031),
+        (UpdateNotReady, 6032),
+        (CommitmentMismatch, 6033),
+        (RevealTooEarly, 6034),
+        (CallerNotAllowed, 6035),
+        (TooManyAllowedPrograms, 6036),
+        (KeyTypeMismatch, 6037),
+        (BundleFull, 6038),
+        (KeyNotInBundle, 6039),
+        (UpdateTooSoon, 6040),
+        (ProgramIdMismatch, 6041),
+        (InvalidEncoding, 6042),
+        (InvalidAttestationTarget, 6043),
+        (InvalidVerifierProgram, 6044),
+        (TestClockDisabled, 6045),
+        (WeakKey, 6046),
+        (InvalidHistoryCapacity, 6047),
+        (OffsetOutOfBounds, 6048),
+        (VerifierNotAllowed, 6049),
+        (TooManyVerifiers, 6050),
+        (OrgRecordCannotAttest, 6051),
+        (LifetimeTooLong, 6052),
+    ];
+    for (error, code) in codes {
+        assert_eq!(u32::from(error), code, "{error:?}");
+    }
+}
+
+// ACCOUNT_SIZE is a record's serialized length with the longest label and
+// URI, discriminator included, as the account stores it.
+#[test]
+fn account_size_is_the_serialized_record() {
+    let mut data = Vec::new();
+    largest_record().try_serialize(&mut data).unwrap();
+    assert_eq!(data.len(), ACCOUNT_SIZE);
+    
+    let mut data = Vec::new();
+    KeyRecord::zeroed().try_serialize(&mut data).unwrap();
+    assert!(data.len() <= ACCOUNT_SIZE);
+    // each history slot past HISTORY_LEN adds one key
+    assert_eq!(
+        KeyRecord::account_size(KeyRecord::MAX_HISTORY_CAPACITY),
+        ACCOUNT_SIZE + MAX_KEY_LEN * (KeyRecord::MAX_HISTORY_CAPACITY - KeyRecord::HISTORY_LEN)
+    );
+}
+
+#[test]
+fn parse_key_record_reads_account_data() {
+    let mut record = KeyRecord::zeroed();
+    record.version = KeyRecord::CURRENT_VERSION;
+    record.owner = Pubkey::new_unique();
+    record.registrant = record.owner;
+    record.key_type = KeyType::Secp256k1 as u8;
+    record.set_key(&[3u8; 33]);
+    record.label = "phone".to_string();
+    record.expires_at = 1_700_000_000;
+    let mut data = Vec::new();
+    record.try_serialize(&mut data).unwrap();
+    data.resize(ACCOUNT_SIZE, 0);
+    
+    let parsed = parse_key_record(&data).unwrap();
+    assert_eq!(parsed.owner, record.owner);
+    assert_eq!(parsed.registrant, record.owner);
+    assert_eq!(parsed.key_type, KeyType::Secp256k1 as u8);
+    assert_eq!(parsed.key_bytes(), &[3u8; 33]);
+    assert_eq!(parsed.label, "phone");
+    assert_eq!(parsed.expires_at, 1_700_000_000);
+    
+    // another account type's discriminator, an older version, or no data
+    let mut other = data.clone();
+    other[..8].copy_from_slice(&Config::DISCRIMINATOR);
+    assert!(parse_key_record(&other).is_err());
+    let mut old = data.clone();
+    old[8] = 2;
+    assert!(parse_key_record(&old).is_err());
+    assert!(parse_key_record(&data[..8]).is_err());
+}
+
+// Each `status` bit is set and cleared with the field it mirrors, leaving
+// the others alone.
+#[test]
+fn refresh_status_tracks_each_flag() {
+    let mut record = KeyRecord::zeroed();
+    for bit in [KeyRecord::STATUS_REVOKED, KeyRecord::STATUS_FROZEN, KeyRecord::STATUS_EXPIRED] {
+        for on in [true, false] {
+            record.revoked = on && bit == KeyRecord::STATUS_REVOKED;
+            record.frozen = on && bit == KeyRecord::STATUS_FROZEN;
+            // expiry is judged against `now` (100): 99 has passed, 101 hasn't
+            record.expires_at = if on && bit == KeyRecord::STATUS_EXPIRED { 99 } else { 101 };
+            record.refresh_status(100, 0);
+            assert_eq!(record.status, if on { bit } else { 0 });
+        }
+    }
+    
+    // a slot expiry counts as well
+    record.expires_at = 0;
+    record.expires_at_slot = 10;
+    record.refresh_status(0, 10);
+    assert_eq!(record.status, KeyRecord::STATUS_EXPIRED);
+    record.refresh_status(0, 9);
+    assert_eq!(record.status, 0);
+    
+    // usage bits sit in the high byte, clear of the flags
+    for usage in [KeyRecord::USAGE_SIGN, KeyRecord::USAGE_ENCRYPT, KeyRecord::USAGE_AUTH, u8::MAX] {
+        record.usage_flags = usage;
+        record.revoked = true;
+        record.refresh_status(0, 0);
+        assert_eq!(record.status, (usage as u16) << KeyRecord::STATUS_USAGE_SHIFT | KeyRecord::STATUS_REVOKED);
+        record.usage_flags = 0;
+        record.revoked = false;
+        record.refresh_status(0, 0);
+        assert_eq!(record.status, 0);
+    }
+}
+
+// build.rs bakes KEY_REGISTRY_PROGRAM_ID into `ID`, or without it the
+// localnet id from Anchor.toml.
+#[test]
+fn program_id_follows_the_build_env() {
+    let expected = option_env!("KEY_REGISTRY_PROGRAM_ID").unwrap_or("asdmk9ot5XzRsYcqezCSGft923CuGJaJS3nR5wfJcpm");
+    assert_eq!(crate::ID.to_string(), expected);
+}
+
+// Property tests: Borsh round-trips and layout upgrades of arbitrary records,
+// key checks on arbitrary keys, and decoding of arbitrary bytes, which must
+// fail cleanly rather than panic.
+mod props {
+    use super::*;
+    use proptest::collection::vec;
+    use proptest::prelude::*;
+
+    fn key_type() -> impl Strategy<Value = KeyType> {
+        prop_oneof![
+            Just(KeyType::Ed25519),
+            Just(KeyType::Secp256k1),
+            Just(KeyType::X25519),
+            Just(KeyType::Secp256r1),
+        ]
+    }
+
+    // KeyRecord isn't Debug, which proptest needs to report a failing case;
+    // this shows the fields the strategy varies.
+    #[derive(Clone)]
+    struct Record(KeyRecord);
+
+    impl std::fmt::Debug for Record {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            let record = &self.0;
+            f.debug_struct("KeyRecord")
+                .field("owner", &record.owner)
+                .field("key_type", &record.key_type)
+                .field("key", &record.key_bytes())
+                .field("label", &record.label)
+                .field("uri", &record.uri)
+                .field("status", &record.status)
+                .field("history_limit", &record.history_limit)
+                .field("alias_of", &record.alias_of)
+                .finish_non_exhaustive()
+        }
+    }
+
+    prop_compose! {
+        fn record()(
+            (key_type, key) in key_type().prop_flat_map(|key_type| (Just(key_type), vec(any::<u8>(), key_type.key_len()))),
+            owner in any::<[u8; 32]>(),
+            label in "[a-z0-9]{0,16}",
+            uri in "[ -~]{0,128}",
+            flags in (any::<bool>(), any::<bool>(), any::<u8>(), any::<u16>()),
+            times in (any::<i64>(), any::<i64>(), any::<u64>(), any::<u64>()),
+            capacity in 0..=KeyRecord::MAX_HISTORY_CAPACITY as u8,
+            alias_of in proptest::option::of(any::<[u8; 32]>()),
+        ) -> Record {
+            let mut record = KeyRecord::zeroed();
+            record.version = KeyRecord::CURRENT_VERSION;
+            record.owner = Pubkey::new_from_array(owner);
+            record.registrant = record.owner;
+            record.key_type = key_type as u8;
+            record.set_key(&key);
+            record.label = label;
+            record.uri = uri;
+            (record.revoked, record.frozen, record.usage_flags, record.status) = flags;
+            (record.expires_at, record.created_at, record.expires_at_slot, record.seq) = times;
+            record.reset_history(capacity);
+            record.alias_of = alias_of.map(Pubkey::new_from_array);
+            Record(record)
+        }
+    }
+
+    // A record's account data, padded to its account size.
+    fn account_data(record: &KeyRecord) -> Vec<u8> {
+        let mut data = Vec::new();
+        record.try_serialize(&mut data).unwrap();
+        assert!(data.len() <= KeyRecord::account_size(record.history_capacity()));
+        data.resize(KeyRecord::account_size(record.history_capacity()), 0);
+        data
+    }
+
+    // `record` in the v3 layout: its body with `delegate` back after
+    // `expires_at` and `guardians` (with `guardian_threshold`) after `updated_at`.
+    fn v3_body(record: &KeyRecord, delegate: &[u8], guardians: &[u8]) -> Vec<u8> {
+        let mut body = record.try_to_vec().unwrap();
+        body[0] = 3;
+        let guardians_at = KeyRecord::V3_DELEGATE_AT + 4 + record.label.len() + 32 + 8 + 8;
+        body.splice(guardians_at..guardians_at, guardians.iter().copied());
+        body.splice(KeyRecord::V3_DELEGATE_AT..KeyRecord::V3_DELEGATE_AT, delegate.iter().copied());
+        body
+    }
+
+    proptest! {
+        #[test]
+        fn records_round_trip(Record(record) in record()) {
+            let data = account_data(&record);
+            let decoded = KeyRecord::try_deserialize(&mut data.as_slice()).unwrap();
+            prop_assert_eq!(decoded.try_to_vec().unwrap(), record.try_to_vec().unwrap());
+            let parsed = parse_key_record(&data).unwrap();
+            prop_assert_eq!(parsed.try_to_vec().unwrap(), record.try_to_vec().unwrap());
+            
+            // a current record comes out of upgrade_layout as it went in
+            let (version, body) = KeyRecord::upgrade_layout(&data).unwrap();
+            prop_assert_eq!(version, KeyRecord::CURRENT_VERSION);
+            let upgraded = KeyRecord::deserialize(&mut body.as_slice()).unwrap();
+            prop_assert_eq!(upgraded.try_to_vec().unwrap(), record.try_to_vec().unwrap());
+        }
+        
+        #[test]
+        fn v3_records_upgrade_without_the_dropped_fields(
+            Record(record) in record(),
+            delegate in any::<[u8; 32]>(),
+            guardians in vec(any::<u8>(), 32 * 5 + 1),
+        ) {
+            let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+            data.extend_from_slice(&v3_body(&record, &delegate, &guardians));
+            
+            let (version, upgraded) = KeyRecord::upgrade_layout(&data).unwrap();
+            prop_assert_eq!(version, 3);
+            let mut expected = record.clone();
+            expected.version = 3;
+            let upgraded = KeyRecord::deserialize(&mut upgraded.as_slice()).unwrap();
+            prop_assert_eq!(upgraded.try_to_vec().unwrap(), expected.try_to_vec().unwrap());
+        }
+        
+        #[test]
+        fn v2_records_upgrade_with_a_zero_status(Record(record) in record()) {
+            // the v2 layout is the v3 one without `status`
+            let body = v3_body(&record, &[0u8; 32], &[0u8; 32 * 5 + 1]);
+            let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+            data.push(2);
+            data.extend_from_slice(&body[3..]);
+            
+            let (version, upgraded) = KeyRecord::upgrade_layout(&data).unwrap();
+            prop_assert_eq!(version, 2);
+            let mut expected = record.clone();
+            expected.version = 2;
+            expected.status = 0;
+            let upgraded = KeyRecord::deserialize(&mut upgraded.as_slice()).unwrap();
+            prop_assert_eq!(upgraded.try_to_vec().unwrap(), expected.try_to_vec().unwrap());
+        }
+        
+        #[test]
+        fn truncated_records_are_rejected(Record(record) in record(), cut in any::<prop::sample::Index>()) {
+            let mut data = Vec::new();
+            record.try_serialize(&mut data).unwrap();
+            let data = &data[..cut.index(data.len())];
+            prop_assert!(KeyRecord::try_deserialize(&mut &data[..]).is_err());
+            prop_assert!(parse_key_record(data).is_err());
+        }
+        
+        #[test]
+        fn random_bytes_are_rejected_cleanly(data in vec(any::<u8>(), 0..2 * ACCOUNT_SIZE)) {
+            // all but impossibly lucky input lacks the discriminator
+            prop_assume!(data.len() < 8 || data[..8] != KeyRecord::DISCRIMINATOR);
+            prop_assert!(KeyRecord::try_deserialize(&mut data.as_slice()).is_err());
+            prop_assert!(parse_key_record(&data).is_err());
+            prop_assert!(KeyRecord::upgrade_layout(&data).is_err());
+        }
+        
+        #[test]
+        fn random_record_bodies_never_panic(
+            version in proptest::option::of(0..=KeyRecord::CURRENT_VERSION + 1),
+            body in vec(any::<u8>(), 0..2 * ACCOUNT_SIZE),
+        ) {
+            let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+            data.extend(version);
+            data.extend_from_slice(&body);
+            
+            let _ = KeyRecord::try_deserialize(&mut data.as_slice());
+            if let Ok(record) = parse_key_record(&data) {
+                prop_assert_eq!(record.version, KeyRecord::CURRENT_VERSION);
+            }
+            if let Ok((version, upgraded)) = KeyRecord::upgrade_layout(&data) {
+                prop_assert!(version <= KeyRecord::CURRENT_VERSION);
+                prop_assert!(upgraded.len() >= KeyRecord::LEN);
+            }
+        }
+        
+        #[test]
+        fn other_accounts_are_not_records(Record(record) in record()) {
+            let mut data = account_data(&record);
+            data[..8].copy_from_slice(&Config::DISCRIMINATOR);
+            prop_assert!(KeyRecord::try_deserialize(&mut data.as_slice()).is_err());
+            prop_assert!(parse_key_record(&data).is_err());
+            prop_assert!(KeyRecord::upgrade_layout(&data).is_err());
+        }
+        
+        #[test]
+        fn check_public_key_never_panics(key_type in key_type(), key in vec(any::<u8>(), 0..=MAX_KEY_LEN)) {
+            let _ = check_public_key(key_type, &key);
+        }
+        
+        #[test]
+        fn repeated_bytes_are_weak(key_type in key_type(), byte in any::<u8>()) {
+            prop_assert!(check_public_key(key_type, &vec![byte; key_type.key_len()]).is_err());
+        }
+        
+        #[test]
+        fn distinct_bytes_pass(key_type in key_type(), bytes in Just((0..=255u8).collect::<Vec<_>>()).prop_shuffle()) {
+            prop_assert!(check_public_key(key_type, &bytes[..key_type.key_len()]).is_ok());
+        }
+    }
+}
@@ -0,0 +1,59 @@
+// An owner who enables an access log gets a record of who verified their
+// keys, and when: verify_key calls that pass the log with a signing verifier
+// append to it, and get_access_log reads it back, most recent first.
+
+mod common;
+
+use anchor_lang::AnchorDeserialize;
+use key_registry::AccessLog;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn verifiers_are_logged_most_recent_first() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let enable = common::enable_access_log(&owner.pubkey());
+    common::send(&mut ctx, &[register, enable], &[&owner]).await.unwrap();
+    let (data, _) = common::simulate(&mut ctx, common::get_access_log(&owner.pubkey()), &[]).await;
+    assert!(Vec::<(Pubkey, i64)>::try_from_slice(&data).unwrap().is_empty());
+    
+    let verifiers = [Keypair::new(), Keypair::new()];
+    for verifier in &verifiers {
+        let verify = common::verify_key_logged(&owner.pubkey(), "", &key, &verifier.pubkey());
+        common::send(&mut ctx, &[verify], &[verifier]).await.unwrap();
+    }
+    // a verify without the log, or without a verifier, isn't recorded
+    let unlogged = common::verify_key(&owner.pubkey(), "", &key, Some(&verifiers[0].pubkey()));
+    common::send(&mut ctx, &[unlogged], &[&verifiers[0]]).await.unwrap();
+    
+    let at = if cfg!(feature = "clock") { common::now(&mut ctx).await } else { 0 };
+    let (data, _) = common::simulate(&mut ctx, common::get_access_log(&owner.pubkey()), &[]).await;
+    let entries = Vec::<(Pubkey, i64)>::try_from_slice(&data).unwrap();
+    assert_eq!(entries, [(verifiers[1].pubkey(), at), (verifiers[0].pubkey(), at)]);
+}
+
+#[tokio::test]
+async fn log_keeps_the_last_entries() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(2);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let enable = common::enable_access_log(&owner.pubkey());
+    common::send(&mut ctx, &[register, enable], &[&owner]).await.unwrap();
+    
+    let verifiers: Vec<Keypair> = (0..=AccessLog::LOG_LEN).map(|_| Keypair::new()).collect();
+    for verifier in &verifiers {
+        let verify = common::verify_key_logged(&owner.pubkey(), "", &key, &verifier.pubkey());
+        common::send(&mut ctx, &[verify], &[verifier]).await.unwrap();
+    }
+    
+    // the first verifier has been overwritten
+    let (data, _) = common::simulate(&mut ctx, common::get_access_log(&owner.pubkey()), &[]).await;
+    let entries = Vec::<(Pubkey, i64)>::try_from_slice(&data).unwrap();
+    let logged: Vec<Pubkey> = entries.into_iter().map(|(verifier, _)| verifier).collect();
+    let expected: Vec<Pubkey> = verifiers[1..].iter().rev().map(|verifier| verifier.pubkey()).collect();
+    assert_eq!(logged, expected);
+}
@@ -0,0 +1,59 @@
+// The config admin changes hands in two steps: the admin proposes a nominee,
+// and only that nominee's accept_admin installs it.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn nominee_accepts_the_handoff() {
+    let mut ctx = common::start(common::program_test()).await;
+    let old_admin = ctx.payer.pubkey();
+    let nominee = Keypair::new();
+    let stranger = Keypair::new();
+    let propose = common::propose_admin(&old_admin, nominee.pubkey());
+    common::send(&mut ctx, &[propose], &[]).await.unwrap();
+    assert_eq!(common::fetch_config(&mut ctx).await.admin, old_admin);
+    
+    let accept = common::accept_admin(&stranger.pubkey());
+    let err = common::send(&mut ctx, &[accept], &[&stranger]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    
+    let accept = common::accept_admin(&nominee.pubkey());
+    common::send(&mut ctx, &[accept], &[&nominee]).await.unwrap();
+    let config = common::fetch_config(&mut ctx).await;
+    assert_eq!(config.admin, nominee.pubkey());
+    assert_eq!(config.pending_admin, Pubkey::default());
+    
+    // the old admin has lost its rights
+    let set_fee = common::set_fee(&ctx, 1, old_admin);
+    let err = common::send(&mut ctx, &[set_fee], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+}
+
+#[tokio::test]
+async fn only_the_admin_proposes_or_cancels() {
+    let mut ctx = common::start(common::program_test()).await;
+    let admin = ctx.payer.pubkey();
+    let nominee = Keypair::new();
+    
+    let propose = common::propose_admin(&nominee.pubkey(), nominee.pubkey());
+    let err = common::send(&mut ctx, &[propose], &[&nominee]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    
+    let propose = common::propose_admin(&admin, nominee.pubkey());
+    common::send(&mut ctx, &[propose], &[]).await.unwrap();
+    let cancel = common::cancel_admin(&nominee.pubkey());
+    let err = common::send(&mut ctx, &[cancel], &[&nominee]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    
+    // once cancelled, the nomination can't be accepted
+    let cancel = common::cancel_admin(&admin);
+    common::send(&mut ctx, &[cancel], &[]).await.unwrap();
+    let accept = common::accept_admin(&nominee.pubkey());
+    let err = common::send(&mut ctx, &[accept], &[&nominee]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    assert_eq!(common::fetch_config(&mut ctx).await.admin, admin);
+}
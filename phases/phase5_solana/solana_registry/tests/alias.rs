@@ -0,0 +1,56 @@
+// set_alias points a record at another owner's (say a shared team key), and
+// verify_key then checks against the target's key. Aliases are one hop.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn alias_verifies_against_the_target_key() {
+    let mut ctx = common::start(common::program_test()).await;
+    let team = common::funded(&mut ctx, 1_000_000_000).await;
+    let member = common::funded(&mut ctx, 1_000_000_000).await;
+    let (team_key, member_key) = (common::test_key(1), common::test_key(2));
+    let team_record = common::key_record(&team.pubkey(), "");
+    let register_team = common::register_key(&ctx, &team.pubkey(), "", &team_key);
+    let register_member = common::register_key(&ctx, &member.pubkey(), "", &member_key);
+    let alias = common::set_alias(&member.pubkey(), "", Some(team_record));
+    common::send(&mut ctx, &[register_team, register_member, alias], &[&team, &member]).await.unwrap();
+    
+    let verify = |candidate: &[u8]| common::verify_key_via(&member.pubkey(), "", candidate, Some(team_record), None);
+    assert_eq!(common::simulate(&mut ctx, verify(&team_key), &[]).await.0, [1]);
+    assert_eq!(common::simulate(&mut ctx, verify(&member_key), &[]).await.0, [0]);
+    
+    // without the target the alias can't be resolved
+    let unresolved = common::verify_key(&member.pubkey(), "", &team_key, None);
+    let err = common::send(&mut ctx, &[unresolved], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidRecord.into()));
+    
+    // removing the alias brings the member's own key back
+    let unalias = common::set_alias(&member.pubkey(), "", None);
+    common::send(&mut ctx, &[unalias], &[&member]).await.unwrap();
+    let verify = common::verify_key(&member.pubkey(), "", &member_key, None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+}
+
+#[tokio::test]
+async fn rejects_a_two_level_chain() {
+    let mut ctx = common::start(common::program_test()).await;
+    let team = common::funded(&mut ctx, 1_000_000_000).await;
+    let member = common::funded(&mut ctx, 1_000_000_000).await;
+    let guest = common::funded(&mut ctx, 1_000_000_000).await;
+    let register_team = common::register_key(&ctx, &team.pubkey(), "", &common::test_key(3));
+    let register_member = common::register_key(&ctx, &member.pubkey(), "", &common::test_key(4));
+    let register_guest = common::register_key(&ctx, &guest.pubkey(), "", &common::test_key(5));
+    let alias = common::set_alias(&member.pubkey(), "", Some(common::key_record(&team.pubkey(), "")));
+    common::send(&mut ctx, &[register_team, register_member, register_guest, alias], &[&team, &member, &guest])
+        .await
+        .unwrap();
+    
+    // guest -> member -> team
+    let chained = common::set_alias(&guest.pubkey(), "", Some(common::key_record(&member.pubkey(), "")));
+    let err = common::send(&mut ctx, &[chained], &[&guest]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::AliasChainTooDeep.into()));
+    assert_eq!(common::fetch_record(&mut ctx, common::key_record(&guest.pubkey(), "")).await.alias_of, None);
+}
@@ -0,0 +1,54 @@
+// assert_not_registered and account_exists, before and after a
+// registration.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::Signer;
+use solana_sdk::system_instruction;
+
+#[tokio::test]
+async fn unregistered_wallet_passes() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    
+    let assert = common::assert_not_registered(&owner.pubkey(), "");
+    common::send(&mut ctx, &[assert], &[]).await.unwrap();
+    let (exists, _) = common::simulate(&mut ctx, common::account_exists(&owner.pubkey(), ""), &[]).await;
+    assert_eq!(exists, [0]);
+}
+
+#[tokio::test]
+async fn registered_wallet_fails() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(1));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let assert = common::assert_not_registered(&owner.pubkey(), "");
+    let err = common::send(&mut ctx, &[assert], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::AlreadyRegistered.into()));
+    let (exists, _) = common::simulate(&mut ctx, common::account_exists(&owner.pubkey(), ""), &[]).await;
+    assert_eq!(exists, [1]);
+    
+    // other labels are still unregistered
+    let assert = common::assert_not_registered(&owner.pubkey(), "work");
+    common::send(&mut ctx, &[assert], &[]).await.unwrap();
+}
+
+#[tokio::test]
+async fn lamports_alone_are_not_a_registration() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let address = common::key_record(&owner.pubkey(), "");
+    let payer = ctx.payer.pubkey();
+    common::send(&mut ctx, &[system_instruction::transfer(&payer, &address, 1_000_000)], &[])
+        .await
+        .unwrap();
+    
+    let assert = common::assert_not_registered(&owner.pubkey(), "");
+    common::send(&mut ctx, &[assert], &[]).await.unwrap();
+    // and registration still succeeds over them
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(2));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+}
@@ -0,0 +1,56 @@
+// invoke_with_attestation: the target program sees the caller's key record
+// as a signer, and org records are turned away rather than failing the CPI.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_program_test::processor;
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::entrypoint::ProgramResult;
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+// Accepts only a call whose first account is a registry-owned signer.
+fn target(_program_id: &Pubkey, accounts: &[AccountInfo], _data: &[u8]) -> ProgramResult {
+    match accounts.first() {
+        Some(record) if record.is_signer && *record.owner == key_registry::ID => Ok(()),
+        _ => Err(ProgramError::MissingRequiredSignature),
+    }
+}
+
+fn program_test(target_id: Pubkey) -> solana_program_test::ProgramTest {
+    let mut test = common::program_test();
+    test.add_program("attestation_target", target_id, processor!(target));
+    test
+}
+
+#[tokio::test]
+async fn the_record_signs_into_the_target() {
+    let target_id = Pubkey::new_unique();
+    let mut ctx = common::start(program_test(target_id)).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(1));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let record = common::key_record(&owner.pubkey(), "");
+    let attest = common::invoke_with_attestation(&owner.pubkey(), record, target_id);
+    common::send(&mut ctx, &[attest], &[&owner]).await.unwrap();
+}
+
+#[tokio::test]
+async fn org_records_cannot_attest() {
+    let target_id = Pubkey::new_unique();
+    let mut ctx = common::start(program_test(target_id)).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let org_id = [7u8; 32];
+    // the context payer is the org admin
+    let create = common::create_org(&ctx.payer.pubkey(), org_id);
+    let register = common::register_key_in_org(&ctx, &owner.pubkey(), &ctx.payer.pubkey(), org_id, &common::test_key(2));
+    common::send(&mut ctx, &[create, register], &[&owner]).await.unwrap();
+    
+    let record = common::org_key_record(org_id, &owner.pubkey());
+    let attest = common::invoke_with_attestation(&owner.pubkey(), record, target_id);
+    let err = common::send(&mut ctx, &[attest], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::OrgRecordCannotAttest.into()));
+}
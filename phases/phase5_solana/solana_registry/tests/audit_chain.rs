@@ -0,0 +1,46 @@
+// The audit chain: every mutation folds `op || audit_state()` into
+// `audit_head`, so a client replaying the operations reaches the stored head.
+
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use key_registry::{AuditOp, KeyRecord, KeyType};
+use solana_sdk::keccak::hashv;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+// `KeyRecord::audit_state` of an unexpired, live X25519 record.
+fn state(owner: &Pubkey, key: &[u8]) -> Vec<u8> {
+    let mut state = owner.to_bytes().to_vec();
+    state.push(KeyType::X25519 as u8);
+    state.extend_from_slice(key);
+    state.extend_from_slice(&[0, 0]); // revoked, frozen
+    state.extend_from_slice(&0i64.to_le_bytes());
+    state.extend_from_slice(&0u64.to_le_bytes());
+    state
+}
+
+#[tokio::test]
+async fn head_matches_a_replay_of_the_operations() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [common::test_key(1), common::test_key(2), common::test_key(3)];
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &keys[0]);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    for pair in keys.windows(2) {
+        let update = common::update_key(&owner.pubkey(), "", &pair[0], &pair[1], 0);
+        common::send(&mut ctx, &[update], &[&owner]).await.unwrap();
+    }
+    
+    let mut head = [0u8; 32];
+    let ops = [AuditOp::Register, AuditOp::Update, AuditOp::Update];
+    for (op, key) in ops.iter().zip(&keys) {
+        head = hashv(&[&head, &[*op as u8], &state(&owner.pubkey(), key)]).to_bytes();
+    }
+    let account = ctx.banks_client.get_account(common::key_record(&owner.pubkey(), "")).await.unwrap().unwrap();
+    let record = KeyRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(record.audit_state(), state(&owner.pubkey(), &keys[2]));
+    assert_eq!(record.audit_head, head);
+    assert_eq!(record.seq, 3);
+}
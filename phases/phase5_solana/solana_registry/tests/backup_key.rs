@@ -0,0 +1,47 @@
+// verify_key_with_backup accepts the record's backup key as well as the
+// primary, and flags a backup match with `BackupKeyUsed`. Rotating the
+// primary keeps the backup.
+
+mod common;
+
+use key_registry::{BackupKeyUsed, KeyVerified};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn primary_backup_and_neither() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (primary, backup) = (common::test_key(1), common::test_key(2));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &primary);
+    let set_backup = common::set_backup_key(&owner.pubkey(), "", &backup);
+    common::send(&mut ctx, &[register, set_backup], &[&owner]).await.unwrap();
+    
+    let neither = common::test_key(3);
+    for (candidate, matched, backup_used) in [(&primary, true, false), (&backup, true, true), (&neither, false, false)] {
+        let verify = common::verify_key_with_backup(&owner.pubkey(), "", candidate);
+        let logs = common::send_logged(&mut ctx, &[verify], &[]).await;
+        let verified = common::events::<KeyVerified>(&logs);
+        assert_eq!(verified.iter().map(|event| event.matched).collect::<Vec<_>>(), [matched]);
+        assert_eq!(common::events::<BackupKeyUsed>(&logs).len(), backup_used as usize);
+    }
+    
+    // plain verify_key knows only the primary
+    let verify = common::verify_key(&owner.pubkey(), "", &backup, None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [0]);
+}
+
+#[tokio::test]
+async fn rotating_the_primary_keeps_the_backup() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (primary, backup, rotated) = (common::test_key(4), common::test_key(5), common::test_key(6));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &primary);
+    let set_backup = common::set_backup_key(&owner.pubkey(), "", &backup);
+    let update = common::update_key(&owner.pubkey(), "", &primary, &rotated, 0);
+    common::send(&mut ctx, &[register, set_backup, update], &[&owner]).await.unwrap();
+    
+    for (candidate, matched) in [(&rotated, [1]), (&backup, [1]), (&primary, [0])] {
+        let verify = common::verify_key_with_backup(&owner.pubkey(), "", candidate);
+        assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, matched);
+    }
+}
@@ -0,0 +1,62 @@
+// register_keys_batch with `continue_on_error` reports a status per entry
+// and still applies the valid ones; without it one bad entry fails the lot.
+
+mod common;
+
+use anchor_lang::AnchorDeserialize;
+use key_registry::{BatchKeyEntry, KeyRegistryError, KeyType, BATCH_APPLIED, BATCH_FAILED};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+fn entry(owner: Pubkey, seed: u8) -> BatchKeyEntry {
+    BatchKeyEntry {
+        owner,
+        key_type: KeyType::X25519,
+        public_key: common::test_key(seed),
+        usage_flags: 0,
+    }
+}
+
+#[tokio::test]
+async fn valid_entries_apply_around_a_bad_one() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owners: Vec<Pubkey> = (0..3).map(|_| Keypair::new().pubkey()).collect();
+    // the middle owner is already registered
+    let taken = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &taken.pubkey(), "", &common::test_key(10));
+    common::send(&mut ctx, &[register], &[&taken]).await.unwrap();
+    let entries = vec![entry(owners[0], 1), entry(taken.pubkey(), 2), entry(owners[2], 3)];
+    
+    let batch = common::register_keys_batch(&ctx, entries, true);
+    let (data, _) = common::simulate(&mut ctx, batch.clone(), &[]).await;
+    let already_registered = (u32::from(KeyRegistryError::AlreadyRegistered) - anchor_lang::error::ERROR_CODE_OFFSET) as u8;
+    assert_eq!(
+        Vec::<u8>::try_from_slice(&data).unwrap(),
+        [BATCH_APPLIED, BATCH_FAILED + already_registered, BATCH_APPLIED]
+    );
+    
+    let logs = common::send_logged(&mut ctx, &[batch], &[]).await;
+    assert!(logs.iter().any(|log| log.contains("Batch-registered 2 of 3 keys")));
+    for (owner, seed) in [(owners[0], 1), (owners[2], 3)] {
+        let record = common::fetch_record(&mut ctx, common::key_record(&owner, "")).await;
+        assert_eq!(record.key_bytes(), common::test_key(seed).as_slice());
+    }
+    // the skipped entry left nothing behind
+    let record = common::fetch_record(&mut ctx, common::key_record(&taken.pubkey(), "")).await;
+    assert_eq!(record.key_bytes(), common::test_key(10).as_slice());
+    assert!(ctx.banks_client.get_account(common::key_index(&common::test_key(2))).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn a_bad_entry_fails_the_batch_by_default() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = Keypair::new().pubkey();
+    let taken = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &taken.pubkey(), "", &common::test_key(10));
+    common::send(&mut ctx, &[register], &[&taken]).await.unwrap();
+    
+    let batch = common::register_keys_batch(&ctx, vec![entry(owner, 1), entry(taken.pubkey(), 2)], false);
+    let err = common::send(&mut ctx, &[batch], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::AlreadyRegistered.into()));
+    assert!(ctx.banks_client.get_account(common::key_record(&owner, "")).await.unwrap().is_none());
+}
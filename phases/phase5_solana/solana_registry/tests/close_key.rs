@@ -0,0 +1,59 @@
+// close_key frees the record PDA: its lamports go back to the owner and the
+// owner can register under the same label again.
+
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use key_registry::{KeyRecord, KeyType};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn close_then_register_again() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (first, second) = (common::test_key(1), common::test_key(2));
+    let address = common::key_record(&owner.pubkey(), "");
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &first);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    let record_lamports = ctx.banks_client.get_balance(address).await.unwrap();
+    let index_lamports = ctx.banks_client.get_balance(common::key_index(&first)).await.unwrap();
+    let before = ctx.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    
+    let close = common::close_key(&owner.pubkey(), "", &first);
+    common::send(&mut ctx, &[close], &[&owner]).await.unwrap();
+    assert!(ctx.banks_client.get_account(address).await.unwrap().is_none());
+    assert!(ctx.banks_client.get_account(common::key_index(&first)).await.unwrap().is_none());
+    let after = ctx.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    assert_eq!(after, before + record_lamports + index_lamports);
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &second);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    let account = ctx.banks_client.get_account(address).await.unwrap().unwrap();
+    let record = KeyRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(record.version, KeyRecord::CURRENT_VERSION);
+    assert_eq!(record.owner, owner.pubkey());
+    assert_eq!(record.registrant, owner.pubkey());
+    assert_eq!(record.key_type, KeyType::X25519 as u8);
+    assert_eq!(record.key_bytes(), second.as_slice());
+    assert!(!record.revoked);
+    // a fresh record: nothing carried over from the closed one
+    assert!(record.previous_keys().is_empty());
+    assert_eq!(record.seq, 1);
+}
+
+#[tokio::test]
+async fn only_the_owner_can_close() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let other = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(3);
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let mut close = common::close_key(&owner.pubkey(), "", &key);
+    close.accounts[0].pubkey = other.pubkey();
+    let err = common::send(&mut ctx, &[close], &[&other]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(key_registry::KeyRegistryError::Unauthorized.into()));
+}
@@ -0,0 +1,40 @@
+// Commit-reveal registration: the key is only registered once the committed
+// hash is opened, and not before `KeyCommitment::MIN_REVEAL_SLOTS` have passed.
+
+mod common;
+
+use key_registry::{KeyCommitment, KeyRegistryError};
+use solana_sdk::hash::hashv;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn commit_then_reveal_registers_the_key() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let salt = [9u8; 32];
+    
+    let commit = common::commit_key(&owner.pubkey(), hashv(&[&key, &salt]).to_bytes());
+    common::send(&mut ctx, &[commit], &[&owner]).await.unwrap();
+    let committed_at = ctx.banks_client.get_root_slot().await.unwrap();
+    
+    // the delay is only enforced with a clock to read the slot from
+    if cfg!(feature = "clock") {
+        let reveal = common::reveal_key(&ctx, &owner.pubkey(), "", &key, salt);
+        let err = common::send(&mut ctx, &[reveal], &[&owner]).await.unwrap_err();
+        assert_eq!(common::error_code(err), Some(KeyRegistryError::RevealTooEarly.into()));
+    }
+    ctx.warp_to_slot(committed_at + KeyCommitment::MIN_REVEAL_SLOTS + 1).unwrap();
+    
+    let wrong_salt = common::reveal_key(&ctx, &owner.pubkey(), "", &key, [8u8; 32]);
+    let err = common::send(&mut ctx, &[wrong_salt], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::CommitmentMismatch.into()));
+    
+    let reveal = common::reveal_key(&ctx, &owner.pubkey(), "", &key, salt);
+    common::send(&mut ctx, &[reveal], &[&owner]).await.unwrap();
+    let verify = common::verify_key(&owner.pubkey(), "", &key, None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+    // the commitment is closed once opened
+    let commitment = ctx.banks_client.get_account(common::key_commitment(&owner.pubkey())).await.unwrap();
+    assert!(commitment.is_none());
+}
@@ -0,0 +1,1464 @@
+// Shared setup for the program-test suites: a local bank running the
+// registry natively (or the built .so, see `bpf_program_test`), with the
+// config initialized, and builders for the instructions the suites send.
+
+#![allow(dead_code)] // each suite uses a different subset
+
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use anchor_lang::{AccountDeserialize, AnchorDeserialize, Discriminator, InstructionData, ToAccountMetas};
+use base64::prelude::{Engine, BASE64_STANDARD};
+use key_registry::{BatchKeyEntry, Config, KeyRecord, KeyType, ID};
+use solana_program_test::{processor, BanksClientError, ProgramTest, ProgramTestBanksClientExt, ProgramTestContext};
+use solana_sdk::account::Account;
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::bpf_loader;
+use solana_sdk::entrypoint::ProgramResult;
+use solana_sdk::instruction::{AccountMeta, Instruction, InstructionError};
+use solana_sdk::program_stubs::{self, SyscallStubs};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::sysvar;
+use solana_sdk::transaction::{Transaction, TransactionError};
+
+pub const REGISTRY: [u8; 8] = [0u8; 8];
+
+// Anchor's entry wants the accounts for 'info, which the processor! shim
+// can't name; each instruction leaks one small Vec, fine for a test run.
+fn entry(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let accounts = Box::leak(Box::new(accounts.to_vec()));
+    key_registry::entry(program_id, accounts, data)
+}
+
+pub fn program_test() -> ProgramTest {
+    ProgramTest::new("key_registry", ID, processor!(entry))
+}
+
+// The registry's SBF build loaded as a deployed program, so compute is
+// metered as on a cluster: `key_registry.so` from SBF_OUT_DIR or BPF_OUT_DIR
+// (set by `cargo test-sbf`), else target/deploy after `cargo build-sbf`.
+// Panics if there is no build.
+pub fn bpf_program_test() -> ProgramTest {
+    let out_dirs = ["SBF_OUT_DIR", "BPF_OUT_DIR"].into_iter().filter_map(std::env::var_os).map(PathBuf::from);
+    let deploy_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("target").join("deploy");
+    let path = out_dirs
+        .chain([deploy_dir])
+        .map(|dir| dir.join("key_registry.so"))
+        .find(|path| path.exists())
+        .expect("no key_registry.so: build it with `cargo build-sbf` first");
+    let data = std::fs::read(path).unwrap();
+    let mut test = ProgramTest::default();
+    test.add_account(
+        ID,
+        Account {
+            lamports: Rent::default().minimum_balance(data.len()),
+            data,
+            owner: bpf_loader::id(),
+            executable: true,
+            rent_epoch: 0,
+        },
+    );
+    test
+}
+
+// Wraps program-test's syscall stubs so events reach the transaction logs
+// as they would on a cluster: natively `sol_log_data` only prints, and the
+// banks don't report the inner instructions `emit_cpi!` puts its event in.
+// Both are logged as "Program data: <base64>", behind program-test's
+// "Program log: " prefix; `events` reads them back.
+struct LogEvents(Box<dyn SyscallStubs>);
+
+impl SyscallStubs for LogEvents {
+    fn sol_log(&self, message: &str) {
+        self.0.sol_log(message)
+    }
+    fn sol_log_compute_units(&self) {
+        self.0.sol_log_compute_units()
+    }
+    fn sol_remaining_compute_units(&self) -> u64 {
+        self.0.sol_remaining_compute_units()
+    }
+    fn sol_invoke_signed(&self, instruction: &Instruction, account_infos: &[AccountInfo], signers_seeds: &[&[&[u8]]]) -> ProgramResult {
+        if instruction.program_id == ID {
+            if let Some(event) = instruction.data.strip_prefix(&anchor_lang::event::EVENT_IX_TAG_LE[..]) {
+                self.sol_log_data(&[event]);
+            }
+        }
+        self.0.sol_invoke_signed(instruction, account_infos, signers_seeds)
+    }
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_clock_sysvar(var_addr)
+    }
+    fn sol_get_epoch_schedule_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_schedule_sysvar(var_addr)
+    }
+    fn sol_get_fees_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_fees_sysvar(var_addr)
+    }
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_rent_sysvar(var_addr)
+    }
+    fn sol_get_epoch_rewards_sysvar(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_epoch_rewards_sysvar(var_addr)
+    }
+    fn sol_get_last_restart_slot(&self, var_addr: *mut u8) -> u64 {
+        self.0.sol_get_last_restart_slot(var_addr)
+    }
+    unsafe fn sol_memcpy(&self, dst: *mut u8, src: *const u8, n: usize) {
+        self.0.sol_memcpy(dst, src, n)
+    }
+    unsafe fn sol_memmove(&self, dst: *mut u8, src: *const u8, n: usize) {
+        self.0.sol_memmove(dst, src, n)
+    }
+    unsafe fn sol_memcmp(&self, s1: *const u8, s2: *const u8, n: usize, result: *mut i32) {
+        self.0.sol_memcmp(s1, s2, n, result)
+    }
+    unsafe fn sol_memset(&self, s: *mut u8, c: u8, n: usize) {
+        self.0.sol_memset(s, c, n)
+    }
+    fn sol_get_return_data(&self) -> Option<(Pubkey, Vec<u8>)> {
+        self.0.sol_get_return_data()
+    }
+    fn sol_set_return_data(&self, data: &[u8]) {
+        self.0.sol_set_return_data(data)
+    }
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        let fields: Vec<String> = fields.iter().map(|field| BASE64_STANDARD.encode(field)).collect();
+        self.0.sol_log(&format!("Program data: {}", fields.join(" ")))
+    }
+    fn sol_get_processed_sibling_instruction(&self, index: usize) -> Option<Instruction> {
+        self.0.sol_get_processed_sibling_instruction(index)
+    }
+    fn sol_get_stack_height(&self) -> u64 {
+        self.0.sol_get_stack_height()
+    }
+}
+
+// Start a bank and initialize the config, with the payer as admin.
+pub async fn start(test: ProgramTest) -> ProgramTestContext {
+    let mut ctx = test.start_with_context().await;
+    // program-test installs its stubs with the first bank; no program runs
+    // before its test's `start` is past the swap
+    static LOG_EVENTS: Once = Once::new();
+    LOG_EVENTS.call_once(|| {
+        struct Swapping;
+        impl SyscallStubs for Swapping {}
+        let stubs = program_stubs::set_syscall_stubs(Box::new(Swapping));
+        program_stubs::set_syscall_stubs(Box::new(LogEvents(stubs)));
+    });
+    let admin = ctx.payer.pubkey();
+    let initialize = Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::InitializeConfig {
+            admin,
+            config: config(),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::InitializeConfig {}.data(),
+    };
+    send(&mut ctx, &[initialize], &[]).await.unwrap();
+    ctx
+}
+
+// Send `instructions` paid for by the context payer, also signed by `signers`.
+pub async fn send(
+    ctx: &mut ProgramTestContext,
+    instructions: &[Instruction],
+    signers: &[&Keypair],
+) -> Result<(), BanksClientError> {
+    let blockhash = ctx.banks_client.get_latest_blockhash().await?;
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(instructions, Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    ctx.banks_client.process_transaction(tx).await
+}
+
+// `send`, returning the transaction's logs; panics if it fails.
+pub async fn send_logged(ctx: &mut ProgramTestContext, instructions: &[Instruction], signers: &[&Keypair]) -> Vec<String> {
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(instructions, Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    // an earlier `send` can return before the bank has released its account
+    // locks, and this runs on the bank directly, so retry until they're free
+    let processed = loop {
+        let processed = ctx.banks_client.process_transaction_with_metadata(tx.clone()).await.unwrap();
+        if processed.result != Err(TransactionError::AccountInUse) {
+            break processed;
+        }
+    };
+    processed.result.unwrap();
+    processed.metadata.expect("transaction metadata").log_messages
+}
+
+// The `T` events in `logs`, in the order they were emitted.
+pub fn events<T: AnchorDeserialize + Discriminator>(logs: &[String]) -> Vec<T> {
+    logs.iter()
+        .filter_map(|line| line.strip_prefix("Program log: Program data: "))
+        .map(|data| BASE64_STANDARD.decode(data).unwrap())
+        .filter_map(|data| data.strip_prefix(&T::DISCRIMINATOR[..]).map(|body| T::deserialize(&mut &body[..]).unwrap()))
+        .collect()
+}
+
+// Simulate `instruction` and return its return data (empty if none) and the
+// compute units it used.
+pub async fn simulate(ctx: &mut ProgramTestContext, instruction: Instruction, signers: &[&Keypair]) -> (Vec<u8>, u64) {
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    let mut all_signers = vec![&ctx.payer];
+    all_signers.extend_from_slice(signers);
+    let tx = Transaction::new_signed_with_payer(&[instruction], Some(&ctx.payer.pubkey()), &all_signers, blockhash);
+    let simulation = ctx.banks_client.simulate_transaction(tx).await.unwrap();
+    if let Some(Err(err)) = simulation.result {
+        panic!("simulation failed: {err:?}");
+    }
+    let details = simulation.simulation_details.expect("simulation details");
+    let data = details.return_data.map(|data| data.data).unwrap_or_default();
+    (data, details.units_consumed)
+}
+
+// The `KeyRegistryError` code a failed transaction reported, if any.
+pub fn error_code(err: BanksClientError) -> Option<u32> {
+    match err.unwrap() {
+        TransactionError::InstructionError(_, InstructionError::Custom(code)) => Some(code),
+        _ => None,
+    }
+}
+
+// A keypair holding `lamports`, funded by the context payer.
+pub async fn funded(ctx: &mut ProgramTestContext, lamports: u64) -> Keypair {
+    let wallet = Keypair::new();
+    let transfer = system_instruction::transfer(&ctx.payer.pubkey(), &wallet.pubkey(), lamports);
+    send(ctx, &[transfer], &[]).await.unwrap();
+    wallet
+}
+
+// A 32-byte X25519 key that passes `check_public_key`, distinct per `seed`.
+// X25519 registrations need no proof of possession.
+pub fn test_key(seed: u8) -> Vec<u8> {
+    (0..32u8).map(|i| i.wrapping_mul(7).wrapping_add(seed)).collect()
+}
+
+pub fn config() -> Pubkey {
+    Pubkey::find_program_address(&[b"config"], &ID).0
+}
+
+pub fn key_record(owner: &Pubkey, label: &str) -> Pubkey {
+    key_record_in(&REGISTRY, owner, label)
+}
+
+pub fn key_record_in(registry_id: &[u8; 8], owner: &Pubkey, label: &str) -> Pubkey {
+    Pubkey::find_program_address(
+        &[b"key_record", key_registry::registry_seed(registry_id), owner.as_ref(), label.as_bytes()],
+        &ID,
+    )
+    .0
+}
+
+pub fn key_index(public_key: &[u8]) -> Pubkey {
+    key_index_in(&REGISTRY, public_key)
+}
+
+pub fn key_index_in(registry_id: &[u8; 8], public_key: &[u8]) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            b"key_index",
+            key_registry::registry_seed(registry_id),
+            key_registry::key_seed(public_key, 0),
+            key_registry::key_seed(public_key, 1),
+        ],
+        &ID,
+    )
+    .0
+}
+
+pub fn settings(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"settings", owner.as_ref()], &ID).0
+}
+
+pub fn access_log(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"access_log", owner.as_ref()], &ID).0
+}
+
+pub fn key_bundle(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"key_bundle", owner.as_ref()], &ID).0
+}
+
+pub fn key_root(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"key_root", owner.as_ref()], &ID).0
+}
+
+pub fn org(org_id: [u8; 32]) -> Pubkey {
+    Pubkey::find_program_address(&[b"org", org_id.as_ref()], &ID).0
+}
+
+pub fn org_key_record(org_id: [u8; 32], owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"key_record", org_id.as_ref(), owner.as_ref()], &ID).0
+}
+
+pub fn key_commitment(owner: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[b"commitment", owner.as_ref()], &ID).0
+}
+
+fn event_authority() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &ID).0
+}
+
+// `set_fee`, signed by the admin (the context payer).
+pub fn set_fee(ctx: &ProgramTestContext, fee_lamports: u64, treasury: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetFee {
+            admin: ctx.payer.pubkey(),
+            config: config(),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetFee { fee_lamports, treasury }.data(),
+    }
+}
+
+// `set_stake`, signed by the admin (the context payer).
+pub fn set_stake(ctx: &ProgramTestContext, stake_lamports: u64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetFee {
+            admin: ctx.payer.pubkey(),
+            config: config(),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetStake { stake_lamports }.data(),
+    }
+}
+
+pub fn propose_admin(admin: &Pubkey, new_admin: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::ProposeAdmin { admin: *admin, config: config() }.to_account_metas(None),
+        data: key_registry::instruction::ProposeAdmin { new_admin }.data(),
+    }
+}
+
+pub fn accept_admin(pending_admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::AcceptAdmin {
+            pending_admin: *pending_admin,
+            config: config(),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::AcceptAdmin {}.data(),
+    }
+}
+
+pub fn cancel_admin(admin: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::ProposeAdmin { admin: *admin, config: config() }.to_account_metas(None),
+        data: key_registry::instruction::CancelAdmin {}.data(),
+    }
+}
+
+// `register_keys_batch` of `entries`, signed by the admin (the context
+// payer), with each entry's unlabelled record and key index appended.
+pub fn register_keys_batch(ctx: &ProgramTestContext, entries: Vec<BatchKeyEntry>, continue_on_error: bool) -> Instruction {
+    let mut accounts = key_registry::accounts::RegisterKeysBatch {
+        admin: ctx.payer.pubkey(),
+        config: config(),
+        system_program: solana_sdk::system_program::ID,
+        event_authority: event_authority(),
+        program: ID,
+    }
+    .to_account_metas(None);
+    for entry in &entries {
+        accounts.push(AccountMeta::new(key_record(&entry.owner, ""), false));
+        accounts.push(AccountMeta::new(key_index(&entry.public_key), false));
+    }
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: key_registry::instruction::RegisterKeysBatch { entries, continue_on_error }.data(),
+    }
+}
+
+// Register the X25519 `public_key` for `owner` under `label`, with no
+// expiry, handle or metadata. The treasury is the admin, i.e. the payer.
+pub fn register_key(ctx: &ProgramTestContext, owner: &Pubkey, label: &str, public_key: &[u8]) -> Instruction {
+    register_expiring_key(ctx, owner, label, public_key, 0)
+}
+
+// `register_key` expiring at Unix time `expires_at`.
+pub fn register_expiring_key(
+    ctx: &ProgramTestContext,
+    owner: &Pubkey,
+    label: &str,
+    public_key: &[u8],
+    expires_at: i64,
+) -> Instruction {
+    register_typed_key(ctx, &REGISTRY, owner, label, KeyType::X25519, public_key, [0u8; 64], expires_at, KeyRecord::HISTORY_LEN as u8)
+}
+
+// `register_key` of an Ed25519 `key`, proving possession with `signature`;
+// send it after `ed25519_verify` of that signature.
+pub fn register_ed25519_key(ctx: &ProgramTestContext, owner: &Pubkey, label: &str, key: &Pubkey, signature: [u8; 64]) -> Instruction {
+    register_typed_key(ctx, &REGISTRY, owner, label, KeyType::Ed25519, key.as_ref(), signature, 0, KeyRecord::HISTORY_LEN as u8)
+}
+
+// `register_key` of a compressed secp256k1 `public_key`, which needs no
+// proof of possession.
+pub fn register_secp256k1_key(ctx: &ProgramTestContext, owner: &Pubkey, label: &str, public_key: &[u8; 33]) -> Instruction {
+    register_typed_key(ctx, &REGISTRY, owner, label, KeyType::Secp256k1, public_key, [0u8; 64], 0, KeyRecord::HISTORY_LEN as u8)
+}
+
+// `register_key` keeping `history_capacity` previous keys.
+pub fn register_key_with_history(ctx: &ProgramTestContext, owner: &Pubkey, label: &str, public_key: &[u8], history_capacity: u8) -> Instruction {
+    register_typed_key(ctx, &REGISTRY, owner, label, KeyType::X25519, public_key, [0u8; 64], 0, history_capacity)
+}
+
+// `register_key` of the X25519 `public_key` in the registry `registry_id`.
+pub fn register_key_in(ctx: &ProgramTestContext, registry_id: &[u8; 8], owner: &Pubkey, label: &str, public_key: &[u8]) -> Instruction {
+    register_typed_key(ctx, registry_id, owner, label, KeyType::X25519, public_key, [0u8; 64], 0, KeyRecord::HISTORY_LEN as u8)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn register_typed_key(
+    ctx: &ProgramTestContext,
+    registry_id: &[u8; 8],
+    owner: &Pubkey,
+    label: &str,
+    key_type: KeyType,
+    public_key: &[u8],
+    signature: [u8; 64],
+    expires_at: i64,
+    history_capacity: u8,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::RegisterKey {
+            owner: *owner,
+            key_record: key_record_in(registry_id, owner, label),
+            key_index: key_index_in(registry_id, public_key),
+            handle_index: None,
+            config: config(),
+            treasury: ctx.payer.pubkey(),
+            instructions: sysvar::instructions::ID,
+            system_program: solana_sdk::system_program::ID,
+            event_authority: event_authority(),
+            program: ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::RegisterKey {
+            registry_id: *registry_id,
+            label: label.to_string(),
+            key_type,
+            public_key: public_key.to_vec(),
+            signature,
+            expires_at,
+            handle: [0u8; 32],
+            usage_flags: 0,
+            email_hash: [0u8; 32],
+            uri: String::new(),
+            history_capacity,
+        }
+        .data(),
+    }
+}
+
+// `key`'s signature over `message`.
+pub fn sign(key: &Keypair, message: &[u8]) -> [u8; 64] {
+    key.sign_message(message).into()
+}
+
+// An Ed25519 native program instruction checking `signature` by `key` over
+// `message`, with everything inline, the layout the registry accepts.
+pub fn ed25519_verify(key: &Pubkey, message: &[u8], signature: [u8; 64]) -> Instruction {
+    const DATA_AT: u16 = 2 + 14;
+    const THIS_IX: u16 = u16::MAX;
+    let (key_at, sig_at, msg_at) = (DATA_AT, DATA_AT + 32, DATA_AT + 32 + 64);
+    let offsets = [sig_at, THIS_IX, key_at, THIS_IX, msg_at, message.len() as u16, THIS_IX];
+    let mut data = vec![1, 0];
+    data.extend(offsets.iter().flat_map(|offset| offset.to_le_bytes()));
+    data.extend_from_slice(key.as_ref());
+    data.extend_from_slice(&signature);
+    data.extend_from_slice(message);
+    Instruction {
+        program_id: solana_sdk::ed25519_program::ID,
+        accounts: vec![],
+        data,
+    }
+}
+
+// `commit_key` of `commitment`, the SHA-256 of `public_key || salt`.
+pub fn commit_key(owner: &Pubkey, commitment: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::CommitKey {
+            owner: *owner,
+            key_commitment: key_commitment(owner),
+            config: config(),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::CommitKey { commitment }.data(),
+    }
+}
+
+// `reveal_key` opening the owner's commitment to the X25519 `public_key`.
+pub fn reveal_key(ctx: &ProgramTestContext, owner: &Pubkey, label: &str, public_key: &[u8], salt: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::RevealKey {
+            owner: *owner,
+            key_commitment: key_commitment(owner),
+            key_record: key_record(owner, label),
+            key_index: key_index(public_key),
+            config: config(),
+            treasury: ctx.payer.pubkey(),
+            instructions: sysvar::instructions::ID,
+            system_program: solana_sdk::system_program::ID,
+            event_authority: event_authority(),
+            program: ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::RevealKey {
+            registry_id: REGISTRY,
+            label: label.to_string(),
+            key_type: KeyType::X25519,
+            public_key: public_key.to_vec(),
+            salt,
+            signature: [0u8; 64],
+        }
+        .data(),
+    }
+}
+
+// `update_key` from `old_key` to `new_key`, signed by the owner.
+pub fn update_key(owner: &Pubkey, label: &str, old_key: &[u8], new_key: &[u8], expires_at: i64) -> Instruction {
+    update_key_as(owner, owner, label, old_key, new_key, expires_at)
+}
+
+// `update_key` of the record `registrant` registered, signed by its current
+// `owner` (after a transfer).
+pub fn update_key_as(
+    owner: &Pubkey,
+    registrant: &Pubkey,
+    label: &str,
+    old_key: &[u8],
+    new_key: &[u8],
+    expires_at: i64,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::UpdateKey {
+            authority: *owner,
+            key_record: key_record(registrant, label),
+            old_key_index: key_index(old_key),
+            new_key_index: key_index(new_key),
+            config: config(),
+            settings: settings(owner),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::UpdateKey {
+            label: label.to_string(),
+            new_public_key: new_key.to_vec(),
+            expires_at,
+        }
+        .data(),
+    }
+}
+
+pub fn grow_record(owner: &Pubkey, label: &str, new_len: u64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::GrowRecord {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::GrowRecord { new_len }.data(),
+    }
+}
+
+// `transfer_ownership` of the record `registrant` registered under `label`,
+// holding `public_key`, signed by its current `owner`.
+pub fn transfer_ownership(owner: &Pubkey, registrant: &Pubkey, label: &str, public_key: &[u8], new_owner: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::TransferOwnership {
+            owner: *owner,
+            key_record: key_record(registrant, label),
+            key_index: key_index(public_key),
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::TransferOwnership { new_owner }.data(),
+    }
+}
+
+// `migrate` of the account at `record`, paid for by `payer`.
+pub fn migrate(payer: &Pubkey, record: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::Migrate {
+            payer: *payer,
+            key_record: record,
+            system_program: solana_sdk::system_program::ID,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::Migrate {}.data(),
+    }
+}
+
+pub fn create_org(admin: &Pubkey, org_id: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::CreateOrg {
+            admin: *admin,
+            org: org(org_id),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::CreateOrg { org_id }.data(),
+    }
+}
+
+// `register_key_in_org` of the X25519 `public_key`, approved by the org
+// `admin`.
+pub fn register_key_in_org(
+    ctx: &ProgramTestContext,
+    owner: &Pubkey,
+    admin: &Pubkey,
+    org_id: [u8; 32],
+    public_key: &[u8],
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::RegisterKeyInOrg {
+            owner: *owner,
+            admin: *admin,
+            org: org(org_id),
+            key_record: org_key_record(org_id, owner),
+            key_index: key_index(public_key),
+            config: config(),
+            treasury: ctx.payer.pubkey(),
+            instructions: sysvar::instructions::ID,
+            system_program: solana_sdk::system_program::ID,
+            event_authority: event_authority(),
+            program: ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::RegisterKeyInOrg {
+            org_id,
+            key_type: KeyType::X25519,
+            public_key: public_key.to_vec(),
+            signature: [0u8; 64],
+        }
+        .data(),
+    }
+}
+
+// `org_revoke` of `member`'s key in org `org_id`, signed by `admin`.
+pub fn org_revoke(admin: &Pubkey, org_id: [u8; 32], member: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::OrgRevoke {
+            admin: *admin,
+            org: org(org_id),
+            key_record: org_key_record(org_id, member),
+            config: Some(config()),
+            event_authority: event_authority(),
+            program: ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::OrgRevoke { org_id, member: *member }.data(),
+    }
+}
+
+pub fn verify_org_key(org_id: [u8; 32], owner: &Pubkey, candidate: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyOrgKey {
+            key_record: org_key_record(org_id, owner),
+            verifier: None,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifyOrgKey {
+            _org_id: org_id,
+            _owner: *owner,
+            public_key_to_verify: candidate.to_vec(),
+        }
+        .data(),
+    }
+}
+
+// `upsert_key` of the X25519 `public_key`; pass the record's current key as
+// `old_key` when rotating an existing record to a different one.
+pub fn upsert_key(ctx: &ProgramTestContext, owner: &Pubkey, label: &str, public_key: &[u8], old_key: Option<&[u8]>) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::UpsertKey {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            old_key_index: old_key.map(key_index),
+            new_key_index: key_index(public_key),
+            config: config(),
+            settings: settings(owner),
+            treasury: ctx.payer.pubkey(),
+            instructions: sysvar::instructions::ID,
+            system_program: solana_sdk::system_program::ID,
+            event_authority: event_authority(),
+            program: ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::UpsertKey {
+            registry_id: REGISTRY,
+            label: label.to_string(),
+            key_type: KeyType::X25519,
+            public_key: public_key.to_vec(),
+            signature: [0u8; 64],
+        }
+        .data(),
+    }
+}
+
+pub fn propose_update(owner: &Pubkey, label: &str, new_key: &[u8], activate_after: i64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::ProposeUpdate {
+            authority: *owner,
+            key_record: key_record(owner, label),
+            config: config(),
+            settings: settings(owner),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::ProposeUpdate {
+            new_key: new_key.to_vec(),
+            activate_after,
+        }
+        .data(),
+    }
+}
+
+// `finalize_update` of the pending key `new_key`, replacing `old_key`.
+pub fn finalize_update(owner: &Pubkey, label: &str, old_key: &[u8], new_key: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::FinalizeUpdate {
+            authority: *owner,
+            key_record: key_record(owner, label),
+            old_key_index: key_index(old_key),
+            new_key_index: key_index(new_key),
+            config: config(),
+            settings: settings(owner),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::FinalizeUpdate {}.data(),
+    }
+}
+
+pub fn cancel_update(owner: &Pubkey, label: &str) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::CancelUpdate {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::CancelUpdate {}.data(),
+    }
+}
+
+// `swap_keys` between `owner`'s and `other`'s unlabelled records, holding
+// `key` and `other_key`.
+pub fn swap_keys(owner: &Pubkey, other: &Pubkey, key: &[u8], other_key: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SwapKeys {
+            owner: *owner,
+            other: *other,
+            key_record: key_record(owner, ""),
+            other_record: key_record(other, ""),
+            key_index: key_index(key),
+            other_key_index: key_index(other_key),
+            config: config(),
+            settings: settings(owner),
+            other_settings: settings(other),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SwapKeys { other_owner: *other }.data(),
+    }
+}
+
+// `set_alias` of the owner's record to the record at `target`, or removing
+// the alias with `None`.
+pub fn set_alias(owner: &Pubkey, label: &str, target: Option<Pubkey>) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetAlias {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            target_record: target,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetAlias { target }.data(),
+    }
+}
+
+pub fn set_backup_key(owner: &Pubkey, label: &str, key: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetBackupKey {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetBackupKey { key: key.to_vec() }.data(),
+    }
+}
+
+pub fn set_update_cooldown(owner: &Pubkey, update_cooldown: i64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetUpdateCooldown {
+            owner: *owner,
+            settings: settings(owner),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetUpdateCooldown { update_cooldown }.data(),
+    }
+}
+
+pub fn set_delegate(owner: &Pubkey, delegate: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetDelegate {
+            owner: *owner,
+            settings: settings(owner),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetDelegate { delegate }.data(),
+    }
+}
+
+// `set_flags` changing only the usage bits.
+pub fn set_usage(owner: &Pubkey, label: &str, usage: u8) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetFlags {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+            settings: settings(owner),
+            event_authority: event_authority(),
+            program: ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetFlags {
+            revoked: None,
+            frozen: None,
+            usage: Some(usage),
+        }
+        .data(),
+    }
+}
+
+pub fn set_key_policy(owner: &Pubkey, max_lifetime: i64, allowed_usage: u8) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetKeyPolicy {
+            owner: *owner,
+            settings: settings(owner),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetKeyPolicy {
+            max_lifetime,
+            allowed_usage,
+        }
+        .data(),
+    }
+}
+
+pub fn revoke_key(owner: &Pubkey, label: &str) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::RevokeKey {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+            event_authority: event_authority(),
+            program: ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::RevokeKey {}.data(),
+    }
+}
+
+// `reactivate_key` of the revoked record holding `old_key` with `new_key`
+// (which may be the same), proven by `signature` at the record's `nonce`.
+pub fn reactivate_key(owner: &Pubkey, label: &str, old_key: &[u8], new_key: &[u8], signature: [u8; 64], nonce: u64) -> Instruction {
+    let rotated = old_key != new_key;
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::ReactivateKey {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            old_key_index: rotated.then(|| key_index(old_key)),
+            new_key_index: rotated.then(|| key_index(new_key)),
+            config: config(),
+            settings: settings(owner),
+            instructions: sysvar::instructions::ID,
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::ReactivateKey {
+            new_public_key: new_key.to_vec(),
+            signature,
+            nonce,
+        }
+        .data(),
+    }
+}
+
+// `freeze_key` of the owner's record, by `authority` (owner or admin).
+pub fn freeze_key(authority: &Pubkey, owner: &Pubkey, label: &str) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::FreezeKey {
+            authority: *authority,
+            key_record: key_record(owner, label),
+            config: config(),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::FreezeKey {}.data(),
+    }
+}
+
+pub fn thaw_key(authority: &Pubkey, owner: &Pubkey, label: &str) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::FreezeKey {
+            authority: *authority,
+            key_record: key_record(owner, label),
+            config: config(),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::ThawKey {}.data(),
+    }
+}
+
+pub fn close_key(owner: &Pubkey, label: &str, public_key: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::CloseKey {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            key_index: key_index(public_key),
+            handle_index: None,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::CloseKey {}.data(),
+    }
+}
+
+// `sweep_expired` of `owners`' unlabelled records holding `keys`, signed by
+// the admin (the context payer).
+pub fn sweep_expired(ctx: &ProgramTestContext, owners: &[Pubkey], keys: &[Vec<u8>], continue_on_error: bool) -> Instruction {
+    let mut accounts = key_registry::accounts::SweepExpired {
+        admin: ctx.payer.pubkey(),
+        config: config(),
+    }
+    .to_account_metas(None);
+    for (owner, key) in owners.iter().zip(keys) {
+        accounts.push(AccountMeta::new(key_record(owner, ""), false));
+        accounts.push(AccountMeta::new(key_index(key), false));
+        accounts.push(AccountMeta::new(*owner, false));
+    }
+    Instruction {
+        program_id: ID,
+        accounts,
+        data: key_registry::instruction::SweepExpired { continue_on_error }.data(),
+    }
+}
+
+// `verify_key` of `candidate` against the record, signed by `verifier` if
+// given.
+pub fn verify_key(owner: &Pubkey, label: &str, candidate: &[u8], verifier: Option<&Pubkey>) -> Instruction {
+    verify_key_via(owner, label, candidate, None, verifier)
+}
+
+// `verify_key` of `candidate` against the record in the registry `registry_id`.
+pub fn verify_key_in(registry_id: &[u8; 8], owner: &Pubkey, label: &str, candidate: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyKey {
+            key_record: key_record_in(registry_id, owner, label),
+            alias_target: None,
+            access_log: None,
+            verifier: None,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifyKey {
+            _registry_id: *registry_id,
+            _owner: *owner,
+            label: label.to_string(),
+            public_key_to_verify: candidate.to_vec(),
+        }
+        .data(),
+    }
+}
+
+// `verify_key_slice` of the key-length window of `blob` at `offset`.
+pub fn verify_key_slice(owner: &Pubkey, label: &str, blob: &[u8], offset: u32) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyKey {
+            key_record: key_record(owner, label),
+            alias_target: None,
+            access_log: None,
+            verifier: None,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifyKeySlice {
+            registry_id: REGISTRY,
+            owner: *owner,
+            label: label.to_string(),
+            blob: blob.to_vec(),
+            offset,
+        }
+        .data(),
+    }
+}
+
+// `verify_key` passing `alias_target`, the record an alias points at.
+pub fn verify_key_via(
+    owner: &Pubkey,
+    label: &str,
+    candidate: &[u8],
+    alias_target: Option<Pubkey>,
+    verifier: Option<&Pubkey>,
+) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyKey {
+            key_record: key_record(owner, label),
+            alias_target,
+            access_log: None,
+            verifier: verifier.copied(),
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifyKey {
+            _registry_id: REGISTRY,
+            _owner: *owner,
+            label: label.to_string(),
+            public_key_to_verify: candidate.to_vec(),
+        }
+        .data(),
+    }
+}
+
+// `verify_key` signed by `verifier`, recorded in the owner's access log.
+pub fn verify_key_logged(owner: &Pubkey, label: &str, candidate: &[u8], verifier: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyKey {
+            key_record: key_record(owner, label),
+            alias_target: None,
+            access_log: Some(access_log(owner)),
+            verifier: Some(*verifier),
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifyKey {
+            _registry_id: REGISTRY,
+            _owner: *owner,
+            label: label.to_string(),
+            public_key_to_verify: candidate.to_vec(),
+        }
+        .data(),
+    }
+}
+
+pub fn add_key(owner: &Pubkey, key: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::AddKey {
+            owner: *owner,
+            key_bundle: key_bundle(owner),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::AddKey { key }.data(),
+    }
+}
+
+pub fn remove_key(owner: &Pubkey, key: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::RemoveKey {
+            owner: *owner,
+            key_bundle: key_bundle(owner),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::RemoveKey { key }.data(),
+    }
+}
+
+pub fn verify_in_bundle(owner: &Pubkey, candidate: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyInBundle { key_bundle: key_bundle(owner) }.to_account_metas(None),
+        data: key_registry::instruction::VerifyInBundle { owner: *owner, candidate }.data(),
+    }
+}
+
+pub fn register_root(owner: &Pubkey, root: [u8; 32]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::RegisterRoot {
+            owner: *owner,
+            key_root: key_root(owner),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::RegisterRoot { root }.data(),
+    }
+}
+
+pub fn verify_membership(owner: &Pubkey, public_key: &[u8], proof: Vec<[u8; 32]>) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyMembership { key_root: key_root(owner) }.to_account_metas(None),
+        data: key_registry::instruction::VerifyMembership {
+            owner: *owner,
+            public_key: public_key.to_vec(),
+            proof,
+        }
+        .data(),
+    }
+}
+
+pub fn enable_access_log(owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::EnableAccessLog {
+            owner: *owner,
+            access_log: access_log(owner),
+            system_program: solana_sdk::system_program::ID,
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::EnableAccessLog {}.data(),
+    }
+}
+
+pub fn get_access_log(owner: &Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::GetAccessLog { access_log: access_log(owner) }.to_account_metas(None),
+        data: key_registry::instruction::GetAccessLog {}.data(),
+    }
+}
+
+pub fn verify_key_counted(owner: &Pubkey, label: &str, candidate: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyKeyCounted {
+            key_record: key_record(owner, label),
+            verifier: None,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifyKeyCounted {
+            _registry_id: REGISTRY,
+            _owner: *owner,
+            label: label.to_string(),
+            public_key_to_verify: candidate.to_vec(),
+        }
+        .data(),
+    }
+}
+
+pub fn verify_key_with_backup(owner: &Pubkey, label: &str, candidate: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyKey {
+            key_record: key_record(owner, label),
+            alias_target: None,
+            access_log: None,
+            verifier: None,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifyKeyWithBackup {
+            _registry_id: REGISTRY,
+            _owner: *owner,
+            label: label.to_string(),
+            public_key_to_verify: candidate.to_vec(),
+        }
+        .data(),
+    }
+}
+
+// `verify_signature` of `signature` over `message` by the record's key;
+// `recovery_id` only matters for secp256k1.
+pub fn verify_signature(owner: &Pubkey, label: &str, message: &[u8], signature: [u8; 64], recovery_id: u8) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifySignature {
+            key_record: key_record(owner, label),
+            instructions: sysvar::instructions::ID,
+            verifier: None,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifySignature {
+            _registry_id: REGISTRY,
+            _owner: *owner,
+            _label: label.to_string(),
+            message: message.to_vec(),
+            signature,
+            recovery_id,
+        }
+        .data(),
+    }
+}
+
+pub fn assert_not_registered(owner: &Pubkey, label: &str) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::MaybeKey {
+            key_record: key_record(owner, label),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::AssertNotRegistered {
+            _registry_id: REGISTRY,
+            owner: *owner,
+            label: label.to_string(),
+        }
+        .data(),
+    }
+}
+
+pub fn account_exists(owner: &Pubkey, label: &str) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::MaybeKey {
+            key_record: key_record(owner, label),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::AccountExists {
+            _registry_id: REGISTRY,
+            owner: *owner,
+            label: label.to_string(),
+        }
+        .data(),
+    }
+}
+
+pub fn set_verifier_allowlist(owner: &Pubkey, label: &str, verifiers: Vec<Pubkey>) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetVerifierAllowlist {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetVerifierAllowlist { verifiers }.data(),
+    }
+}
+
+pub fn get_metadata(owner: &Pubkey, label: &str, verifier: Option<&Pubkey>) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::GetMetadata {
+            key_record: key_record(owner, label),
+            verifier: verifier.copied(),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::GetMetadata {
+            _registry_id: REGISTRY,
+            _owner: *owner,
+            _label: label.to_string(),
+        }
+        .data(),
+    }
+}
+
+pub fn get_history(owner: &Pubkey, label: &str, verifier: Option<&Pubkey>) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::GetHistory {
+            key_record: key_record(owner, label),
+            verifier: verifier.copied(),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::GetHistory {
+            _registry_id: REGISTRY,
+            _owner: *owner,
+            _label: label.to_string(),
+        }
+        .data(),
+    }
+}
+
+pub fn renew_key(owner: &Pubkey, label: &str, new_expires_at: i64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::RenewKey {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+            settings: settings(owner),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::RenewKey { new_expires_at }.data(),
+    }
+}
+
+pub fn set_slot_expiry(owner: &Pubkey, label: &str, expires_at_slot: u64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::RenewKey {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+            settings: settings(owner),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetSlotExpiry { expires_at_slot }.data(),
+    }
+}
+
+pub fn set_verifier(owner: &Pubkey, label: &str, program: Option<Pubkey>) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetVerifier {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetVerifier { program }.data(),
+    }
+}
+
+// `verify_delegated` of `data`, asking `verifier_program`.
+pub fn verify_delegated(owner: &Pubkey, label: &str, verifier_program: Pubkey, data: &[u8]) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::VerifyDelegated {
+            key_record: key_record(owner, label),
+            verifier_program,
+            verifier: None,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::VerifyDelegated {
+            _registry_id: REGISTRY,
+            _owner: *owner,
+            _label: label.to_string(),
+            data: data.to_vec(),
+        }
+        .data(),
+    }
+}
+
+// `invoke_with_attestation` of `record` into `target_program`, with empty
+// instruction data and no further accounts.
+pub fn invoke_with_attestation(owner: &Pubkey, record: Pubkey, target_program: Pubkey) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::InvokeWithAttestation {
+            owner: *owner,
+            key_record: record,
+            target_program,
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::InvokeWithAttestation { inner_ix_data: vec![] }.data(),
+    }
+}
+
+pub fn touch(owner: &Pubkey, label: &str) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::Touch {
+            owner: *owner,
+            key_record: key_record(owner, label),
+            config: Some(config()),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::Touch {}.data(),
+    }
+}
+
+// The record's current contents.
+pub async fn fetch_record(ctx: &mut ProgramTestContext, address: Pubkey) -> KeyRecord {
+    let account = ctx.banks_client.get_account(address).await.unwrap().expect("record exists");
+    KeyRecord::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+pub async fn fetch_config(ctx: &mut ProgramTestContext) -> Config {
+    let account = ctx.banks_client.get_account(config()).await.unwrap().expect("config exists");
+    Config::try_deserialize(&mut account.data.as_slice()).unwrap()
+}
+
+// `set_test_clock`, signed by the admin (the context payer).
+pub fn set_test_clock(ctx: &ProgramTestContext, unix_ts: i64) -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::SetPaused {
+            admin: ctx.payer.pubkey(),
+            config: config(),
+        }
+        .to_account_metas(None),
+        data: key_registry::instruction::SetTestClock { unix_ts }.data(),
+    }
+}
+
+// Move the bank's clock `seconds` forward; the slot stays where it is.
+pub async fn advance_clock(ctx: &mut ProgramTestContext, seconds: i64) {
+    let mut clock: solana_sdk::clock::Clock = ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp += seconds;
+    ctx.set_sysvar(&clock);
+    // a transaction retried after the wait must not be answered from the
+    // status cache with its earlier failure
+    refresh_blockhash(ctx).await;
+}
+
+// Wait for a new blockhash, so sending a transaction again really runs it
+// rather than hitting the status cache.
+pub async fn refresh_blockhash(ctx: &mut ProgramTestContext) {
+    let blockhash = ctx.banks_client.get_latest_blockhash().await.unwrap();
+    ctx.banks_client.get_new_latest_blockhash(&blockhash).await.unwrap();
+}
+
+// The bank's current Unix time.
+pub async fn now(ctx: &mut ProgramTestContext) -> i64 {
+    let clock: solana_sdk::clock::Clock = ctx.banks_client.get_sysvar().await.unwrap();
+    clock.unix_timestamp
+}
+
+pub fn program_info() -> Instruction {
+    Instruction {
+        program_id: ID,
+        accounts: key_registry::accounts::GetProgramInfo {}.to_account_metas(None),
+        data: key_registry::instruction::ProgramInfo {}.data(),
+    }
+}
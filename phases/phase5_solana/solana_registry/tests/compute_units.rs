@@ -0,0 +1,42 @@
+// Compute-unit budgets, checked against the SBF build since native runs
+// don't meter compute. Ignored by default as they need that build; run them
+// with `cargo test-sbf -- --ignored`, or `cargo build-sbf` and then
+// `cargo test --test compute_units -- --ignored`. Without a build they fail.
+//
+// The budgets are what CPI callers may plan around, with headroom over the
+// cost when each was set; a change that needs more should raise the budget
+// deliberately, not drift past it.
+
+mod common;
+
+use solana_sdk::signature::Signer;
+
+// verify_key on a plain record: what a relying program pays per check.
+const VERIFY_KEY_BUDGET: u64 = 20_000;
+// register_key creating the record and its key index, emitting the event by
+// self-CPI.
+const REGISTER_KEY_BUDGET: u64 = 80_000;
+// close_key on a record without a handle.
+const CLOSE_KEY_BUDGET: u64 = 25_000;
+
+#[tokio::test]
+#[ignore = "needs the SBF build, see the top of this file"]
+async fn instructions_stay_within_budget() {
+    let mut ctx = common::start(common::bpf_program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let (_, units) = common::simulate(&mut ctx, register.clone(), &[&owner]).await;
+    assert!(units <= REGISTER_KEY_BUDGET, "register_key used {units} CU, budget {REGISTER_KEY_BUDGET}");
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let verify = common::verify_key(&owner.pubkey(), "", &key, None);
+    let (matched, units) = common::simulate(&mut ctx, verify, &[]).await;
+    assert_eq!(matched, [1]);
+    assert!(units <= VERIFY_KEY_BUDGET, "verify_key used {units} CU, budget {VERIFY_KEY_BUDGET}");
+    
+    let close = common::close_key(&owner.pubkey(), "", &key);
+    let (_, units) = common::simulate(&mut ctx, close, &[&owner]).await;
+    assert!(units <= CLOSE_KEY_BUDGET, "close_key used {units} CU, budget {CLOSE_KEY_BUDGET}");
+}
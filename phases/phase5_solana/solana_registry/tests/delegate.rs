@@ -0,0 +1,62 @@
+// An owner's delegate may update their keys in the owner's place; closing a
+// record and appointing the delegate stay with the owner.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+// `instruction` as sent by `signer` rather than the owner.
+fn signed_by(mut instruction: Instruction, signer: &Keypair) -> Instruction {
+    instruction.accounts[0].pubkey = signer.pubkey();
+    instruction
+}
+
+#[tokio::test]
+async fn delegate_updates_for_the_owner() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let delegate = common::funded(&mut ctx, 1_000_000_000).await;
+    let stranger = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [common::test_key(1), common::test_key(2), common::test_key(3)];
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &keys[0]);
+    let appoint = common::set_delegate(&owner.pubkey(), delegate.pubkey());
+    common::send(&mut ctx, &[register, appoint], &[&owner]).await.unwrap();
+    
+    let update = |from: usize, to: usize, signer: &Keypair| {
+        signed_by(common::update_key(&owner.pubkey(), "", &keys[from], &keys[to], 0), signer)
+    };
+    let err = common::send(&mut ctx, &[update(0, 1, &stranger)], &[&stranger]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    common::send(&mut ctx, &[update(0, 1, &delegate)], &[&delegate]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.key_bytes(), keys[1].as_slice());
+    assert_eq!(record.owner, owner.pubkey());
+    
+    // clearing the delegate withdraws the authority
+    let dismiss = common::set_delegate(&owner.pubkey(), Pubkey::default());
+    common::send(&mut ctx, &[dismiss], &[&owner]).await.unwrap();
+    let err = common::send(&mut ctx, &[update(1, 2, &delegate)], &[&delegate]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+}
+
+#[tokio::test]
+async fn closing_stays_with_the_owner() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let delegate = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(4);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let appoint = common::set_delegate(&owner.pubkey(), delegate.pubkey());
+    common::send(&mut ctx, &[register, appoint], &[&owner]).await.unwrap();
+    
+    let close = signed_by(common::close_key(&owner.pubkey(), "", &key), &delegate);
+    let err = common::send(&mut ctx, &[close], &[&delegate]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    
+    let close = common::close_key(&owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[close], &[&owner]).await.unwrap();
+    assert!(ctx.banks_client.get_account(common::key_record(&owner.pubkey(), "")).await.unwrap().is_none());
+}
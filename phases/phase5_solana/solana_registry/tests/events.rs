@@ -0,0 +1,43 @@
+// register_key, update_key and verify_key emit `KeyRegistered`, `KeyUpdated`
+// and `KeyVerified` for indexers, next to the existing msg! lines.
+
+mod common;
+
+use key_registry::{KeyRegistered, KeyType, KeyUpdated, KeyVerified};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn register_update_and_verify_emit_events() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [common::test_key(1), common::test_key(2)];
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &keys[0]);
+    let logs = common::send_logged(&mut ctx, &[register], &[&owner]).await;
+    assert!(logs.iter().any(|line| line.contains("Registered X25519 public key")));
+    let [registered] = &common::events::<KeyRegistered>(&logs)[..] else {
+        panic!("one KeyRegistered in {logs:?}");
+    };
+    assert_eq!(registered.owner, owner.pubkey());
+    assert_eq!(registered.key_type, KeyType::X25519 as u8);
+    assert_eq!(registered.public_key, keys[0]);
+    
+    let update = common::update_key(&owner.pubkey(), "", &keys[0], &keys[1], 0);
+    let logs = common::send_logged(&mut ctx, &[update], &[&owner]).await;
+    let [updated] = &common::events::<KeyUpdated>(&logs)[..] else {
+        panic!("one KeyUpdated in {logs:?}");
+    };
+    assert_eq!(updated.owner, owner.pubkey());
+    assert_eq!((&updated.old_key, &updated.new_key), (&keys[0], &keys[1]));
+    assert!(updated.seq > registered.seq);
+    
+    for (candidate, matched) in [(&keys[1], true), (&keys[0], false)] {
+        let verify = common::verify_key(&owner.pubkey(), "", candidate, None);
+        let logs = common::send_logged(&mut ctx, &[verify], &[]).await;
+        let [verified] = &common::events::<KeyVerified>(&logs)[..] else {
+            panic!("one KeyVerified in {logs:?}");
+        };
+        assert_eq!(verified.owner, owner.pubkey());
+        assert_eq!(verified.matched, matched);
+    }
+}
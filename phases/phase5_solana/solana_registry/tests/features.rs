@@ -0,0 +1,44 @@
+// The unit and program-test suites again under the other feature sets
+// (without `clock`, and with `test-clock`), in nested cargo runs. They share
+// a target directory of their own, since this run holds the build lock on
+// ours, so the first run builds the dependencies once more. Set
+// KEY_REGISTRY_FEATURE_MATRIX to skip them.
+
+use std::path::Path;
+use std::process::Command;
+
+// also set in the nested runs, so they don't recurse into this suite
+const SKIP: &str = "KEY_REGISTRY_FEATURE_MATRIX";
+
+fn cargo_test(features: &[&str]) {
+    if std::env::var_os(SKIP).is_some() {
+        return;
+    }
+    let target_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("feature-matrix");
+    let output = Command::new(env!("CARGO"))
+        .arg("test")
+        .arg("--manifest-path")
+        .arg(Path::new(env!("CARGO_MANIFEST_DIR")).join("Cargo.toml"))
+        .args(features)
+        .env(SKIP, "1")
+        .env("CARGO_TARGET_DIR", target_dir)
+        .output()
+        .expect("run cargo");
+    assert!(
+        output.status.success(),
+        "cargo test {}\n{}{}",
+        features.join(" "),
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn without_clock() {
+    cargo_test(&["--no-default-features"]);
+}
+
+#[test]
+fn with_test_clock() {
+    cargo_test(&["--features", "test-clock"]);
+}
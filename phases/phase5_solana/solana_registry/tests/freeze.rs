@@ -0,0 +1,55 @@
+// freeze_key suspends a key until thaw_key lifts it: frozen, it doesn't
+// verify and can't be updated. The owner or the registry admin may do both.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn freeze_blocks_updates_until_thawed() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [common::test_key(1), common::test_key(2)];
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &keys[0]);
+    let freeze = common::freeze_key(&owner.pubkey(), &owner.pubkey(), "");
+    common::send(&mut ctx, &[register, freeze], &[&owner]).await.unwrap();
+    
+    let update = || common::update_key(&owner.pubkey(), "", &keys[0], &keys[1], 0);
+    let err = common::send(&mut ctx, &[update()], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Frozen.into()));
+    let verify = common::verify_key(&owner.pubkey(), "", &keys[0], None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [0]);
+    
+    let thaw = common::thaw_key(&owner.pubkey(), &owner.pubkey(), "");
+    common::send(&mut ctx, &[thaw], &[&owner]).await.unwrap();
+    common::refresh_blockhash(&mut ctx).await;
+    common::send(&mut ctx, &[update()], &[&owner]).await.unwrap();
+    let verify = common::verify_key(&owner.pubkey(), "", &keys[1], None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+}
+
+#[tokio::test]
+async fn owner_or_admin_only() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let stranger = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(3));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let freeze = common::freeze_key(&stranger.pubkey(), &owner.pubkey(), "");
+    let err = common::send(&mut ctx, &[freeze], &[&stranger]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    
+    // the admin is the context payer
+    let admin = ctx.payer.pubkey();
+    let freeze = common::freeze_key(&admin, &owner.pubkey(), "");
+    common::send(&mut ctx, &[freeze], &[]).await.unwrap();
+    assert!(common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await.frozen);
+    let thaw = common::thaw_key(&stranger.pubkey(), &owner.pubkey(), "");
+    let err = common::send(&mut ctx, &[thaw], &[&stranger]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    let thaw = common::thaw_key(&admin, &owner.pubkey(), "");
+    common::send(&mut ctx, &[thaw], &[]).await.unwrap();
+    assert!(!common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await.frozen);
+}
@@ -0,0 +1,63 @@
+// grow_record makes room in an existing record for fields it predates,
+// zero-filled, without a full migrate; it never shrinks a record.
+
+mod common;
+
+use anchor_lang::error::ErrorCode;
+use anchor_lang::AnchorSerialize;
+use key_registry::{KeyRegistryError, ACCOUNT_SIZE};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn grown_record_takes_a_field_it_had_no_room_for() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(1));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    // cut the record back to before `verifier_allowlist` and what follows it
+    // (the allowlist, `history_limit` and an empty `history_overflow`)
+    let address = common::key_record(&owner.pubkey(), "");
+    let written = common::fetch_record(&mut ctx, address).await.try_to_vec().unwrap().len();
+    let mut account = ctx.banks_client.get_account(address).await.unwrap().unwrap();
+    account.data.truncate(8 + written - (32 * 4 + 1 + 4));
+    ctx.set_account(&address, &account.into());
+    
+    let allowlist = || common::set_verifier_allowlist(&owner.pubkey(), "", vec![owner.pubkey()]);
+    let err = common::send(&mut ctx, &[allowlist()], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(ErrorCode::AccountDidNotDeserialize.into()));
+    
+    let grow = common::grow_record(&owner.pubkey(), "", ACCOUNT_SIZE as u64);
+    common::send(&mut ctx, &[grow, allowlist()], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, address).await;
+    assert_eq!(record.verifier_allowlist[0], owner.pubkey());
+    let account = ctx.banks_client.get_account(address).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), ACCOUNT_SIZE);
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    assert!(rent.is_exempt(account.lamports, account.data.len()));
+}
+
+#[tokio::test]
+async fn never_shrinks_and_is_a_no_op_at_size() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let stranger = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(2));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    let address = common::key_record(&owner.pubkey(), "");
+    let before = ctx.banks_client.get_account(address).await.unwrap().unwrap();
+    let len = before.data.len() as u64;
+    
+    let shrink = common::grow_record(&owner.pubkey(), "", len - 1);
+    let err = common::send(&mut ctx, &[shrink], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::CannotShrink.into()));
+    let same = common::grow_record(&owner.pubkey(), "", len);
+    common::send(&mut ctx, &[same], &[&owner]).await.unwrap();
+    assert_eq!(ctx.banks_client.get_account(address).await.unwrap().unwrap(), before);
+    
+    // the owner's record, grown by someone else
+    let mut grow = common::grow_record(&owner.pubkey(), "", len + 32);
+    grow.accounts[0].pubkey = stranger.pubkey();
+    let err = common::send(&mut ctx, &[grow], &[&stranger]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+}
@@ -0,0 +1,58 @@
+// The previous-key ring is sized per record at registration: 0 keeps no
+// history, anything up to `MAX_HISTORY_CAPACITY` wraps after that many
+// rotations, and larger capacities get the account space to hold them.
+
+mod common;
+
+use anchor_lang::AnchorDeserialize;
+use key_registry::{KeyRecord, KeyRegistryError};
+use solana_sdk::signature::Signer;
+
+// Register with `capacity`, then rotate through `keys` in order.
+async fn rotate_through(capacity: u8, keys: &[Vec<u8>]) -> Vec<Vec<u8>> {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key_with_history(&ctx, &owner.pubkey(), "", &keys[0], capacity);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    let account = ctx.banks_client.get_account(common::key_record(&owner.pubkey(), "")).await.unwrap().unwrap();
+    assert_eq!(account.data.len(), KeyRecord::account_size(capacity as usize));
+    
+    let updates: Vec<_> = keys
+        .windows(2)
+        .map(|pair| common::update_key(&owner.pubkey(), "", &pair[0], &pair[1], 0))
+        .collect();
+    common::send(&mut ctx, &updates, &[&owner]).await.unwrap();
+    let (data, _) = common::simulate(&mut ctx, common::get_history(&owner.pubkey(), "", None), &[]).await;
+    Vec::<Vec<u8>>::try_from_slice(&data).unwrap()
+}
+
+#[tokio::test]
+async fn capacity_zero_keeps_no_history() {
+    let keys: Vec<Vec<u8>> = (1..=3).map(common::test_key).collect();
+    assert!(rotate_through(0, &keys).await.is_empty());
+}
+
+#[tokio::test]
+async fn capacity_three_wraps_after_three() {
+    let keys: Vec<Vec<u8>> = (1..=5).map(common::test_key).collect();
+    // the first key has been overwritten
+    assert_eq!(rotate_through(3, &keys).await, [keys[3].clone(), keys[2].clone(), keys[1].clone()]);
+}
+
+#[tokio::test]
+async fn capacity_beyond_the_fixed_slots() {
+    let keys: Vec<Vec<u8>> = (1..=8).map(common::test_key).collect();
+    let history = rotate_through(KeyRecord::MAX_HISTORY_CAPACITY as u8, &keys).await;
+    let expected: Vec<Vec<u8>> = keys[..7].iter().rev().cloned().collect();
+    assert_eq!(history, expected);
+}
+
+#[tokio::test]
+async fn capacity_above_the_max_is_rejected() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let capacity = KeyRecord::MAX_HISTORY_CAPACITY as u8 + 1;
+    let register = common::register_key_with_history(&ctx, &owner.pubkey(), "", &common::test_key(1), capacity);
+    let err = common::send(&mut ctx, &[register], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidHistoryCapacity.into()));
+}
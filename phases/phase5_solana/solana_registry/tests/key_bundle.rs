@@ -0,0 +1,58 @@
+// A key bundle holds several 32-byte keys of one owner in a single account:
+// add_key appends, remove_key swap-removes and verify_in_bundle checks
+// membership.
+
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use key_registry::{KeyBundle, KeyRegistryError};
+use solana_sdk::signature::Signer;
+
+fn bundle_key(seed: u8) -> [u8; 32] {
+    common::test_key(seed).try_into().unwrap()
+}
+
+#[tokio::test]
+async fn add_three_remove_the_middle_one() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [bundle_key(1), bundle_key(2), bundle_key(3)];
+    let add = keys.map(|key| common::add_key(&owner.pubkey(), key));
+    common::send(&mut ctx, &add[..1], &[&owner]).await.unwrap();
+    // one rent deposit covers every key
+    let rent = ctx.banks_client.get_balance(common::key_bundle(&owner.pubkey())).await.unwrap();
+    common::send(&mut ctx, &add[1..], &[&owner]).await.unwrap();
+    assert_eq!(ctx.banks_client.get_balance(common::key_bundle(&owner.pubkey())).await.unwrap(), rent);
+    
+    let remove = common::remove_key(&owner.pubkey(), keys[1]);
+    common::send(&mut ctx, &[remove], &[&owner]).await.unwrap();
+    let account = ctx.banks_client.get_account(common::key_bundle(&owner.pubkey())).await.unwrap().unwrap();
+    let bundle = KeyBundle::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!(bundle.owner, owner.pubkey());
+    assert_eq!(bundle.keys, [keys[0], keys[2]]);
+    
+    for (key, expected) in [(keys[0], 1), (keys[1], 0), (keys[2], 1)] {
+        let (matched, _) = common::simulate(&mut ctx, common::verify_in_bundle(&owner.pubkey(), key), &[]).await;
+        assert_eq!(matched, [expected]);
+    }
+}
+
+#[tokio::test]
+async fn bundle_rejects_duplicates_and_overflow() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let add: Vec<_> = (0..KeyBundle::MAX_KEYS as u8)
+        .map(|seed| common::add_key(&owner.pubkey(), bundle_key(seed)))
+        .collect();
+    common::send(&mut ctx, &add, &[&owner]).await.unwrap();
+    
+    let duplicate = common::add_key(&owner.pubkey(), bundle_key(0));
+    let err = common::send(&mut ctx, &[duplicate], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::KeyAlreadyIndexed.into()));
+    let ninth = common::add_key(&owner.pubkey(), bundle_key(100));
+    let err = common::send(&mut ctx, &[ninth], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::BundleFull.into()));
+    let missing = common::remove_key(&owner.pubkey(), bundle_key(100));
+    let err = common::send(&mut ctx, &[missing], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::KeyNotInBundle.into()));
+}
@@ -0,0 +1,56 @@
+// An owner can keep several keys, one record per label; the label is a PDA
+// seed, stored in the record, and picks the record in verify_key and
+// update_key.
+
+mod common;
+
+use key_registry::{KeyRecord, KeyRegistryError};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn one_owner_two_labelled_keys() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (signing, encryption) = (common::test_key(1), common::test_key(2));
+    let register_signing = common::register_key(&ctx, &owner.pubkey(), "signing", &signing);
+    let register_encryption = common::register_key(&ctx, &owner.pubkey(), "encryption", &encryption);
+    common::send(&mut ctx, &[register_signing, register_encryption], &[&owner]).await.unwrap();
+    
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "signing")).await;
+    assert_eq!((record.label.as_str(), record.key_bytes()), ("signing", signing.as_slice()));
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "encryption")).await;
+    assert_eq!((record.label.as_str(), record.key_bytes()), ("encryption", encryption.as_slice()));
+    
+    // each label verifies its own key only
+    for (label, candidate, matched) in [
+        ("signing", &signing, [1]),
+        ("signing", &encryption, [0]),
+        ("encryption", &encryption, [1]),
+    ] {
+        let verify = common::verify_key(&owner.pubkey(), label, candidate, None);
+        assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, matched, "{label}");
+    }
+    
+    // and an update leaves the other label alone
+    let rotated = common::test_key(3);
+    let update = common::update_key(&owner.pubkey(), "signing", &signing, &rotated, 0);
+    common::send(&mut ctx, &[update], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "signing")).await;
+    assert_eq!(record.key_bytes(), rotated.as_slice());
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "encryption")).await;
+    assert_eq!(record.key_bytes(), encryption.as_slice());
+}
+
+#[tokio::test]
+async fn rejects_a_label_over_the_limit() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let at_limit = "l".repeat(KeyRecord::MAX_LABEL_LEN);
+    let register = common::register_key(&ctx, &owner.pubkey(), &at_limit, &common::test_key(4));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let too_long = "l".repeat(KeyRecord::MAX_LABEL_LEN + 1);
+    let register = common::register_key(&ctx, &owner.pubkey(), &too_long, &common::test_key(5));
+    let err = common::send(&mut ctx, &[register], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::LabelTooLong.into()));
+}
@@ -0,0 +1,80 @@
+// An owner's allowlist as one Merkle root: verify_membership recomputes the
+// root from a key and its proof. The tree is built here from the documented
+// scheme, keccak256(0x00 || key) leaves and keccak256(0x01 || lower ||
+// higher) parents, rather than with the program's own helpers.
+
+mod common;
+
+use key_registry::{KeyRegistryError, MAX_MERKLE_DEPTH};
+use solana_sdk::keccak::hashv;
+use solana_sdk::signature::Signer;
+
+fn leaf(key: &[u8]) -> [u8; 32] {
+    hashv(&[&[0], key]).to_bytes()
+}
+
+fn node(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+    let (lower, higher) = if a <= b { (a, b) } else { (b, a) };
+    hashv(&[&[1], lower, higher]).to_bytes()
+}
+
+// A leaf's sibling hashes, from the leaf up.
+type Proof = Vec<[u8; 32]>;
+
+// Four keys, their tree's root and each key's proof.
+fn four_leaf_tree() -> (Vec<Vec<u8>>, [u8; 32], Vec<Proof>) {
+    let keys: Vec<Vec<u8>> = (1..=4).map(common::test_key).collect();
+    let leaves: Vec<[u8; 32]> = keys.iter().map(|key| leaf(key)).collect();
+    let (left, right) = (node(&leaves[0], &leaves[1]), node(&leaves[2], &leaves[3]));
+    let proofs = vec![
+        vec![leaves[1], right],
+        vec![leaves[0], right],
+        vec![leaves[3], left],
+        vec![leaves[2], left],
+    ];
+    (keys, node(&left, &right), proofs)
+}
+
+#[tokio::test]
+async fn every_leaf_of_a_four_leaf_tree() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (keys, root, proofs) = four_leaf_tree();
+    assert_eq!(key_registry::merkle_leaf(&keys[0]), leaf(&keys[0]));
+    let register = common::register_root(&owner.pubkey(), root);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    for (key, proof) in keys.iter().zip(&proofs) {
+        let verify = common::verify_membership(&owner.pubkey(), key, proof.clone());
+        let (matched, _) = common::simulate(&mut ctx, verify, &[]).await;
+        assert_eq!(matched, [1]);
+    }
+    // a key outside the tree, and a member with another member's proof
+    let outsider = common::verify_membership(&owner.pubkey(), &common::test_key(5), proofs[0].clone());
+    let (matched, _) = common::simulate(&mut ctx, outsider, &[]).await;
+    assert_eq!(matched, [0]);
+    let swapped = common::verify_membership(&owner.pubkey(), &keys[0], proofs[2].clone());
+    let (matched, _) = common::simulate(&mut ctx, swapped, &[]).await;
+    assert_eq!(matched, [0]);
+}
+
+#[tokio::test]
+async fn interior_nodes_are_not_leaves() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (keys, root, proofs) = four_leaf_tree();
+    let register = common::register_root(&owner.pubkey(), root);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    // the left parent's children, presented as a 64-byte key one level up
+    let (a, b) = (leaf(&keys[0]), leaf(&keys[1]));
+    let (lower, higher) = if a <= b { (a, b) } else { (b, a) };
+    let forged = [lower, higher].concat();
+    let verify = common::verify_membership(&owner.pubkey(), &forged, vec![proofs[0][1]]);
+    let (matched, _) = common::simulate(&mut ctx, verify, &[]).await;
+    assert_eq!(matched, [0]);
+    
+    let too_deep = common::verify_membership(&owner.pubkey(), &keys[0], vec![[0u8; 32]; MAX_MERKLE_DEPTH + 1]);
+    let err = common::send(&mut ctx, &[too_deep], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::BatchTooLarge.into()));
+}
@@ -0,0 +1,71 @@
+// migrate brings a record written under an older layout up to the current
+// one in place, growing the account; on a current record it does nothing.
+
+mod common;
+
+use anchor_lang::Discriminator;
+use key_registry::{KeyRecord, KeyType, ID};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+
+// A v1 record: no version byte or status, and the delegate still in it. At
+// the size v1 accounts were allocated with.
+fn v1_account(owner: &Pubkey, public_key: &[u8], expires_at: i64, bump: u8) -> Account {
+    let mut data = KeyRecord::DISCRIMINATOR.to_vec();
+    data.extend_from_slice(owner.as_ref());
+    data.extend_from_slice(&[KeyType::X25519 as u8, public_key.len() as u8]);
+    data.extend_from_slice(public_key);
+    data.resize(data.len() + 64 - public_key.len(), 0);
+    data.extend_from_slice(&[bump, 0]); // bump, revoked
+    data.resize(data.len() + 64 * 5 + 1, 0); // empty history
+    data.extend_from_slice(&expires_at.to_le_bytes());
+    data.extend_from_slice(Keypair::new().pubkey().as_ref()); // delegate
+    data.extend_from_slice(&0u32.to_le_bytes()); // unlabelled
+    data.extend_from_slice(owner.as_ref()); // registrant
+    data.resize(8 + KeyRecord::V1_LEN, 0);
+    Account {
+        lamports: Rent::default().minimum_balance(data.len()),
+        data,
+        owner: ID,
+        executable: false,
+        rent_epoch: 0,
+    }
+}
+
+#[tokio::test]
+async fn migrates_a_v1_record() {
+    let owner = Keypair::new();
+    let key = common::test_key(1);
+    let expires_at = i64::MAX;
+    let record = common::key_record(&owner.pubkey(), "");
+    let registry_seed = key_registry::registry_seed(&common::REGISTRY);
+    let bump = Pubkey::find_program_address(&[b"key_record", registry_seed, owner.pubkey().as_ref()], &ID).1;
+    let mut test = common::program_test();
+    test.add_account(record, v1_account(&owner.pubkey(), &key, expires_at, bump));
+    let mut ctx = common::start(test).await;
+    
+    let payer = ctx.payer.pubkey();
+    common::send(&mut ctx, &[common::migrate(&payer, record)], &[]).await.unwrap();
+    let migrated = common::fetch_record(&mut ctx, record).await;
+    assert_eq!(migrated.version, KeyRecord::CURRENT_VERSION);
+    assert_eq!(migrated.owner, owner.pubkey());
+    assert_eq!(migrated.registrant, owner.pubkey());
+    assert_eq!(migrated.key_bytes(), key.as_slice());
+    assert_eq!(migrated.expires_at, expires_at);
+    assert_eq!((migrated.bump, migrated.label.as_str()), (bump, ""));
+    let account = ctx.banks_client.get_account(record).await.unwrap().unwrap();
+    assert!(account.data.len() > 8 + KeyRecord::V1_LEN);
+    
+    // the migrated record is found and verifies like a new one
+    let verify = common::verify_key(&owner.pubkey(), "", &key, None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+    
+    // and migrating it again leaves it as it is
+    common::refresh_blockhash(&mut ctx).await;
+    common::send(&mut ctx, &[common::migrate(&payer, record)], &[]).await.unwrap();
+    let again = ctx.banks_client.get_account(record).await.unwrap().unwrap();
+    assert_eq!(again.data, account.data);
+    assert_eq!(again.lamports, account.lamports);
+}
@@ -0,0 +1,28 @@
+// Builds without `clock` (`--no-default-features`): every timestamp reads 0,
+// so records carry no times and expiry is never enforced.
+
+#![cfg(not(feature = "clock"))]
+
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use key_registry::KeyRecord;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn timestamps_read_zero_and_expiry_is_skipped() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    // long past by any real clock
+    let register = common::register_expiring_key(&ctx, &owner.pubkey(), "", &key, 1);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let account = ctx.banks_client.get_account(common::key_record(&owner.pubkey(), "")).await.unwrap().unwrap();
+    let record = KeyRecord::try_deserialize(&mut account.data.as_slice()).unwrap();
+    assert_eq!((record.created_at, record.updated_at), (0, 0));
+    assert_eq!(record.expires_at, 1);
+    
+    let verify = common::verify_key(&owner.pubkey(), "", &key, None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+}
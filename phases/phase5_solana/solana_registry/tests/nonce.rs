@@ -0,0 +1,41 @@
+// Signed authorizations cover the record's nonce, which advances on use, so a
+// captured one can't be replayed. reactivate_key is the one that takes it.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn replayed_authorization_is_rejected() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = Keypair::new();
+    let signature = common::sign(&key, owner.pubkey().as_ref());
+    let proof = common::ed25519_verify(&key.pubkey(), owner.pubkey().as_ref(), signature);
+    let register = common::register_ed25519_key(&ctx, &owner.pubkey(), "", &key.pubkey(), signature);
+    let revoke = common::revoke_key(&owner.pubkey(), "");
+    common::send(&mut ctx, &[proof, register, revoke], &[&owner]).await.unwrap();
+    
+    // the owner's signed reactivation at nonce 0
+    let message = [owner.pubkey().as_ref(), &0u64.to_le_bytes()].concat();
+    let signature = common::sign(&key, &message);
+    let reactivate = |nonce| {
+        let proof = common::ed25519_verify(&key.pubkey(), &message, signature);
+        let key = key.pubkey();
+        [proof, common::reactivate_key(&owner.pubkey(), "", key.as_ref(), key.as_ref(), signature, nonce)]
+    };
+    common::send(&mut ctx, &reactivate(0), &[&owner]).await.unwrap();
+    assert_eq!(common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await.nonce, 1);
+    
+    // revoked again, the same authorization, resent as a new transaction,
+    // no longer matches the nonce; nor does the signature with the new one
+    let revoke = common::revoke_key(&owner.pubkey(), "");
+    common::send(&mut ctx, &[revoke], &[&owner]).await.unwrap();
+    common::refresh_blockhash(&mut ctx).await;
+    let err = common::send(&mut ctx, &reactivate(0), &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::BadNonce.into()));
+    let err = common::send(&mut ctx, &reactivate(1), &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidProofOfPossession.into()));
+    assert!(common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await.revoked);
+}
@@ -0,0 +1,67 @@
+// Keys registered in an org namespace live at `[b"key_record", org_id, owner]`
+// and answer to the org admin, who can revoke any member's key.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::{Keypair, Signer};
+
+const ORG: [u8; 32] = [3u8; 32];
+
+#[tokio::test]
+async fn member_registers_and_admin_revokes() {
+    let mut ctx = common::start(common::program_test()).await;
+    let admin = common::funded(&mut ctx, 1_000_000_000).await;
+    let member = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let create = common::create_org(&admin.pubkey(), ORG);
+    let register = common::register_key_in_org(&ctx, &member.pubkey(), &admin.pubkey(), ORG, &key);
+    common::send(&mut ctx, &[create, register], &[&admin, &member]).await.unwrap();
+    
+    let record = common::fetch_record(&mut ctx, common::org_key_record(ORG, &member.pubkey())).await;
+    assert_eq!(record.owner, member.pubkey());
+    assert_eq!(record.key_bytes(), key.as_slice());
+    let verify = || common::verify_org_key(ORG, &member.pubkey(), &key);
+    let (matched, _) = common::simulate(&mut ctx, verify(), &[]).await;
+    assert_eq!(matched, [1]);
+    
+    let revoke = common::org_revoke(&admin.pubkey(), ORG, &member.pubkey());
+    common::send(&mut ctx, &[revoke], &[&admin]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::org_key_record(ORG, &member.pubkey())).await;
+    assert!(record.revoked);
+    let (matched, _) = common::simulate(&mut ctx, verify(), &[]).await;
+    assert_eq!(matched, [0]);
+}
+
+#[tokio::test]
+async fn only_the_org_admin_revokes() {
+    let mut ctx = common::start(common::program_test()).await;
+    let admin = common::funded(&mut ctx, 1_000_000_000).await;
+    let member = common::funded(&mut ctx, 1_000_000_000).await;
+    let create = common::create_org(&admin.pubkey(), ORG);
+    let register = common::register_key_in_org(&ctx, &member.pubkey(), &admin.pubkey(), ORG, &common::test_key(2));
+    common::send(&mut ctx, &[create, register], &[&admin, &member]).await.unwrap();
+    
+    // neither the member nor a stranger is the org admin
+    for signer in [&member, &Keypair::new()] {
+        let revoke = common::org_revoke(&signer.pubkey(), ORG, &member.pubkey());
+        let err = common::send(&mut ctx, &[revoke], &[signer]).await.unwrap_err();
+        assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    }
+    let record = common::fetch_record(&mut ctx, common::org_key_record(ORG, &member.pubkey())).await;
+    assert!(!record.revoked);
+}
+
+#[tokio::test]
+async fn registration_needs_the_org_admin() {
+    let mut ctx = common::start(common::program_test()).await;
+    let admin = common::funded(&mut ctx, 1_000_000_000).await;
+    let member = common::funded(&mut ctx, 1_000_000_000).await;
+    let create = common::create_org(&admin.pubkey(), ORG);
+    common::send(&mut ctx, &[create], &[&admin]).await.unwrap();
+    
+    // the member approving their own registration
+    let register = common::register_key_in_org(&ctx, &member.pubkey(), &member.pubkey(), ORG, &common::test_key(3));
+    let err = common::send(&mut ctx, &[register], &[&member]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+}
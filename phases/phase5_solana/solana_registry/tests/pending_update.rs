@@ -0,0 +1,78 @@
+// A time-locked rotation: propose_update stages a key, finalize_update
+// installs it once its activation time has passed, and cancel_update drops
+// it. Needs a clock, as activation is measured against now.
+
+#![cfg(feature = "clock")]
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::Signer;
+
+const DELAY: i64 = 3600;
+
+#[tokio::test]
+async fn finalize_waits_for_activation() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (first, second) = (common::test_key(1), common::test_key(2));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &first);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let now = common::now(&mut ctx).await;
+    let propose = common::propose_update(&owner.pubkey(), "", &second, now + DELAY);
+    common::send(&mut ctx, &[propose], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.pending_key_bytes(), second.as_slice());
+    assert_eq!(record.key_bytes(), first.as_slice());
+    
+    let finalize = || common::finalize_update(&owner.pubkey(), "", &first, &second);
+    let err = common::send(&mut ctx, &[finalize()], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::UpdateNotReady.into()));
+    
+    common::advance_clock(&mut ctx, DELAY).await;
+    common::send(&mut ctx, &[finalize()], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.key_bytes(), second.as_slice());
+    assert_eq!(record.history[0][..32], first[..]);
+    assert!(!record.has_pending_update());
+}
+
+#[tokio::test]
+async fn activation_must_be_in_the_future() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(3));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let now = common::now(&mut ctx).await;
+    let propose = common::propose_update(&owner.pubkey(), "", &common::test_key(4), now);
+    let err = common::send(&mut ctx, &[propose], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidExpiry.into()));
+}
+
+#[tokio::test]
+async fn cancel_drops_the_pending_key() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (first, second) = (common::test_key(5), common::test_key(6));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &first);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let now = common::now(&mut ctx).await;
+    let propose = common::propose_update(&owner.pubkey(), "", &second, now + DELAY);
+    let cancel = common::cancel_update(&owner.pubkey(), "");
+    common::send(&mut ctx, &[propose, cancel], &[&owner]).await.unwrap();
+    
+    // nothing is left to finalize, even once the delay has passed (the new
+    // index is derived from the pending key, now zeroed)
+    common::advance_clock(&mut ctx, DELAY).await;
+    let finalize = common::finalize_update(&owner.pubkey(), "", &first, &[0u8; 32]);
+    let err = common::send(&mut ctx, &[finalize], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::NoPendingUpdate.into()));
+    let cancel = common::cancel_update(&owner.pubkey(), "");
+    let err = common::send(&mut ctx, &[cancel], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::NoPendingUpdate.into()));
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.key_bytes(), first.as_slice());
+}
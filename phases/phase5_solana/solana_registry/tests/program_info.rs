@@ -0,0 +1,20 @@
+// program_info's return data decodes to what this build reports.
+
+mod common;
+
+use anchor_lang::AnchorDeserialize;
+use key_registry::{KeyRecord, ProgramInfo, FEATURE_COMPRESSION, FEATURE_EXPIRY, FEATURE_TEST_CLOCK};
+
+#[tokio::test]
+async fn return_data_decodes_as_program_info() {
+    let mut ctx = common::start(common::program_test()).await;
+    let (data, _) = common::simulate(&mut ctx, common::program_info(), &[]).await;
+    let info = ProgramInfo::deserialize(&mut data.as_slice()).unwrap();
+    
+    assert_eq!(info, ProgramInfo::current());
+    assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(info.schema_version, KeyRecord::CURRENT_VERSION);
+    assert!(info.supports(FEATURE_COMPRESSION));
+    assert_eq!(info.supports(FEATURE_EXPIRY), cfg!(feature = "clock"));
+    assert_eq!(info.supports(FEATURE_TEST_CLOCK), cfg!(feature = "test-clock"));
+}
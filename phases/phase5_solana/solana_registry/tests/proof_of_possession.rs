@@ -0,0 +1,46 @@
+// Ed25519 registrations must prove possession of the key: a signature by it
+// over the owner's pubkey bytes, checked by the Ed25519 instruction just
+// before register_key.
+
+mod common;
+
+use key_registry::{KeyRegistryError, KeyType};
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn registers_with_proof_of_possession() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = Keypair::new();
+    
+    let signature = common::sign(&key, owner.pubkey().as_ref());
+    let proof = common::ed25519_verify(&key.pubkey(), owner.pubkey().as_ref(), signature);
+    let register = common::register_ed25519_key(&ctx, &owner.pubkey(), "", &key.pubkey(), signature);
+    common::send(&mut ctx, &[proof, register], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.key_type, KeyType::Ed25519 as u8);
+    assert_eq!(record.key_bytes(), key.pubkey().as_ref());
+}
+
+#[tokio::test]
+async fn rejects_a_forged_proof() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = Keypair::new();
+    
+    // a genuine signature, but over another owner: replaying someone
+    // else's proof
+    let other = Keypair::new().pubkey();
+    let signature = common::sign(&key, other.as_ref());
+    let proof = common::ed25519_verify(&key.pubkey(), other.as_ref(), signature);
+    let forged = common::register_ed25519_key(&ctx, &owner.pubkey(), "", &key.pubkey(), signature);
+    let err = common::send(&mut ctx, &[proof, forged], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidProofOfPossession.into()));
+    
+    // a signature by some other key, with no Ed25519 instruction to check it
+    let signature = common::sign(&Keypair::new(), owner.pubkey().as_ref());
+    let forged = common::register_ed25519_key(&ctx, &owner.pubkey(), "", &key.pubkey(), signature);
+    let err = common::send(&mut ctx, &[forged], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidProofOfPossession.into()));
+    assert!(ctx.banks_client.get_account(common::key_record(&owner.pubkey(), "")).await.unwrap().is_none());
+}
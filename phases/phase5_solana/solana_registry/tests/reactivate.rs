@@ -0,0 +1,79 @@
+// reactivate_key clears a revocation by installing a key the owner proves
+// they hold, signed over their pubkey and the record's nonce.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+// The proof reactivate_key wants: `owner || nonce`.
+fn reactivation_message(owner: &Pubkey, nonce: u64) -> Vec<u8> {
+    [owner.as_ref(), &nonce.to_le_bytes()].concat()
+}
+
+#[tokio::test]
+async fn register_revoke_reactivate_verify() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (compromised, fresh) = (Keypair::new(), Keypair::new());
+    let signature = common::sign(&compromised, owner.pubkey().as_ref());
+    let proof = common::ed25519_verify(&compromised.pubkey(), owner.pubkey().as_ref(), signature);
+    let register = common::register_ed25519_key(&ctx, &owner.pubkey(), "", &compromised.pubkey(), signature);
+    let revoke = common::revoke_key(&owner.pubkey(), "");
+    common::send(&mut ctx, &[proof, register, revoke], &[&owner]).await.unwrap();
+    let verify = common::verify_key(&owner.pubkey(), "", compromised.pubkey().as_ref(), None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [0]);
+    
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    let message = reactivation_message(&owner.pubkey(), record.nonce);
+    let signature = common::sign(&fresh, &message);
+    let proof = common::ed25519_verify(&fresh.pubkey(), &message, signature);
+    let (old_key, new_key) = (compromised.pubkey(), fresh.pubkey());
+    let reactivate = common::reactivate_key(&owner.pubkey(), "", old_key.as_ref(), new_key.as_ref(), signature, record.nonce);
+    common::send(&mut ctx, &[proof, reactivate], &[&owner]).await.unwrap();
+    
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert!(!record.revoked);
+    let verify = common::verify_key(&owner.pubkey(), "", fresh.pubkey().as_ref(), None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+    let verify = common::verify_key(&owner.pubkey(), "", compromised.pubkey().as_ref(), None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [0]);
+}
+
+#[tokio::test]
+async fn needs_a_revoked_record_and_a_fresh_proof() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = Keypair::new();
+    let signature = common::sign(&key, owner.pubkey().as_ref());
+    let proof = common::ed25519_verify(&key.pubkey(), owner.pubkey().as_ref(), signature);
+    let register = common::register_ed25519_key(&ctx, &owner.pubkey(), "", &key.pubkey(), signature);
+    common::send(&mut ctx, &[proof, register], &[&owner]).await.unwrap();
+    
+    let message = reactivation_message(&owner.pubkey(), 0);
+    let signature = common::sign(&key, &message);
+    let reactivate = || {
+        let proof = common::ed25519_verify(&key.pubkey(), &message, signature);
+        let reactivate = common::reactivate_key(&owner.pubkey(), "", key.pubkey().as_ref(), key.pubkey().as_ref(), signature, 0);
+        [proof, reactivate]
+    };
+    let err = common::send(&mut ctx, &reactivate(), &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::NotRevoked.into()));
+    
+    // the registration proof, without the nonce, doesn't do
+    let revoke = common::revoke_key(&owner.pubkey(), "");
+    common::send(&mut ctx, &[revoke], &[&owner]).await.unwrap();
+    let stale = common::sign(&key, owner.pubkey().as_ref());
+    let proof = common::ed25519_verify(&key.pubkey(), owner.pubkey().as_ref(), stale);
+    let replayed = common::reactivate_key(&owner.pubkey(), "", key.pubkey().as_ref(), key.pubkey().as_ref(), stale, 0);
+    let err = common::send(&mut ctx, &[proof, replayed], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidProofOfPossession.into()));
+    
+    // the owner got the key back, so it is reinstated as it was
+    common::refresh_blockhash(&mut ctx).await;
+    common::send(&mut ctx, &reactivate(), &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert!(!record.revoked);
+    assert_eq!((record.key_bytes(), record.nonce), (key.pubkey().as_ref(), 1));
+}
@@ -0,0 +1,53 @@
+// With a fee set, register_key pays it from the owner to the config's
+// treasury before creating the record; a fee of zero is free.
+
+mod common;
+
+use key_registry::{KeyIndex, KeyRecord, KeyRegistryError};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+const FEE: u64 = 5_000_000;
+
+// `register` paying into `treasury` rather than the admin.
+fn paying(mut register: Instruction, treasury: &Pubkey) -> Instruction {
+    register.accounts[5].pubkey = *treasury;
+    register
+}
+
+#[tokio::test]
+async fn treasury_receives_exactly_the_fee() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let treasury = common::funded(&mut ctx, 1_000_000).await.pubkey();
+    let set_fee = common::set_fee(&ctx, FEE, treasury);
+    common::send(&mut ctx, &[set_fee], &[]).await.unwrap();
+    
+    let register = paying(common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(1)), &treasury);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    assert_eq!(ctx.banks_client.get_balance(treasury).await.unwrap(), 1_000_000 + FEE);
+    
+    // a fee of zero charges nothing
+    let set_fee = common::set_fee(&ctx, 0, treasury);
+    let register = paying(common::register_key(&ctx, &owner.pubkey(), "free", &common::test_key(2)), &treasury);
+    common::send(&mut ctx, &[set_fee, register], &[&owner]).await.unwrap();
+    assert_eq!(ctx.banks_client.get_balance(treasury).await.unwrap(), 1_000_000 + FEE);
+}
+
+#[tokio::test]
+async fn fee_the_owner_cannot_pay_fails() {
+    let mut ctx = common::start(common::program_test()).await;
+    let treasury = common::funded(&mut ctx, 1_000_000).await.pubkey();
+    let set_fee = common::set_fee(&ctx, FEE, treasury);
+    common::send(&mut ctx, &[set_fee], &[]).await.unwrap();
+    
+    // rent for the new accounts, and a lamport short of the fee
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let rent = rent.minimum_balance(KeyRecord::account_size(KeyRecord::HISTORY_LEN)) + rent.minimum_balance(8 + KeyIndex::LEN);
+    let owner = common::funded(&mut ctx, rent + FEE - 1).await;
+    let register = paying(common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(3)), &treasury);
+    let err = common::send(&mut ctx, &[register], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::FeeTransferFailed.into()));
+    assert_eq!(ctx.banks_client.get_balance(treasury).await.unwrap(), 1_000_000);
+}
@@ -0,0 +1,46 @@
+// A non-zero `registry_id` is a separate namespace: records and key index
+// entries in it never collide with the default registry's, so one owner can
+// register the same label, and even the same key, in both.
+
+mod common;
+
+use key_registry::registry_seed;
+use solana_sdk::signature::Signer;
+
+const STAGING: [u8; 8] = *b"staging\0";
+
+#[tokio::test]
+async fn same_owner_in_two_registries() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let default = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let staging = common::register_key_in(&ctx, &STAGING, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[default, staging], &[&owner]).await.unwrap();
+    
+    let default = common::key_record(&owner.pubkey(), "");
+    let staging = common::key_record_in(&STAGING, &owner.pubkey(), "");
+    assert_ne!(default, staging);
+    assert_ne!(common::key_index(&key), common::key_index_in(&STAGING, &key));
+    assert_eq!(common::fetch_record(&mut ctx, default).await.registry_id, common::REGISTRY);
+    assert_eq!(common::fetch_record(&mut ctx, staging).await.registry_id, STAGING);
+    // the default registry adds no seed, so its addresses predate registry ids
+    assert!(registry_seed(&common::REGISTRY).is_empty());
+}
+
+#[tokio::test]
+async fn registries_are_verified_separately() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (first, second) = (common::test_key(2), common::test_key(3));
+    let default = common::register_key(&ctx, &owner.pubkey(), "", &first);
+    let staging = common::register_key_in(&ctx, &STAGING, &owner.pubkey(), "", &second);
+    common::send(&mut ctx, &[default, staging], &[&owner]).await.unwrap();
+    
+    let (matched, _) = common::simulate(&mut ctx, common::verify_key_in(&STAGING, &owner.pubkey(), "", &second), &[]).await;
+    assert_eq!(matched, [1]);
+    let (matched, _) = common::simulate(&mut ctx, common::verify_key_in(&STAGING, &owner.pubkey(), "", &first), &[]).await;
+    assert_eq!(matched, [0]);
+    let (matched, _) = common::simulate(&mut ctx, common::verify_key(&owner.pubkey(), "", &first, None), &[]).await;
+    assert_eq!(matched, [1]);
+}
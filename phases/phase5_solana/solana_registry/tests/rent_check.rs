@@ -0,0 +1,29 @@
+// register_key checks up front that the owner can pay rent for the record and
+// its key index, failing with InsufficientRent rather than a system program
+// error from inside `init`.
+
+mod common;
+
+use key_registry::{KeyIndex, KeyRecord, KeyRegistryError};
+use solana_sdk::signature::Signer;
+use solana_sdk::system_instruction;
+
+#[tokio::test]
+async fn one_lamport_short_is_insufficient_rent() {
+    let mut ctx = common::start(common::program_test()).await;
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let required = rent.minimum_balance(KeyRecord::account_size(KeyRecord::HISTORY_LEN))
+        + rent.minimum_balance(8 + KeyIndex::LEN);
+    let owner = common::funded(&mut ctx, required - 1).await;
+    let key = common::test_key(1);
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let err = common::send(&mut ctx, &[register], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InsufficientRent.into()));
+    
+    // the missing lamport is all it takes
+    let top_up = system_instruction::transfer(&ctx.payer.pubkey(), &owner.pubkey(), 1);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[top_up, register], &[&owner]).await.unwrap();
+    assert_eq!(ctx.banks_client.get_balance(owner.pubkey()).await.unwrap(), 0);
+}
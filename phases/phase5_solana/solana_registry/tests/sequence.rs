@@ -0,0 +1,50 @@
+// Every mutation of a record bumps its `seq`, and the events it emits carry
+// the new value, so an indexer can order a record's history and spot gaps.
+
+mod common;
+
+use key_registry::{AuditExtended, KeyRegistered, KeyRevoked, KeyUpdated, KeyVerified};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn three_mutations_count_one_two_three() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [common::test_key(1), common::test_key(2)];
+    let address = common::key_record(&owner.pubkey(), "");
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &keys[0]);
+    let logs = common::send_logged(&mut ctx, &[register], &[&owner]).await;
+    assert_eq!(common::events::<KeyRegistered>(&logs)[0].seq, 1);
+    assert_eq!(common::events::<AuditExtended>(&logs)[0].seq, 1);
+    assert_eq!(common::fetch_record(&mut ctx, address).await.seq, 1);
+    
+    let update = common::update_key(&owner.pubkey(), "", &keys[0], &keys[1], 0);
+    let logs = common::send_logged(&mut ctx, &[update], &[&owner]).await;
+    assert_eq!(common::events::<KeyUpdated>(&logs)[0].seq, 2);
+    assert_eq!(common::fetch_record(&mut ctx, address).await.seq, 2);
+    
+    // verifying doesn't mutate, so it reports the current seq
+    let verify = common::verify_key(&owner.pubkey(), "", &keys[1], None);
+    let logs = common::send_logged(&mut ctx, &[verify], &[]).await;
+    assert_eq!(common::events::<KeyVerified>(&logs)[0].seq, 2);
+    
+    let revoke = common::revoke_key(&owner.pubkey(), "");
+    let logs = common::send_logged(&mut ctx, &[revoke], &[&owner]).await;
+    assert_eq!(common::events::<KeyRevoked>(&logs)[0].seq, 3);
+    assert_eq!(common::events::<AuditExtended>(&logs)[0].seq, 3);
+    assert_eq!(common::fetch_record(&mut ctx, address).await.seq, 3);
+}
+
+#[tokio::test]
+async fn records_count_separately() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let first = common::register_key(&ctx, &owner.pubkey(), "a", &common::test_key(3));
+    let second = common::register_key(&ctx, &owner.pubkey(), "b", &common::test_key(4));
+    let renew = common::renew_key(&owner.pubkey(), "a", 0);
+    common::send(&mut ctx, &[first, second, renew], &[&owner]).await.unwrap();
+    
+    assert_eq!(common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "a")).await.seq, 2);
+    assert_eq!(common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "b")).await.seq, 1);
+}
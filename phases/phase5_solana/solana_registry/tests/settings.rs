@@ -0,0 +1,103 @@
+// Management policy lives in the owner's `Settings`, not the key record:
+// verify_key never needs it, while the delegate, the expiry policy and the
+// usage policy are read from it by the instructions they govern.
+
+mod common;
+
+use key_registry::{KeyRecord, KeyRegistryError};
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn verify_does_not_need_settings() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let verify = || common::verify_key(&owner.pubkey(), "", &key, None);
+    let settings = common::settings(&owner.pubkey());
+    assert!(verify().accounts.iter().all(|meta| meta.pubkey != settings));
+    
+    // before the owner has Settings, and after
+    let (matched, _) = common::simulate(&mut ctx, verify(), &[]).await;
+    assert_eq!(matched, [1]);
+    let delegate = common::set_delegate(&owner.pubkey(), Keypair::new().pubkey());
+    let policy = common::set_key_policy(&owner.pubkey(), 3600, KeyRecord::USAGE_SIGN);
+    common::send(&mut ctx, &[delegate, policy], &[&owner]).await.unwrap();
+    assert!(ctx.banks_client.get_account(settings).await.unwrap().is_some());
+    let (matched, _) = common::simulate(&mut ctx, verify(), &[]).await;
+    assert_eq!(matched, [1]);
+}
+
+#[tokio::test]
+async fn delegate_comes_from_settings() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let delegate = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [common::test_key(2), common::test_key(3)];
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &keys[0]);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    // the owner's update, signed by the delegate instead
+    let update = || {
+        let mut update = common::update_key(&owner.pubkey(), "", &keys[0], &keys[1], 0);
+        update.accounts[0].pubkey = delegate.pubkey();
+        update
+    };
+    let err = common::send(&mut ctx, &[update()], &[&delegate]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    
+    let appoint = common::set_delegate(&owner.pubkey(), delegate.pubkey());
+    common::send(&mut ctx, &[appoint], &[&owner]).await.unwrap();
+    // the same transaction again, which must not come from the status cache
+    common::refresh_blockhash(&mut ctx).await;
+    common::send(&mut ctx, &[update()], &[&delegate]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.key_bytes(), keys[1].as_slice());
+}
+
+#[tokio::test]
+async fn usage_policy_bounds_set_flags() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(4));
+    let policy = common::set_key_policy(&owner.pubkey(), 0, KeyRecord::USAGE_SIGN | KeyRecord::USAGE_AUTH);
+    common::send(&mut ctx, &[register, policy], &[&owner]).await.unwrap();
+    
+    let allowed = common::set_usage(&owner.pubkey(), "", KeyRecord::USAGE_AUTH);
+    common::send(&mut ctx, &[allowed], &[&owner]).await.unwrap();
+    let denied = common::set_usage(&owner.pubkey(), "", KeyRecord::USAGE_SIGN | KeyRecord::USAGE_ENCRYPT);
+    let err = common::send(&mut ctx, &[denied], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::UsageNotPermitted.into()));
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.usage_flags, KeyRecord::USAGE_AUTH);
+}
+
+// Needs a clock, as the lifetime is measured from now.
+#[cfg(feature = "clock")]
+#[tokio::test]
+async fn lifetime_policy_bounds_expiry() {
+    const LIFETIME: i64 = 3600;
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(5));
+    let policy = common::set_key_policy(&owner.pubkey(), LIFETIME, 0);
+    common::send(&mut ctx, &[register, policy], &[&owner]).await.unwrap();
+    
+    let now = common::now(&mut ctx).await;
+    for expires_at in [0, now + LIFETIME + 1] {
+        let renew = common::renew_key(&owner.pubkey(), "", expires_at);
+        let err = common::send(&mut ctx, &[renew], &[&owner]).await.unwrap_err();
+        assert_eq!(common::error_code(err), Some(KeyRegistryError::LifetimeTooLong.into()));
+    }
+    let renew = common::renew_key(&owner.pubkey(), "", now + LIFETIME);
+    common::send(&mut ctx, &[renew], &[&owner]).await.unwrap();
+    
+    // lifting the policy allows a key that never expires again
+    let lift = common::set_key_policy(&owner.pubkey(), 0, 0);
+    let renew = common::renew_key(&owner.pubkey(), "", 0);
+    common::send(&mut ctx, &[lift, renew], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.expires_at, 0);
+}
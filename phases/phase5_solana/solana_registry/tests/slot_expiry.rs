@@ -0,0 +1,74 @@
+// A record can expire at a slot instead of a timestamp; set_slot_expiry and
+// renew_key each switch the record to their own mode. Needs a clock to read
+// the slot from.
+
+#![cfg(feature = "clock")]
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn key_expires_once_the_slot_passes() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let slot = ctx.banks_client.get_root_slot().await.unwrap();
+    let expiry = common::set_slot_expiry(&owner.pubkey(), "", slot + 10);
+    common::send(&mut ctx, &[expiry], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!((record.expires_at, record.expires_at_slot), (0, slot + 10));
+    
+    let verify = || common::verify_key(&owner.pubkey(), "", &key, None);
+    let (matched, _) = common::simulate(&mut ctx, verify(), &[]).await;
+    assert_eq!(matched, [1]);
+    ctx.warp_to_slot(slot + 10).unwrap();
+    let (matched, _) = common::simulate(&mut ctx, verify(), &[]).await;
+    assert_eq!(matched, [0]);
+    
+    // renewing moves the record back to timestamp expiry
+    let renew = common::renew_key(&owner.pubkey(), "", 0);
+    common::send(&mut ctx, &[renew], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.expires_at_slot, 0);
+    let (matched, _) = common::simulate(&mut ctx, verify(), &[]).await;
+    assert_eq!(matched, [1]);
+}
+
+#[tokio::test]
+async fn slot_expiry_clears_the_timestamp() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let now = common::now(&mut ctx).await;
+    let register = common::register_expiring_key(&ctx, &owner.pubkey(), "", &common::test_key(2), now + 3600);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let slot = ctx.banks_client.get_root_slot().await.unwrap();
+    let expiry = common::set_slot_expiry(&owner.pubkey(), "", slot + 100);
+    common::send(&mut ctx, &[expiry], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!((record.expires_at, record.expires_at_slot), (0, slot + 100));
+}
+
+#[tokio::test]
+async fn slot_expiry_must_be_ahead_and_unbounded() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(3));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let slot = ctx.banks_client.get_root_slot().await.unwrap();
+    let past = common::set_slot_expiry(&owner.pubkey(), "", slot);
+    let err = common::send(&mut ctx, &[past], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidExpiry.into()));
+    
+    // a lifetime policy is in seconds, which slots can't be checked against
+    let policy = common::set_key_policy(&owner.pubkey(), 3600, 0);
+    let expiry = common::set_slot_expiry(&owner.pubkey(), "", slot + 100);
+    let err = common::send(&mut ctx, &[policy, expiry], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::LifetimeTooLong.into()));
+}
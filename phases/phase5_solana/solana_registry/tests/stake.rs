@@ -0,0 +1,48 @@
+// With a stake set, register_key locks it in the new record on top of its
+// rent, and close_key hands it back to the owner with the rent.
+
+mod common;
+
+use key_registry::KeyRecord;
+use solana_sdk::signature::Signer;
+
+const STAKE: u64 = 10_000_000;
+
+#[tokio::test]
+async fn record_holds_rent_and_stake_until_closed() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let address = common::key_record(&owner.pubkey(), "");
+    let set_stake = common::set_stake(&ctx, STAKE);
+    common::send(&mut ctx, &[set_stake], &[]).await.unwrap();
+    
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let record_lamports = ctx.banks_client.get_balance(address).await.unwrap();
+    let len = KeyRecord::account_size(KeyRecord::HISTORY_LEN);
+    assert_eq!(record_lamports, rent.minimum_balance(len) + STAKE);
+    assert_eq!(common::fetch_record(&mut ctx, address).await.staked, STAKE);
+    
+    let index_lamports = ctx.banks_client.get_balance(common::key_index(&key)).await.unwrap();
+    let before = ctx.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    let close = common::close_key(&owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[close], &[&owner]).await.unwrap();
+    let after = ctx.banks_client.get_balance(owner.pubkey()).await.unwrap();
+    assert_eq!(after, before + record_lamports + index_lamports);
+}
+
+#[tokio::test]
+async fn zero_stake_locks_nothing() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let address = common::key_record(&owner.pubkey(), "");
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(2));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let rent = ctx.banks_client.get_rent().await.unwrap();
+    let len = KeyRecord::account_size(KeyRecord::HISTORY_LEN);
+    assert_eq!(ctx.banks_client.get_balance(address).await.unwrap(), rent.minimum_balance(len));
+    assert_eq!(common::fetch_record(&mut ctx, address).await.staked, 0);
+}
@@ -0,0 +1,66 @@
+// swap_keys exchanges two owners' keys in one instruction, signed by both:
+// each record ends up with the other's key and each reverse index entry
+// follows its key.
+
+mod common;
+
+use anchor_lang::AccountDeserialize;
+use key_registry::{KeyIndex, KeyRegistryError};
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn both_records_hold_the_others_key() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let other = common::funded(&mut ctx, 1_000_000_000).await;
+    let (key, other_key) = (common::test_key(1), common::test_key(2));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let other_register = common::register_key(&ctx, &other.pubkey(), "", &other_key);
+    common::send(&mut ctx, &[register, other_register], &[&owner, &other]).await.unwrap();
+    
+    let swap = common::swap_keys(&owner.pubkey(), &other.pubkey(), &key, &other_key);
+    common::send(&mut ctx, &[swap], &[&owner, &other]).await.unwrap();
+    let record = common::key_record(&owner.pubkey(), "");
+    let other_record = common::key_record(&other.pubkey(), "");
+    let swapped = common::fetch_record(&mut ctx, record).await;
+    assert_eq!(swapped.key_bytes(), other_key.as_slice());
+    assert_eq!(swapped.history[0][..32], key[..]);
+    let swapped = common::fetch_record(&mut ctx, other_record).await;
+    assert_eq!(swapped.key_bytes(), key.as_slice());
+    assert_eq!(swapped.history[0][..32], other_key[..]);
+    
+    for (public_key, owner, record) in [(&key, other.pubkey(), other_record), (&other_key, owner.pubkey(), record)] {
+        let account = ctx.banks_client.get_account(common::key_index(public_key)).await.unwrap().unwrap();
+        let index = KeyIndex::try_deserialize(&mut account.data.as_slice()).unwrap();
+        assert_eq!((index.owner, index.record), (owner, record));
+    }
+}
+
+#[tokio::test]
+async fn swap_needs_both_signatures() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let other = common::funded(&mut ctx, 1_000_000_000).await;
+    let (key, other_key) = (common::test_key(3), common::test_key(4));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let other_register = common::register_key(&ctx, &other.pubkey(), "", &other_key);
+    common::send(&mut ctx, &[register, other_register], &[&owner, &other]).await.unwrap();
+    
+    // the other owner listed, but not signing
+    let mut swap = common::swap_keys(&owner.pubkey(), &other.pubkey(), &key, &other_key);
+    swap.accounts[1].is_signer = false;
+    let err = common::send(&mut ctx, &[swap], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(anchor_lang::error::ErrorCode::AccountNotSigner.into()));
+    // a third party signing in the other owner's place
+    let stranger = common::funded(&mut ctx, 1_000_000_000).await;
+    let mut swap = common::swap_keys(&owner.pubkey(), &other.pubkey(), &key, &other_key);
+    swap.accounts[1].pubkey = stranger.pubkey();
+    let err = common::send(&mut ctx, &[swap], &[&owner, &stranger]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    
+    // nothing moved
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.key_bytes(), key.as_slice());
+    let record = common::fetch_record(&mut ctx, common::key_record(&other.pubkey(), "")).await;
+    assert_eq!(record.key_bytes(), other_key.as_slice());
+}
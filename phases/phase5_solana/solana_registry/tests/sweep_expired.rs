@@ -0,0 +1,78 @@
+// The admin's sweep_expired closes the expired records among those it's
+// given, refunding each owner, and skips the rest. Needs a clock, since
+// without one nothing ever expires.
+
+#![cfg(feature = "clock")]
+
+mod common;
+
+use anchor_lang::AnchorDeserialize;
+use key_registry::{KeyRegistryError, BATCH_APPLIED, BATCH_FAILED, BATCH_SKIPPED};
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn only_expired_records_are_closed() {
+    let mut ctx = common::start(common::program_test()).await;
+    let expiring = common::funded(&mut ctx, 1_000_000_000).await;
+    let lasting = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = vec![common::test_key(1), common::test_key(2)];
+    let now = common::now(&mut ctx).await;
+    let first = common::register_expiring_key(&ctx, &expiring.pubkey(), "", &keys[0], now + 60);
+    let second = common::register_key(&ctx, &lasting.pubkey(), "", &keys[1]);
+    common::send(&mut ctx, &[first, second], &[&expiring, &lasting]).await.unwrap();
+    common::advance_clock(&mut ctx, 61).await;
+    
+    let record = common::key_record(&expiring.pubkey(), "");
+    let refund = ctx.banks_client.get_balance(record).await.unwrap()
+        + ctx.banks_client.get_balance(common::key_index(&keys[0])).await.unwrap();
+    let before = ctx.banks_client.get_balance(expiring.pubkey()).await.unwrap();
+    let owners = [expiring.pubkey(), lasting.pubkey()];
+    let sweep = common::sweep_expired(&ctx, &owners, &keys, false);
+    let logs = common::send_logged(&mut ctx, &[sweep], &[]).await;
+    assert!(logs.iter().any(|log| log.contains("Swept 1 expired key records of 2")));
+    
+    assert!(ctx.banks_client.get_account(record).await.unwrap().is_none());
+    assert!(ctx.banks_client.get_account(common::key_index(&keys[0])).await.unwrap().is_none());
+    assert_eq!(ctx.banks_client.get_balance(expiring.pubkey()).await.unwrap(), before + refund);
+    let kept = common::fetch_record(&mut ctx, common::key_record(&lasting.pubkey(), "")).await;
+    assert_eq!(kept.key_bytes(), keys[1].as_slice());
+    
+    // the lasting record alone, reported per entry
+    let sweep = common::sweep_expired(&ctx, &owners[1..], &keys[1..], false);
+    let (data, _) = common::simulate(&mut ctx, sweep, &[]).await;
+    assert_eq!(Vec::<u8>::try_from_slice(&data).unwrap(), [BATCH_SKIPPED]);
+}
+
+#[tokio::test]
+async fn a_malformed_entry_fails_unless_skipped() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = vec![common::test_key(3)];
+    let now = common::now(&mut ctx).await;
+    let register = common::register_expiring_key(&ctx, &owner.pubkey(), "", &keys[0], now + 60);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    common::advance_clock(&mut ctx, 61).await;
+    
+    // the refund going to someone other than the owner
+    let mut sweep = common::sweep_expired(&ctx, &[owner.pubkey()], &keys, false);
+    sweep.accounts[4].pubkey = Keypair::new().pubkey();
+    let err = common::send(&mut ctx, &[sweep.clone()], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidRecord.into()));
+    
+    // with continue_on_error, the same entry done right still closes
+    let mut lenient = common::sweep_expired(&ctx, &[owner.pubkey(); 2], &[keys[0].clone(), keys[0].clone()], true);
+    lenient.accounts[4] = sweep.accounts[4].clone();
+    let (data, _) = common::simulate(&mut ctx, lenient, &[]).await;
+    let invalid_record = (u32::from(KeyRegistryError::InvalidRecord) - anchor_lang::error::ERROR_CODE_OFFSET) as u8;
+    assert_eq!(Vec::<u8>::try_from_slice(&data).unwrap(), [BATCH_FAILED + invalid_record, BATCH_APPLIED]);
+}
+
+#[tokio::test]
+async fn only_the_admin_sweeps() {
+    let mut ctx = common::start(common::program_test()).await;
+    let stranger = Keypair::new();
+    let mut sweep = common::sweep_expired(&ctx, &[], &[], false);
+    sweep.accounts[0].pubkey = stranger.pubkey();
+    let err = common::send(&mut ctx, &[sweep], &[&stranger]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+}
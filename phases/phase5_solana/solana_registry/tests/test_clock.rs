@@ -0,0 +1,104 @@
+// Expiry against the `set_test_clock` override, which `test-clock` builds
+// read through `config_now`:
+//     cargo test --features test-clock --test test_clock
+// Default builds only check that the override is refused.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+#[cfg(feature = "test-clock")]
+use key_registry::KeyRecord;
+#[cfg(feature = "test-clock")]
+use solana_sdk::signature::Signer;
+
+#[cfg(feature = "test-clock")]
+#[tokio::test]
+async fn key_expires_at_the_overridden_time() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let expires_at = common::now(&mut ctx).await + 3600;
+    let register = common::register_expiring_key(&ctx, &owner.pubkey(), "", &key, expires_at);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let verify = || common::verify_key(&owner.pubkey(), "", &key, None);
+    assert_eq!(common::simulate(&mut ctx, verify(), &[]).await.0, [1]);
+    
+    // the last second before expiry still verifies, the expiry itself doesn't
+    let clock = common::set_test_clock(&ctx, expires_at - 1);
+    common::send(&mut ctx, &[clock], &[]).await.unwrap();
+    assert_eq!(common::simulate(&mut ctx, verify(), &[]).await.0, [1]);
+    let clock = common::set_test_clock(&ctx, expires_at);
+    common::send(&mut ctx, &[clock], &[]).await.unwrap();
+    assert_eq!(common::simulate(&mut ctx, verify(), &[]).await.0, [0]);
+    
+    // 0 goes back to the real clock
+    let clock = common::set_test_clock(&ctx, 0);
+    common::send(&mut ctx, &[clock], &[]).await.unwrap();
+    assert_eq!(common::simulate(&mut ctx, verify(), &[]).await.0, [1]);
+}
+
+#[cfg(feature = "test-clock")]
+#[tokio::test]
+async fn renewal_is_checked_against_the_overridden_time() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(2);
+    let expires_at = common::now(&mut ctx).await + 3600;
+    let register = common::register_expiring_key(&ctx, &owner.pubkey(), "", &key, expires_at);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let later = expires_at + 86_400;
+    let clock = common::set_test_clock(&ctx, later);
+    common::send(&mut ctx, &[clock], &[]).await.unwrap();
+    // in the future by the real clock, already past by the override
+    let renew = common::renew_key(&owner.pubkey(), "", later - 1);
+    let err = common::send(&mut ctx, &[renew], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidExpiry.into()));
+    
+    let renew = common::renew_key(&owner.pubkey(), "", later + 3600);
+    common::send(&mut ctx, &[renew], &[&owner]).await.unwrap();
+    let verify = common::verify_key(&owner.pubkey(), "", &key, None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+}
+
+#[cfg(feature = "test-clock")]
+#[tokio::test]
+async fn registration_rejects_expiry_before_the_overridden_time() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let later = common::now(&mut ctx).await + 86_400;
+    let clock = common::set_test_clock(&ctx, later);
+    common::send(&mut ctx, &[clock], &[]).await.unwrap();
+    
+    let register = common::register_expiring_key(&ctx, &owner.pubkey(), "", &common::test_key(3), later - 1);
+    let err = common::send(&mut ctx, &[register], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidExpiry.into()));
+}
+
+#[cfg(feature = "test-clock")]
+#[tokio::test]
+async fn stored_times_follow_the_override() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let expires_at = common::now(&mut ctx).await + 3600;
+    let register = common::register_expiring_key(&ctx, &owner.pubkey(), "", &common::test_key(4), expires_at);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let clock = common::set_test_clock(&ctx, expires_at);
+    common::send(&mut ctx, &[clock], &[]).await.unwrap();
+    let touch = common::touch(&owner.pubkey(), "");
+    common::send(&mut ctx, &[touch], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, common::key_record(&owner.pubkey(), "")).await;
+    assert_eq!(record.updated_at, expires_at);
+    assert_ne!(record.status & KeyRecord::STATUS_EXPIRED, 0);
+}
+
+#[cfg(not(feature = "test-clock"))]
+#[tokio::test]
+async fn override_needs_the_feature() {
+    let mut ctx = common::start(common::program_test()).await;
+    let clock = common::set_test_clock(&ctx, 1);
+    let err = common::send(&mut ctx, &[clock], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::TestClockDisabled.into()));
+}
@@ -0,0 +1,37 @@
+// transfer_ownership hands a record to a new wallet in place: the PDA stays
+// seeded by the registrant, and from then on only the new owner manages it.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn new_owner_can_update_the_key() {
+    let mut ctx = common::start(common::program_test()).await;
+    let old_owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let new_owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [common::test_key(1), common::test_key(2), common::test_key(3)];
+    let record = common::key_record(&old_owner.pubkey(), "");
+    let register = common::register_key(&ctx, &old_owner.pubkey(), "", &keys[0]);
+    common::send(&mut ctx, &[register], &[&old_owner]).await.unwrap();
+    
+    let transfer = common::transfer_ownership(&old_owner.pubkey(), &old_owner.pubkey(), "", &keys[0], new_owner.pubkey());
+    common::send(&mut ctx, &[transfer], &[&old_owner]).await.unwrap();
+    assert_eq!(common::fetch_record(&mut ctx, record).await.owner, new_owner.pubkey());
+    
+    let update = common::update_key_as(&new_owner.pubkey(), &old_owner.pubkey(), "", &keys[0], &keys[1], 0);
+    common::send(&mut ctx, &[update], &[&new_owner]).await.unwrap();
+    let updated = common::fetch_record(&mut ctx, record).await;
+    assert_eq!(updated.key_bytes(), keys[1].as_slice());
+    assert_eq!(updated.registrant, old_owner.pubkey());
+    
+    // the old owner has no say any more, over the key or the record
+    let mut update = common::update_key_as(&new_owner.pubkey(), &old_owner.pubkey(), "", &keys[1], &keys[2], 0);
+    update.accounts[0].pubkey = old_owner.pubkey();
+    let err = common::send(&mut ctx, &[update], &[&old_owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    let transfer = common::transfer_ownership(&old_owner.pubkey(), &old_owner.pubkey(), "", &keys[1], old_owner.pubkey());
+    let err = common::send(&mut ctx, &[transfer], &[&old_owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+}
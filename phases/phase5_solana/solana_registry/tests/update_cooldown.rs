@@ -0,0 +1,81 @@
+// The owner's `update_cooldown` bounds how often a record's key can change,
+// on every path that replaces it. Needs a clock: without one every
+// `updated_at` reads 0 and the cooldown never runs out.
+
+#![cfg(feature = "clock")]
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::Signer;
+
+const COOLDOWN: i64 = 3600;
+
+#[tokio::test]
+async fn update_waits_out_the_cooldown() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let keys = [common::test_key(1), common::test_key(2), common::test_key(3)];
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &keys[0]);
+    let cooldown = common::set_update_cooldown(&owner.pubkey(), COOLDOWN);
+    common::send(&mut ctx, &[register, cooldown], &[&owner]).await.unwrap();
+    
+    // the registration itself counts as the last change
+    let update = || common::update_key(&owner.pubkey(), "", &keys[0], &keys[1], 0);
+    let err = common::send(&mut ctx, &[update()], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::UpdateTooSoon.into()));
+    
+    common::advance_clock(&mut ctx, COOLDOWN).await;
+    common::send(&mut ctx, &[update()], &[&owner]).await.unwrap();
+    let update = common::update_key(&owner.pubkey(), "", &keys[1], &keys[2], 0);
+    let err = common::send(&mut ctx, &[update], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::UpdateTooSoon.into()));
+}
+
+#[tokio::test]
+async fn rotating_upsert_and_finalize_are_limited_too() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (first, second) = (common::test_key(4), common::test_key(5));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &first);
+    let cooldown = common::set_update_cooldown(&owner.pubkey(), COOLDOWN);
+    common::send(&mut ctx, &[register, cooldown], &[&owner]).await.unwrap();
+    
+    let upsert = common::upsert_key(&ctx, &owner.pubkey(), "", &second, Some(&first));
+    let err = common::send(&mut ctx, &[upsert], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::UpdateTooSoon.into()));
+    // upserting the same key changes nothing, so it isn't limited
+    let upsert = common::upsert_key(&ctx, &owner.pubkey(), "", &first, None);
+    common::send(&mut ctx, &[upsert], &[&owner]).await.unwrap();
+    
+    let activate_after = common::now(&mut ctx).await + 1;
+    let propose = common::propose_update(&owner.pubkey(), "", &second, activate_after);
+    common::send(&mut ctx, &[propose], &[&owner]).await.unwrap();
+    common::advance_clock(&mut ctx, 1).await;
+    let finalize = || common::finalize_update(&owner.pubkey(), "", &first, &second);
+    let err = common::send(&mut ctx, &[finalize()], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::UpdateTooSoon.into()));
+    
+    common::advance_clock(&mut ctx, COOLDOWN).await;
+    common::send(&mut ctx, &[finalize()], &[&owner]).await.unwrap();
+}
+
+#[tokio::test]
+async fn swap_checks_both_owners() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let other = common::funded(&mut ctx, 1_000_000_000).await;
+    let (key, other_key) = (common::test_key(6), common::test_key(7));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let other_register = common::register_key(&ctx, &other.pubkey(), "", &other_key);
+    // only the other side has a cooldown
+    let cooldown = common::set_update_cooldown(&other.pubkey(), COOLDOWN);
+    common::send(&mut ctx, &[register, other_register, cooldown], &[&owner, &other]).await.unwrap();
+    
+    let swap = || common::swap_keys(&owner.pubkey(), &other.pubkey(), &key, &other_key);
+    let err = common::send(&mut ctx, &[swap()], &[&owner, &other]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::UpdateTooSoon.into()));
+    
+    common::advance_clock(&mut ctx, COOLDOWN).await;
+    common::send(&mut ctx, &[swap()], &[&owner, &other]).await.unwrap();
+}
@@ -0,0 +1,65 @@
+// upsert_key registers a record that doesn't exist yet and rotates one that
+// does, from the same instruction; an existing record is only updated for
+// its owner, never re-initialized.
+
+mod common;
+
+use key_registry::{KeyRecord, KeyRegistryError};
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn creates_then_updates() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (first, second) = (common::test_key(1), common::test_key(2));
+    let address = common::key_record(&owner.pubkey(), "");
+    
+    let create = common::upsert_key(&ctx, &owner.pubkey(), "", &first, None);
+    common::send(&mut ctx, &[create], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, address).await;
+    assert_eq!(record.owner, owner.pubkey());
+    assert_eq!(record.version, KeyRecord::CURRENT_VERSION);
+    assert_eq!(record.key_bytes(), first.as_slice());
+    
+    let update = common::upsert_key(&ctx, &owner.pubkey(), "", &second, Some(&first));
+    common::send(&mut ctx, &[update], &[&owner]).await.unwrap();
+    let record = common::fetch_record(&mut ctx, address).await;
+    assert_eq!(record.owner, owner.pubkey());
+    assert_eq!(record.key_bytes(), second.as_slice());
+    assert_eq!(record.history[0][..32], first[..]);
+    assert!(ctx.banks_client.get_account(common::key_index(&first)).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn rotation_needs_the_old_key_index() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (first, second) = (common::test_key(3), common::test_key(4));
+    let create = common::upsert_key(&ctx, &owner.pubkey(), "", &first, None);
+    common::send(&mut ctx, &[create], &[&owner]).await.unwrap();
+    
+    let update = common::upsert_key(&ctx, &owner.pubkey(), "", &second, None);
+    let err = common::send(&mut ctx, &[update], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidRecord.into()));
+    // and upserting the current key is a no-op that mustn't pass it
+    let same = common::upsert_key(&ctx, &owner.pubkey(), "", &first, Some(&first));
+    let err = common::send(&mut ctx, &[same], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidRecord.into()));
+}
+
+#[tokio::test]
+async fn existing_record_answers_to_its_owner() {
+    let mut ctx = common::start(common::program_test()).await;
+    let registrant = common::funded(&mut ctx, 1_000_000_000).await;
+    let (first, second) = (common::test_key(5), common::test_key(6));
+    let create = common::upsert_key(&ctx, &registrant.pubkey(), "", &first, None);
+    let transfer = common::transfer_ownership(&registrant.pubkey(), &registrant.pubkey(), "", &first, Keypair::new().pubkey());
+    common::send(&mut ctx, &[create, transfer], &[&registrant]).await.unwrap();
+    
+    // the record is still at the registrant's address, but no longer theirs
+    let update = common::upsert_key(&ctx, &registrant.pubkey(), "", &second, Some(&first));
+    let err = common::send(&mut ctx, &[update], &[&registrant]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::Unauthorized.into()));
+    let record = common::fetch_record(&mut ctx, common::key_record(&registrant.pubkey(), "")).await;
+    assert_eq!(record.key_bytes(), first.as_slice());
+}
@@ -0,0 +1,84 @@
+// An owner's verifier allowlist gates verify_key: listed verifiers get an
+// answer, anyone else VerifierNotAllowed, and clearing the list makes the
+// record public again.
+
+mod common;
+
+use anchor_lang::AnchorDeserialize;
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn listed_and_unlisted_verifiers() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let (listed, unlisted) = (Keypair::new(), Keypair::new());
+    let key = common::test_key(1);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    // no list yet: anyone, signer or not
+    let (matched, _) = common::simulate(&mut ctx, common::verify_key(&owner.pubkey(), "", &key, None), &[]).await;
+    assert_eq!(matched, [1]);
+    
+    let allow = common::set_verifier_allowlist(&owner.pubkey(), "", vec![listed.pubkey()]);
+    common::send(&mut ctx, &[allow], &[&owner]).await.unwrap();
+    
+    let verify = common::verify_key(&owner.pubkey(), "", &key, Some(&listed.pubkey()));
+    let (matched, _) = common::simulate(&mut ctx, verify, &[&listed]).await;
+    assert_eq!(matched, [1]);
+    let verify = common::verify_key(&owner.pubkey(), "", &common::test_key(2), Some(&listed.pubkey()));
+    let (matched, _) = common::simulate(&mut ctx, verify, &[&listed]).await;
+    assert_eq!(matched, [0]);
+    
+    let verify = common::verify_key(&owner.pubkey(), "", &key, Some(&unlisted.pubkey()));
+    let err = common::send(&mut ctx, &[verify], &[&unlisted]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::VerifierNotAllowed.into()));
+    let verify = common::verify_key(&owner.pubkey(), "", &key, None);
+    let err = common::send(&mut ctx, &[verify], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::VerifierNotAllowed.into()));
+    
+    let clear = common::set_verifier_allowlist(&owner.pubkey(), "", vec![]);
+    common::send(&mut ctx, &[clear], &[&owner]).await.unwrap();
+    let verify = common::verify_key(&owner.pubkey(), "", &key, Some(&unlisted.pubkey()));
+    let (matched, _) = common::simulate(&mut ctx, verify, &[&unlisted]).await;
+    assert_eq!(matched, [1]);
+}
+
+#[tokio::test]
+async fn at_most_four_verifiers() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(3));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let verifiers = (0..5).map(|_| Keypair::new().pubkey()).collect();
+    let allow = common::set_verifier_allowlist(&owner.pubkey(), "", verifiers);
+    let err = common::send(&mut ctx, &[allow], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::TooManyVerifiers.into()));
+}
+
+#[tokio::test]
+async fn history_and_metadata_are_gated_too() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let listed = Keypair::new();
+    let (first, second) = (common::test_key(4), common::test_key(5));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &first);
+    let update = common::update_key(&owner.pubkey(), "", &first, &second, 0);
+    let allow = common::set_verifier_allowlist(&owner.pubkey(), "", vec![listed.pubkey()]);
+    common::send(&mut ctx, &[register, update, allow], &[&owner]).await.unwrap();
+    
+    for read in [common::get_history(&owner.pubkey(), "", None), common::get_metadata(&owner.pubkey(), "", None)] {
+        let err = common::send(&mut ctx, &[read], &[]).await.unwrap_err();
+        assert_eq!(common::error_code(err), Some(KeyRegistryError::VerifierNotAllowed.into()));
+    }
+    
+    let history = common::get_history(&owner.pubkey(), "", Some(&listed.pubkey()));
+    let (data, _) = common::simulate(&mut ctx, history, &[&listed]).await;
+    assert_eq!(Vec::<Vec<u8>>::try_from_slice(&data).unwrap(), vec![first]);
+    let metadata = common::get_metadata(&owner.pubkey(), "", Some(&listed.pubkey()));
+    let (data, _) = common::simulate(&mut ctx, metadata, &[&listed]).await;
+    let (_, _, uri) = <(i64, i64, String)>::try_from_slice(&data).unwrap();
+    assert_eq!(uri, "");
+}
@@ -0,0 +1,85 @@
+// verify_delegated hands the record's key to the verifier program the record
+// names (set_verifier) and reports its answer; a program the record doesn't
+// name can't stand in for it.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_program_test::{processor, ProgramTest};
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::entrypoint::ProgramResult;
+use solana_sdk::program::set_return_data;
+use solana_sdk::program_error::ProgramError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+// Accepts when the caller's data after the key repeats the key, i.e. the
+// relying party presented the stored key; the record must come read-only.
+fn mock_verifier(_program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let record = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+    if *record.owner != key_registry::ID || record.is_writable {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let key_len = *data.get(1).ok_or(ProgramError::InvalidInstructionData)? as usize;
+    let (key, presented) = data[2..].split_at(key_len);
+    set_return_data(&[(key == presented) as u8]);
+    Ok(())
+}
+
+fn program_test(verifier_ids: &[Pubkey]) -> ProgramTest {
+    let mut test = common::program_test();
+    for id in verifier_ids {
+        test.add_program("mock_verifier", *id, processor!(mock_verifier));
+    }
+    test
+}
+
+#[tokio::test]
+async fn verifier_program_answers_for_the_key() {
+    let verifier_id = Pubkey::new_unique();
+    let mut ctx = common::start(program_test(&[verifier_id])).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let set = common::set_verifier(&owner.pubkey(), "", Some(verifier_id));
+    common::send(&mut ctx, &[register, set], &[&owner]).await.unwrap();
+    
+    let accepted = common::verify_delegated(&owner.pubkey(), "", verifier_id, &key);
+    let (matched, _) = common::simulate(&mut ctx, accepted, &[]).await;
+    assert_eq!(matched, [1]);
+    let rejected = common::verify_delegated(&owner.pubkey(), "", verifier_id, &common::test_key(2));
+    let (matched, _) = common::simulate(&mut ctx, rejected, &[]).await;
+    assert_eq!(matched, [0]);
+    
+    // a revoked key is false without asking
+    let revoke = common::revoke_key(&owner.pubkey(), "");
+    common::send(&mut ctx, &[revoke], &[&owner]).await.unwrap();
+    let accepted = common::verify_delegated(&owner.pubkey(), "", verifier_id, &key);
+    let (matched, _) = common::simulate(&mut ctx, accepted, &[]).await;
+    assert_eq!(matched, [0]);
+}
+
+#[tokio::test]
+async fn only_the_named_program_is_asked() {
+    let (named, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let mut ctx = common::start(program_test(&[named, other])).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(3);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    // before set_verifier, and with another program once it's set
+    let ask_other = || common::verify_delegated(&owner.pubkey(), "", other, &key);
+    let err = common::send(&mut ctx, &[ask_other()], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidVerifierProgram.into()));
+    let set = common::set_verifier(&owner.pubkey(), "", Some(named));
+    common::send(&mut ctx, &[set], &[&owner]).await.unwrap();
+    common::refresh_blockhash(&mut ctx).await;
+    let err = common::send(&mut ctx, &[ask_other()], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidVerifierProgram.into()));
+    
+    // the registry can't name itself
+    let set = common::set_verifier(&owner.pubkey(), "", Some(key_registry::ID));
+    let err = common::send(&mut ctx, &[set], &[&owner]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::InvalidVerifierProgram.into()));
+}
@@ -0,0 +1,31 @@
+// verify_key_counted is verify_key that also counts its matches in the
+// record's `verify_count`; verify_key itself stays read-only.
+
+mod common;
+
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn counts_matching_verifications() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    let record = common::key_record(&owner.pubkey(), "");
+    
+    for _ in 0..3 {
+        let verify = common::verify_key_counted(&owner.pubkey(), "", &key);
+        common::send(&mut ctx, &[verify], &[]).await.unwrap();
+        common::refresh_blockhash(&mut ctx).await;
+    }
+    assert_eq!(common::fetch_record(&mut ctx, record).await.verify_count, 3);
+    
+    // a mismatch, or a plain verify_key, leaves the count alone
+    let wrong = common::verify_key_counted(&owner.pubkey(), "", &common::test_key(2));
+    let plain = common::verify_key(&owner.pubkey(), "", &key, None);
+    common::send(&mut ctx, &[wrong, plain], &[]).await.unwrap();
+    assert_eq!(common::fetch_record(&mut ctx, record).await.verify_count, 3);
+    let verify = common::verify_key_counted(&owner.pubkey(), "", &key);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+}
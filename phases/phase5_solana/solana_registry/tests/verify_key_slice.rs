@@ -0,0 +1,44 @@
+// verify_key_slice checks the key-length window of a larger blob at an
+// offset against the stored key, refusing windows that run off the end.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use solana_sdk::signature::Signer;
+
+#[tokio::test]
+async fn window_at_the_offset_is_compared() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(1);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    // a header, the key, then a trailer
+    let blob = [&[0xaa; 16][..], &key, &[0xbb; 8]].concat();
+    for (offset, expected) in [(16, 1), (15, 0), (17, 0)] {
+        let verify = common::verify_key_slice(&owner.pubkey(), "", &blob, offset);
+        let (matched, _) = common::simulate(&mut ctx, verify, &[]).await;
+        assert_eq!(matched, [expected], "offset {offset}");
+    }
+    // the key ending exactly at the end of the blob
+    let verify = common::verify_key_slice(&owner.pubkey(), "", &blob[..48], 16);
+    let (matched, _) = common::simulate(&mut ctx, verify, &[]).await;
+    assert_eq!(matched, [1]);
+}
+
+#[tokio::test]
+async fn window_past_the_end_is_rejected() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = common::test_key(2);
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let blob = [&[0u8; 4][..], &key].concat();
+    for offset in [5, blob.len() as u32, u32::MAX] {
+        let verify = common::verify_key_slice(&owner.pubkey(), "", &blob, offset);
+        let err = common::send(&mut ctx, &[verify], &[]).await.unwrap_err();
+        assert_eq!(common::error_code(err), Some(KeyRegistryError::OffsetOutOfBounds.into()));
+    }
+}
@@ -0,0 +1,35 @@
+// VerifyKey derives the record's address from the owner and label the caller
+// passes, so a record that isn't at that address is refused, even one that is
+// a genuine copy.
+
+mod common;
+
+use anchor_lang::error::ErrorCode;
+use solana_sdk::signature::{Keypair, Signer};
+
+#[tokio::test]
+async fn rejects_a_record_at_the_wrong_address() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let other = common::funded(&mut ctx, 1_000_000_000).await;
+    let (key, other_key) = (common::test_key(1), common::test_key(2));
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &key);
+    let other_register = common::register_key(&ctx, &other.pubkey(), "", &other_key);
+    common::send(&mut ctx, &[register, other_register], &[&owner, &other]).await.unwrap();
+    
+    // a byte-for-byte copy of the owner's record, elsewhere
+    let real = common::key_record(&owner.pubkey(), "");
+    let spoof = Keypair::new().pubkey();
+    let account = ctx.banks_client.get_account(real).await.unwrap().unwrap();
+    ctx.set_account(&spoof, &account.into());
+    
+    // the copy, or someone else's genuine record, standing in for the owner's
+    for stand_in in [spoof, common::key_record(&other.pubkey(), "")] {
+        let mut verify = common::verify_key(&owner.pubkey(), "", &key, None);
+        verify.accounts[0].pubkey = stand_in;
+        let err = common::send(&mut ctx, &[verify], &[]).await.unwrap_err();
+        assert_eq!(common::error_code(err), Some(ErrorCode::ConstraintSeeds.into()));
+    }
+    let verify = common::verify_key(&owner.pubkey(), "", &key, None);
+    assert_eq!(common::simulate(&mut ctx, verify, &[]).await.0, [1]);
+}
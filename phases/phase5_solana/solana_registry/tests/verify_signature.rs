@@ -0,0 +1,71 @@
+// verify_signature checks a signature by the record's key the way its key
+// type allows: Ed25519 through the preceding Ed25519 instruction, secp256k1
+// by recovering the signer. Types that can't sign are UnsupportedKeyType.
+
+mod common;
+
+use key_registry::KeyRegistryError;
+use libsecp256k1::{Message, PublicKey, SecretKey};
+use solana_sdk::keccak;
+use solana_sdk::signature::{Keypair, Signer};
+
+const MESSAGE: &[u8] = b"sign in to example.org";
+
+#[tokio::test]
+async fn ed25519_signatures_go_through_the_precompile() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let key = Keypair::new();
+    let signature = common::sign(&key, owner.pubkey().as_ref());
+    let proof = common::ed25519_verify(&key.pubkey(), owner.pubkey().as_ref(), signature);
+    let register = common::register_ed25519_key(&ctx, &owner.pubkey(), "", &key.pubkey(), signature);
+    common::send(&mut ctx, &[proof, register], &[&owner]).await.unwrap();
+    
+    let signature = common::sign(&key, MESSAGE);
+    let precompile = common::ed25519_verify(&key.pubkey(), MESSAGE, signature);
+    let verify = common::verify_signature(&owner.pubkey(), "", MESSAGE, signature, 0);
+    let logs = common::send_logged(&mut ctx, &[precompile, verify], &[]).await;
+    assert!(logs.iter().any(|line| line.contains("Ed25519 signature verified")), "{logs:?}");
+    
+    // a signature by another key, checked by the precompile, isn't the owner's
+    let other = Keypair::new();
+    let signature = common::sign(&other, MESSAGE);
+    let precompile = common::ed25519_verify(&other.pubkey(), MESSAGE, signature);
+    let verify = common::verify_signature(&owner.pubkey(), "", MESSAGE, signature, 0);
+    let err = common::send(&mut ctx, &[precompile, verify], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::SignatureVerificationFailed.into()));
+}
+
+#[tokio::test]
+async fn secp256k1_signers_are_recovered() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let secret = SecretKey::parse(&[7u8; 32]).unwrap();
+    let public_key = PublicKey::from_secret_key(&secret).serialize_compressed();
+    let register = common::register_secp256k1_key(&ctx, &owner.pubkey(), "", &public_key);
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let hash = Message::parse(&keccak::hash(MESSAGE).to_bytes());
+    let (signature, recovery_id) = libsecp256k1::sign(&hash, &secret);
+    let (signature, recovery_id) = (signature.serialize(), recovery_id.serialize());
+    let verify = common::verify_signature(&owner.pubkey(), "", MESSAGE, signature, recovery_id);
+    let logs = common::send_logged(&mut ctx, &[verify], &[]).await;
+    assert!(logs.iter().any(|line| line.contains("Secp256k1 signature verified")), "{logs:?}");
+    
+    // the same signature over another message recovers someone else
+    let verify = common::verify_signature(&owner.pubkey(), "", b"another message", signature, recovery_id);
+    let err = common::send(&mut ctx, &[verify], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::SignatureVerificationFailed.into()));
+}
+
+#[tokio::test]
+async fn keys_that_cannot_sign_are_unsupported() {
+    let mut ctx = common::start(common::program_test()).await;
+    let owner = common::funded(&mut ctx, 1_000_000_000).await;
+    let register = common::register_key(&ctx, &owner.pubkey(), "", &common::test_key(1));
+    common::send(&mut ctx, &[register], &[&owner]).await.unwrap();
+    
+    let verify = common::verify_signature(&owner.pubkey(), "", MESSAGE, [1u8; 64], 0);
+    let err = common::send(&mut ctx, &[verify], &[]).await.unwrap_err();
+    assert_eq!(common::error_code(err), Some(KeyRegistryError::UnsupportedKeyType.into()));
+}
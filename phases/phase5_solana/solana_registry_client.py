@@ -29,7 +29,7 @@ class SolanaKeyRegistryClient:
         # Create a client for a given RPC endpoint and program id.
         self.rpc_url = rpc_url
         self.connection = solana_api.Client(rpc_url)
-        self.program_id = Pubkey.from_string(program_id or "KeyRegistry11111111111111111111111111111")
+        self.program_id = Pubkey.from_string(program_id or "asdmk9ot5XzRsYcqezCSGft923CuGJaJS3nR5wfJcpm")
         
         self.idl = self._load_idl()
         